@@ -0,0 +1,315 @@
+use std::f64::consts::PI;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use glam::{DQuat, DVec3};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::controller::{direction, yaw_pitch, PITCH_LIMIT};
+
+/// An orbit view: the eye sits `distance` away from `target`, placed by
+/// yaw/pitch. Angles are in degrees so files stay readable.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraView {
+    pub yaw: f64,
+    pub pitch: f64,
+    pub distance: f64,
+    pub target: [f64; 3],
+}
+
+impl CameraView {
+    pub fn from_camera(camera: &Camera) -> Self {
+        let distance = camera.distance();
+        let (yaw, pitch) = yaw_pitch((camera.eye - camera.target) / distance);
+        Self {
+            yaw: yaw.to_degrees(),
+            pitch: pitch.to_degrees(),
+            distance,
+            target: camera.target.to_array(),
+        }
+    }
+
+    /// False for views `interpolate` can't handle: a distance that isn't
+    /// positive, or any non-finite value.
+    fn is_valid(&self) -> bool {
+        let mut values = [self.yaw, self.pitch, self.distance].into_iter().chain(self.target);
+        self.distance > 0.0 && values.all(f64::is_finite)
+    }
+
+    fn eye_dir(&self) -> DVec3 {
+        direction(self.yaw.to_radians(), self.pitch.to_radians())
+    }
+
+    fn target(&self) -> DVec3 {
+        DVec3::from_array(self.target)
+    }
+
+    /// Angle the eye travels around the target on the way to `other`.
+    fn arc(&self, other: &CameraView) -> f64 {
+        self.eye_dir().angle_between(other.eye_dir())
+    }
+}
+
+/// Time remapping applied within one path segment.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t), // smoothstep
+        }
+    }
+}
+
+/// Camera pose between two views at `t` in 0..=1. The eye follows the great
+/// circle around the (interpolated) target; distance changes geometrically
+/// and rises mid-way on long arcs so the flight shows where it is going.
+fn interpolate(a: &CameraView, b: &CameraView, t: f64, camera: &mut Camera) {
+    let dir = slerp_dir(a.eye_dir(), b.eye_dir(), t);
+    let (yaw, pitch) = yaw_pitch(dir);
+    let dir = direction(yaw, pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT)); // keep `up` usable
+
+    let lift = 1.0 + 0.5 * (a.arc(b) / PI) * (t * PI).sin();
+    let distance = (a.distance.ln() + (b.distance.ln() - a.distance.ln()) * t).exp() * lift;
+    let target = a.target().lerp(b.target(), t);
+
+    camera.eye = target + dir * distance;
+    camera.target = target;
+    camera.up = DVec3::Y;
+}
+
+/// Great-circle interpolation between unit vectors. Between (nearly)
+/// opposite ones every great circle is as short, and the plain slerp has
+/// none to pick; the eye then swings round the up axis, like a yaw.
+fn slerp_dir(a: DVec3, b: DVec3, t: f64) -> DVec3 {
+    let arc = a.angle_between(b);
+    if arc < PI - 1e-3 {
+        return a.slerp(b, t).normalize();
+    }
+    let axis = DVec3::Y.reject_from_normalized(a).try_normalize().unwrap_or_else(|| a.any_orthonormal_vector());
+    DQuat::from_axis_angle(axis, arc * t) * a
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f64, // seconds from the start of the path
+    #[serde(flatten)]
+    pub view: CameraView,
+    #[serde(default)]
+    pub easing: Easing, // of the segment that ends at this keyframe
+}
+
+/// Keyframed camera motion. With `fps` set, playback advances by exactly
+/// `1 / fps` per rendered frame instead of wall-clock time, so recordings
+/// come out identical every run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraPath {
+    #[serde(default)]
+    pub fps: Option<f64>,
+    #[serde(rename = "keyframe")]
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let camera_path: CameraPath = toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        if camera_path.keyframes.is_empty() {
+            bail!("{} has no keyframes", path.display());
+        }
+        if camera_path.keyframes.windows(2).any(|w| w[1].time <= w[0].time) {
+            bail!("{}: keyframe times must increase", path.display());
+        }
+        if let Some(keyframe) = camera_path.keyframes.iter().find(|k| !k.view.is_valid()) {
+            bail!(
+                "{}: keyframe at {}s needs a positive distance and finite values",
+                path.display(),
+                keyframe.time
+            );
+        }
+        if camera_path.fps.is_some_and(|fps| fps <= 0.0) {
+            bail!("{}: fps must be positive", path.display());
+        }
+        Ok(camera_path)
+    }
+
+    /// A two-keyframe path from `from` to `to`, taking longer for longer arcs.
+    pub fn flight(from: CameraView, to: CameraView) -> Self {
+        let duration = 1.0 + 2.0 * from.arc(&to) / PI;
+        Self {
+            fps: None,
+            keyframes: vec![
+                Keyframe { time: 0.0, view: from, easing: Easing::Linear },
+                Keyframe { time: duration, view: to, easing: Easing::EaseInOut },
+            ],
+        }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Writes the pose at `time` into `camera`.
+    fn sample(&self, time: f64, camera: &mut Camera) {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        match (next.checked_sub(1).map(|i| &self.keyframes[i]), self.keyframes.get(next)) {
+            (Some(a), Some(b)) => {
+                let t = (time - a.time) / (b.time - a.time);
+                interpolate(&a.view, &b.view, b.easing.apply(t), camera);
+            }
+            (Some(k), None) | (None, Some(k)) => interpolate(&k.view, &k.view, 0.0, camera),
+            (None, None) => {}
+        }
+    }
+}
+
+/// A path being played back.
+pub struct CameraAnimation {
+    path: CameraPath,
+    time: f64,
+}
+
+impl CameraAnimation {
+    pub fn new(path: CameraPath) -> Self {
+        Self { path, time: 0.0 }
+    }
+
+    /// Moves the camera along the path; returns false once it has ended.
+    pub fn advance(&mut self, camera: &mut Camera, dt: f64) -> bool {
+        self.time += self.path.fps.map_or(dt, |fps| 1.0 / fps);
+        let time = self.time.min(self.path.duration());
+        self.path.sample(time, camera);
+        self.time < self.path.duration()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    #[serde(flatten)]
+    pub view: CameraView,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct BookmarkFile {
+    #[serde(default, rename = "bookmark")]
+    bookmarks: Vec<Bookmark>,
+}
+
+/// Named views, kept in a TOML file next to the binary's working directory.
+pub struct Bookmarks {
+    path: PathBuf,
+    pub bookmarks: Vec<Bookmark>,
+    next: usize, // cycled through by `next`
+}
+
+impl Bookmarks {
+    /// Loads `path`; a missing file just means no bookmarks yet. Bookmarks
+    /// with unusable views are dropped.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut bookmarks = match std::fs::read_to_string(&path) {
+            Ok(text) => match toml::from_str::<BookmarkFile>(&text) {
+                Ok(file) => file.bookmarks,
+                Err(err) => {
+                    log::warn!("{} is invalid ({err}), starting without bookmarks", path.display());
+                    Vec::new()
+                }
+            },
+            Err(_) => Vec::new(),
+        };
+        bookmarks.retain(|b| {
+            let valid = b.view.is_valid();
+            if !valid {
+                log::warn!("{}: dropping bookmark `{}`, its view is invalid", path.display(), b.name);
+            }
+            valid
+        });
+        Self { path, bookmarks, next: 0 }
+    }
+
+    /// Stores `view` as `name`, replacing a bookmark of the same name, and
+    /// writes the file.
+    pub fn add(&mut self, name: &str, view: CameraView) -> Result<&Bookmark> {
+        let index = match self.bookmarks.iter().position(|b| b.name == name) {
+            Some(index) => {
+                self.bookmarks[index].view = view;
+                index
+            }
+            None => {
+                self.bookmarks.push(Bookmark { name: name.to_string(), view });
+                self.bookmarks.len() - 1
+            }
+        };
+        let file = BookmarkFile { bookmarks: self.bookmarks.clone() };
+        let text = toml::to_string(&file)?;
+        std::fs::write(&self.path, text).with_context(|| format!("writing {}", self.path.display()))?;
+        Ok(&self.bookmarks[index])
+    }
+
+    /// A `view N` name no bookmark has yet, for views saved without one.
+    pub fn unused_name(&self) -> String {
+        (self.bookmarks.len() + 1..)
+            .map(|n| format!("view {n}"))
+            .find(|name| self.get(name).is_none())
+            .unwrap()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| b.name == name)
+    }
+
+    /// The bookmark after the one returned last time, wrapping around.
+    pub fn next(&mut self) -> Option<&Bookmark> {
+        if self.bookmarks.is_empty() {
+            return None;
+        }
+        let index = self.next % self.bookmarks.len();
+        self.next = index + 1;
+        self.bookmarks.get(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slerp_dir_follows_the_great_circle() {
+        let mid = slerp_dir(DVec3::X, DVec3::Z, 0.5);
+        assert!(mid.abs_diff_eq(DVec3::new(1.0, 0.0, 1.0).normalize(), 1e-9));
+    }
+
+    #[test]
+    fn slerp_dir_handles_opposite_directions() {
+        for a in [DVec3::X, DVec3::new(0.3, 0.8, -0.5).normalize(), DVec3::Y] {
+            for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+                let dir = slerp_dir(a, -a, t);
+                assert!(dir.is_finite() && (dir.length() - 1.0).abs() < 1e-9, "{a} at {t}: {dir}");
+                assert!((dir.angle_between(a) - PI * t).abs() < 1e-6, "{a} at {t}: {dir}");
+            }
+        }
+        // horizontal views swing round the up axis
+        assert!(slerp_dir(DVec3::X, -DVec3::X, 0.5).y.abs() < 1e-9);
+    }
+
+    #[test]
+    fn views_need_a_positive_finite_distance() {
+        let view = CameraView { yaw: 30.0, pitch: 10.0, distance: 3.0, target: [0.0; 3] };
+        assert!(view.is_valid());
+        assert!(!CameraView { distance: 0.0, ..view }.is_valid());
+        assert!(!CameraView { distance: -1.0, ..view }.is_valid());
+        assert!(!CameraView { distance: f64::INFINITY, ..view }.is_valid());
+        assert!(!CameraView { yaw: f64::NAN, ..view }.is_valid());
+        assert!(!CameraView { target: [0.0, f64::NAN, 0.0], ..view }.is_valid());
+    }
+}
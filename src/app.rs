@@ -1,4 +1,5 @@
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Receiver};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -11,18 +12,33 @@ use winit::event::{MouseScrollDelta, StartCause};
 use winit::event::WindowEvent;
 use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowId};
-use winit::event::{ElementState, MouseButton};
+use winit::event::ElementState;
+use winit::keyboard::PhysicalKey;
 
 use crate::gpu::{self, GpuState};
+use crate::input::{Action, Input, InputMap, Trigger};
+use crate::markers::{Marker, MarkerPosition};
+
+const INPUT_CONFIG_PATH: &str = "input.toml";
+const CAMERA_PATH: &str = "camera_path.toml";
 
 pub struct App {
     window: Option<Arc<Window>>,
     gpu: Option<GpuState>,
+    input: Input,
 
     shader_rx: Receiver<Event>,
     last_reload: Instant,
     #[allow(dead_code)]
     shader_watcher: RecommendedWatcher, // keep it alive
+
+    // vector layer files live wherever the config points, so their
+    // directories are watched and events filtered by file
+    overlay_rx: Receiver<Event>,
+    overlay_watcher: RecommendedWatcher,
+    overlay_dirs: HashSet<PathBuf>,
+    overlay_files: HashSet<PathBuf>,
+    last_overlay_reload: Instant,
 }
 
 impl Default for App {
@@ -42,13 +58,57 @@ impl Default for App {
             .watch(path, notify::RecursiveMode::NonRecursive)
             .expect("watch failed");
 
+        let (overlay_tx, overlay_rx) = mpsc::channel::<Event>();
+        let overlay_watcher = notify::recommended_watcher(move |res| {
+            if let Ok(event) = res {
+                let _ = overlay_tx.send(event);
+            }
+        })
+        .expect("watcher init failed");
+
         App {
             window: None,
             gpu: None,
+            input: Input::new(InputMap::load(INPUT_CONFIG_PATH)),
 
             shader_rx: rx,
             last_reload: Instant::now() - Duration::from_secs(1), // in the past
             shader_watcher: watcher,
+
+            overlay_rx,
+            overlay_watcher,
+            overlay_dirs: HashSet::new(),
+            overlay_files: HashSet::new(),
+            last_overlay_reload: Instant::now(),
+        }
+    }
+}
+
+impl App {
+    /// Starts watching the directories of every vector layer file, the
+    /// scalar grid and their configs, including ones added since the last
+    /// call.
+    fn watch_overlays(&mut self) {
+        let Some(gpu) = self.gpu.as_ref() else { return };
+        let paths = gpu.vector_layers.watched_paths().into_iter().chain(gpu.scalar_layer.watched_paths());
+        for path in paths {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let (Ok(dir), Some(name)) = (dir.canonicalize(), path.file_name()) else {
+                log::warn!("not watching {}: directory not found", path.display());
+                continue;
+            };
+            self.overlay_files.insert(dir.join(name));
+            if !self.overlay_dirs.contains(&dir) {
+                match self.overlay_watcher.watch(&dir, notify::RecursiveMode::NonRecursive) {
+                    Ok(()) => {
+                        self.overlay_dirs.insert(dir);
+                    }
+                    Err(err) => log::warn!("not watching {}: {err}", dir.display()),
+                }
+            }
         }
     }
 }
@@ -79,6 +139,20 @@ impl ApplicationHandler for App {
             }
         }
 
+        let mut overlays_changed = false;
+        while let Ok(ev) = self.overlay_rx.try_recv() {
+            // editors often save by writing a new file and renaming it over the old one
+            let relevant = matches!(ev.kind, EventKind::Create(_) | EventKind::Modify(_));
+            overlays_changed |= relevant && ev.paths.iter().any(|p| self.overlay_files.contains(p));
+        }
+        if overlays_changed && self.last_overlay_reload.elapsed() > Duration::from_millis(200) {
+            self.last_overlay_reload = Instant::now();
+            if let Some(gpu) = self.gpu.as_mut() {
+                gpu.reload_overlays();
+                self.watch_overlays(); // the list may name new files
+            }
+        }
+
         if let StartCause::Init = cause {
             let attrs = Window::default_attributes()
                 .with_inner_size(Size::Physical(PhysicalSize::new(800, 400)))
@@ -107,47 +181,68 @@ impl ApplicationHandler for App {
             }
         });
 
+        self.watch_overlays();
         window.request_redraw();
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         if let Some(gpu) = self.gpu.as_mut() {
+            // input the debug panel uses doesn't reach the camera
+            if gpu.debug_ui.on_window_event(self.window.as_ref().unwrap(), &event) {
+                return;
+            }
             match event {
                 WindowEvent::CloseRequested => event_loop.exit(),
                 WindowEvent::RedrawRequested => gpu.render(&self.window),
+                WindowEvent::Resized(size) => {
+                    gpu.resize(size.width, size.height);
+                    self.window.as_ref().unwrap().request_redraw();
+                }
+
+                WindowEvent::ModifiersChanged(modifiers) => self.input.set_modifiers(modifiers.state()),
 
                 WindowEvent::MouseInput { state, button, .. } => {
-                    if button == MouseButton::Left {
-                        gpu.dragging = state == ElementState::Pressed;
-                        println!("Dragging: {}", gpu.dragging);
+                    let pressed = state == ElementState::Pressed;
+                    if let Some(action) = self.input.button(Trigger::Mouse(button), pressed) {
+                        handle_action(gpu, self.window.as_ref().unwrap(), action, pressed);
                     }
-                },
+                }
+
+                WindowEvent::KeyboardInput { event, .. } if !event.repeat => {
+                    if let PhysicalKey::Code(code) = event.physical_key {
+                        let pressed = event.state == ElementState::Pressed;
+                        if let Some(action) = self.input.button(Trigger::Key(code), pressed) {
+                            handle_action(gpu, self.window.as_ref().unwrap(), action, pressed);
+                        }
+                    }
+                }
 
                 WindowEvent::CursorMoved { position, .. } => {
-                    println!("Cursor moved: {:?}", position);
-                    let sensitivity = 0.01;
                     let (x, y) = (position.x as f32, position.y as f32);
-                    let delta_x = gpu.last_mouse_pos.0 - x;
-                    let delta_y = gpu.last_mouse_pos.1 - y;
-                    if gpu.dragging {
-                        gpu.camera.yaw -= delta_x * sensitivity;
-                        gpu.camera.pitch = (gpu.camera.pitch - delta_y * sensitivity).clamp(-89.0_f32.to_radians(), 89.0_f32.to_radians());
+                    // the first event after entering has nothing to compare against
+                    if let Some((last_x, last_y)) = gpu.last_mouse_pos {
+                        let (dx, dy) = ((x - last_x) as f64, (y - last_y) as f64);
+                        if gpu.dragging {
+                            gpu.controller.mouse_motion(dx, dy);
+                        }
+                        if gpu.panning {
+                            let height = gpu.resolution().1 as f64;
+                            gpu.controller.pan(&gpu.camera, dx / height, dy / height);
+                        }
                     }
-                    gpu.last_mouse_pos = (x, y);
+                    gpu.last_mouse_pos = Some((x, y));
                 }
 
-                WindowEvent::MouseWheel { delta, .. } => {
-                    println!("MouseWheel event: {:?}", delta);
+                WindowEvent::CursorLeft { .. } => gpu.last_mouse_pos = None,
+
+                WindowEvent::MouseWheel { delta, .. } if self.input.wheel() == Some(Action::Zoom) => {
                     let raw_scroll = match delta {
                         MouseScrollDelta::LineDelta(_, y)    => y,
                         MouseScrollDelta::PixelDelta(pos) => (pos.y as f32) / 120.0, // normalize pixels to “line” units
                     };
 
-                    let zoom_speed = 0.1;
-                    let scale = 1.0 - raw_scroll * zoom_speed;
-
-                    gpu.camera.distance = (gpu.camera.distance * scale).max(0.1);
-                    println!("Updated zoom: {}", gpu.camera.distance);
+                    let anchor = gpu.cursor_anchor();
+                    gpu.controller.scroll(raw_scroll as f64, anchor);
 
                     self.window.as_ref().unwrap().request_redraw();
                 }
@@ -157,3 +252,183 @@ impl ApplicationHandler for App {
         }
     }
 }
+
+/// Applies a bound action; `pressed` is false when a held action ends.
+fn handle_action(gpu: &mut GpuState, window: &Window, action: Action, pressed: bool) {
+    match action {
+        Action::Orbit => {
+            if pressed {
+                gpu.stop_animation(); // grabbing the camera takes it back
+            }
+            gpu.dragging = pressed;
+            gpu.controller.set_dragging(pressed);
+        }
+        Action::Pan => {
+            if pressed {
+                gpu.stop_animation();
+            }
+            gpu.panning = pressed;
+        }
+        Action::MoveForward
+        | Action::MoveBack
+        | Action::MoveLeft
+        | Action::MoveRight
+        | Action::MoveUp
+        | Action::MoveDown
+        | Action::Boost => gpu.controller.action(action, pressed),
+
+        // everything below fires once, on press
+        _ if !pressed => {}
+        Action::Zoom => {} // wheel only
+        Action::ResetCamera => {
+            gpu.reset_camera();
+            println!("🎥 camera reset");
+        }
+        Action::Screenshot => gpu.request_screenshot(),
+        Action::ToggleWireframe => {
+            let view = gpu.toggle_wireframe();
+            println!("Debug view: {}", view.name());
+        }
+        Action::ToggleGraticule => {
+            gpu.graticule.enabled = !gpu.graticule.enabled;
+            println!("Graticule: {}", if gpu.graticule.enabled { "on" } else { "off" });
+        }
+        Action::ToggleVectorLayers => {
+            gpu.vector_layers.enabled = !gpu.vector_layers.enabled;
+            println!("Vector layers: {}", if gpu.vector_layers.enabled { "on" } else { "off" });
+        }
+        Action::ToggleScalarLayer => {
+            gpu.scalar_layer.enabled = !gpu.scalar_layer.enabled;
+            println!("Scalar layer: {}", if gpu.scalar_layer.enabled { "on" } else { "off" });
+        }
+        Action::ToggleSwarm => {
+            let count = gpu.toggle_swarm();
+            println!("🛰️ {count} satellites");
+        }
+        Action::ToggleMoon => {
+            println!("Moon: {}", if gpu.toggle_moon() { "on" } else { "off" });
+        }
+        Action::CycleColormap => {
+            gpu.scalar_layer.colormap = gpu.scalar_layer.colormap.next();
+            println!("Colormap: {:?}", gpu.scalar_layer.colormap);
+        }
+        Action::NarrowScalarRange | Action::WidenScalarRange => {
+            let factor = if action == Action::NarrowScalarRange { 0.8 } else { 1.25 };
+            gpu.scalar_layer.scale_range(factor);
+            let (min, max) = gpu.scalar_layer.range;
+            println!("Scalar range: {min} … {max}");
+        }
+        Action::ToggleBloom => {
+            let bloom = &mut gpu.post.settings.bloom;
+            bloom.enabled = !bloom.enabled;
+            println!("Bloom: {}", if bloom.enabled { "on" } else { "off" });
+        }
+        Action::ToggleColorGrading => {
+            let grading = &mut gpu.post.settings.color_grading;
+            grading.enabled = !grading.enabled;
+            println!("Color grading: {}", if grading.enabled { "on" } else { "off" });
+        }
+        Action::ToggleFxaa => {
+            let fxaa = &mut gpu.post.settings.fxaa;
+            fxaa.enabled = !fxaa.enabled;
+            println!("FXAA: {}", if fxaa.enabled { "on" } else { "off" });
+        }
+        Action::CycleTonemapper => {
+            let settings = &mut gpu.post.settings;
+            settings.tonemapper = settings.tonemapper.next();
+            println!("Tonemapper: {:?}", settings.tonemapper);
+        }
+        Action::ExposureDown | Action::ExposureUp => {
+            let step = if action == Action::ExposureUp { 0.5 } else { -0.5 };
+            gpu.post.settings.exposure += step;
+            println!("Exposure: {:+.1} EV", gpu.post.settings.exposure);
+        }
+        Action::CycleMsaa => {
+            let samples = gpu.cycle_sample_count();
+            println!("MSAA: {samples}x");
+        }
+        Action::DumpRenderGraph => gpu.request_graph_dump(),
+        Action::ToggleProfiler => {
+            gpu.profiler.enabled = !gpu.profiler.enabled;
+            println!("Profiler: {}", if gpu.profiler.enabled { "on" } else { "off" });
+            if gpu.profiler.enabled && !gpu.profiler.gpu_supported() {
+                println!("⚠️ no timestamp queries on this adapter, timing the CPU only");
+            }
+        }
+        Action::ExportTrace => gpu.export_trace(),
+        Action::ToggleDebugUi => {
+            gpu.debug_ui.visible = !gpu.debug_ui.visible;
+            println!("Debug UI: {}", if gpu.debug_ui.visible { "on" } else { "off" });
+        }
+        Action::ReloadShaders => {
+            println!("🔄 reloading shaders…");
+            gpu.reload_shader_pipeline();
+        }
+        Action::CycleDepthMode => {
+            let mode = gpu.depth_mode().next();
+            gpu.set_depth_mode(mode);
+            println!("Depth mode: {:?}", mode);
+        }
+        Action::CycleDebugView => {
+            let view = gpu.cycle_debug_view();
+            println!("Debug view: {}", view.name());
+        }
+        Action::ToggleProjection => {
+            gpu.toggle_projection();
+            println!("Projection: {:?}", gpu.camera.projection);
+        }
+        Action::CycleController => {
+            let kind = gpu.controller_kind().next();
+            gpu.set_controller(kind);
+            println!("Camera controller: {}", gpu.controller.name());
+        }
+        Action::SaveBookmark => gpu.save_bookmark(None),
+        Action::NextBookmark => gpu.fly_to_next_bookmark(),
+        Action::PlayCameraPath => gpu.play_camera_path(CAMERA_PATH),
+        Action::Pick => match gpu.pick_at_cursor() {
+            Some(pick) => {
+                let node = pick.node.map(|node| format!(", node {node:?}")).unwrap_or_default();
+                println!(
+                    "📍 {pick} (object {}{node}) at {:.3?}, normal {:.3?}",
+                    pick.object_id,
+                    pick.position.to_array(),
+                    pick.normal.to_array(),
+                );
+                window.set_title(&format!("{pick}"));
+            }
+            None => window.set_title("nothing picked"),
+        },
+        // on an existing marker, M cycles its icon instead
+        Action::AddMarker => match gpu.pick_at_cursor() {
+            Some(pick) => match gpu.marker_near(pick.position) {
+                Some(id) => {
+                    if let Some(marker) = gpu.marker_mut(id) {
+                        marker.icon = marker.icon.next();
+                    }
+                }
+                None => {
+                    let (position, label) = match (pick.latitude, pick.longitude) {
+                        (Some(latitude), Some(longitude)) => (
+                            MarkerPosition::LatLong { latitude, longitude, altitude: 0.0 },
+                            format!("{latitude:.2}°, {longitude:.2}°"),
+                        ),
+                        _ => (MarkerPosition::World(pick.position), format!("object {}", pick.object_id)),
+                    };
+                    gpu.add_marker(Marker::new(position, label));
+                    println!("📌 marker {} placed", gpu.markers().len());
+                }
+            },
+            None => println!("no surface under the cursor"),
+        },
+        Action::RemoveMarker => {
+            let near = gpu.pick_at_cursor().and_then(|pick| gpu.marker_near(pick.position));
+            if let Some(marker) = near.and_then(|id| gpu.remove_marker(id)) {
+                println!("📌 removed marker '{}'", marker.label);
+            }
+        }
+        Action::ClearMarkers => {
+            gpu.clear_markers();
+            println!("📌 markers cleared");
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, ScaleFont};
+use anyhow::{Context, Result};
+use glam::Vec2;
+
+use crate::markers::Icon;
+
+const ATLAS_WIDTH: u32 = 512;
+const PADDING: u32 = 1; // keeps linear filtering from bleeding between entries
+const ICON_SIZE: u32 = 32;
+const SOLID_SIZE: u32 = 4;
+
+/// Where an entry landed in the atlas and how to place it on screen.
+#[derive(Copy, Clone, Debug)]
+pub struct AtlasRect {
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    pub size: Vec2,   // pixels
+    pub offset: Vec2, // pixels from the pen position (glyphs) to the top-left corner
+}
+
+struct Glyph {
+    id: GlyphId,
+    rect: Option<AtlasRect>, // None for blank glyphs like space
+    advance: f32,
+}
+
+/// One coverage texture holding the marker icons, a solid patch and, when a
+/// font could be loaded, the glyphs labels are set in.
+pub struct MarkerAtlas {
+    pub image: image::GrayImage,
+    icons: HashMap<Icon, AtlasRect>,
+    solid: AtlasRect,
+    font: Option<(FontVec, PxScale)>,
+    glyphs: HashMap<char, Glyph>,
+    pub line_height: f32,
+    pub ascent: f32,
+}
+
+/// A bitmap waiting for a place in the atlas.
+struct Pending {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl MarkerAtlas {
+    /// Bakes printable ASCII and Latin-1 at `px` pixels. Without the font
+    /// file there are no labels, only icons.
+    pub fn new(font_path: &Path, px: f32) -> Self {
+        let font = match load_font(font_path) {
+            Ok(font) => Some((font, PxScale::from(px))),
+            Err(err) => {
+                log::warn!("marker labels disabled: {err:#}");
+                None
+            }
+        };
+
+        let mut pending = Vec::new();
+        for icon in Icon::ALL {
+            let pixel = 2.0 / ICON_SIZE as f32;
+            let pixels = (0..ICON_SIZE * ICON_SIZE)
+                .map(|i| {
+                    let x = ((i % ICON_SIZE) as f32 + 0.5) * pixel - 1.0;
+                    let y = ((i / ICON_SIZE) as f32 + 0.5) * pixel - 1.0;
+                    (icon.coverage(x, y, pixel) * 255.0) as u8
+                })
+                .collect();
+            pending.push(Pending { width: ICON_SIZE, height: ICON_SIZE, pixels });
+        }
+        // fully covered block for flat-colored quads
+        let pixels = vec![255; (SOLID_SIZE * SOLID_SIZE) as usize];
+        pending.push(Pending { width: SOLID_SIZE, height: SOLID_SIZE, pixels });
+
+        let mut glyphs = HashMap::new();
+        let mut glyph_bitmaps = Vec::new(); // (char, offset) for entries in `pending`
+        let (mut line_height, mut ascent) = (0.0, 0.0);
+        if let Some((font, scale)) = &font {
+            let scaled = font.as_scaled(*scale);
+            line_height = scaled.height() + scaled.line_gap();
+            ascent = scaled.ascent();
+            for c in (' '..='~').chain('\u{a0}'..='\u{ff}') {
+                let id = scaled.glyph_id(c);
+                glyphs.insert(c, Glyph { id, rect: None, advance: scaled.h_advance(id) });
+                let Some(outlined) = font.outline_glyph(id.with_scale(*scale)) else {
+                    continue; // nothing to draw
+                };
+                let bounds = outlined.px_bounds();
+                let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+                let mut pixels = vec![0; (width * height) as usize];
+                outlined.draw(|x, y, coverage| {
+                    if x < width && y < height {
+                        pixels[(y * width + x) as usize] = (coverage * 255.0) as u8;
+                    }
+                });
+                glyph_bitmaps.push((c, Vec2::new(bounds.min.x, bounds.min.y)));
+                pending.push(Pending { width, height, pixels });
+            }
+        }
+
+        // shelf packing, tallest first so rows stay tight
+        let mut order: Vec<usize> = (0..pending.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(pending[i].height));
+        let mut positions = vec![(0, 0); pending.len()];
+        let (mut x, mut y, mut row) = (PADDING, PADDING, 0);
+        for &i in &order {
+            let entry = &pending[i];
+            if x + entry.width + PADDING > ATLAS_WIDTH {
+                (x, y, row) = (PADDING, y + row + PADDING, 0);
+            }
+            positions[i] = (x, y);
+            x += entry.width + PADDING;
+            row = row.max(entry.height);
+        }
+        let height = (y + row + PADDING).next_power_of_two();
+
+        let mut image = image::GrayImage::new(ATLAS_WIDTH, height);
+        let size = Vec2::new(ATLAS_WIDTH as f32, height as f32);
+        let mut rects = Vec::with_capacity(pending.len());
+        for (entry, &(x, y)) in pending.iter().zip(&positions) {
+            for row in 0..entry.height {
+                for column in 0..entry.width {
+                    let value = entry.pixels[(row * entry.width + column) as usize];
+                    image.put_pixel(x + column, y + row, image::Luma([value]));
+                }
+            }
+            let min = Vec2::new(x as f32, y as f32);
+            let extent = Vec2::new(entry.width as f32, entry.height as f32);
+            rects.push(AtlasRect { uv_min: min / size, uv_max: (min + extent) / size, size: extent, offset: Vec2::ZERO });
+        }
+
+        let icons = Icon::ALL
+            .into_iter()
+            .zip(&rects)
+            .map(|(icon, rect)| (icon, AtlasRect { offset: -rect.size * 0.5, ..*rect }))
+            .collect();
+        // sample only the middle so filtering never reaches the edges
+        let solid = rects[Icon::ALL.len()];
+        let inset = (solid.uv_max - solid.uv_min) * 0.25;
+        let solid = AtlasRect { uv_min: solid.uv_min + inset, uv_max: solid.uv_max - inset, ..solid };
+        for ((c, offset), rect) in glyph_bitmaps.into_iter().zip(&rects[Icon::ALL.len() + 1..]) {
+            if let Some(glyph) = glyphs.get_mut(&c) {
+                glyph.rect = Some(AtlasRect { offset, ..*rect });
+            }
+        }
+
+        Self { image, icons, solid, font, glyphs, line_height, ascent }
+    }
+
+    /// Centered on the marker, at the icon's native size.
+    pub fn icon(&self, icon: Icon) -> AtlasRect {
+        self.icons[&icon]
+    }
+
+    /// A fully covered patch, for flat-colored quads.
+    pub fn solid(&self) -> AtlasRect {
+        self.solid
+    }
+
+    pub fn has_font(&self) -> bool {
+        self.font.is_some()
+    }
+
+    /// Glyph quads for one line of `text`, relative to a pen starting at
+    /// the origin on the baseline, plus the advance width. Characters that
+    /// weren't baked show as `?`.
+    pub fn layout(&self, text: &str) -> (Vec<(Vec2, AtlasRect)>, f32) {
+        let Some((font, scale)) = &self.font else {
+            return (Vec::new(), 0.0);
+        };
+        let scaled = font.as_scaled(*scale);
+        let mut quads = Vec::with_capacity(text.len());
+        let mut pen = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for c in text.chars() {
+            let Some(glyph) = self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?')) else {
+                continue;
+            };
+            if let Some(previous) = previous {
+                pen += scaled.kern(previous, glyph.id);
+            }
+            if let Some(rect) = glyph.rect {
+                quads.push((Vec2::new(pen, 0.0) + rect.offset, rect));
+            }
+            pen += glyph.advance;
+            previous = Some(glyph.id);
+        }
+        (quads, pen)
+    }
+}
+
+fn load_font(path: &Path) -> Result<FontVec> {
+    let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    FontVec::try_from_vec(bytes).with_context(|| format!("parsing {}", path.display()))
+}
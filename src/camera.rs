@@ -1,25 +1,169 @@
-use glam::Vec3;
+use glam::{DVec3, Mat4, Vec2, Vec3};
 
+use crate::gpu::DepthMode;
+
+const DEFAULT_FOV_Y: f32 = std::f32::consts::FRAC_PI_4; // 45°
+
+/// How view space is mapped to clip space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        fov_y: f32, // vertical, in radians
+        near: f32,
+        far: f32,   // only bounds the standard and logarithmic depth ranges
+    },
+    Orthographic {
+        height: f32, // height of the view volume, in world units
+        near: f32,
+        far: f32,
+    },
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective {
+            fov_y: DEFAULT_FOV_Y,
+            near: 0.001,
+            far: 100.0,
+        }
+    }
+}
+
+impl Projection {
+    pub fn far(&self) -> f32 {
+        match *self {
+            Projection::Perspective { far, .. } | Projection::Orthographic { far, .. } => far,
+        }
+    }
+
+    /// Projection matrix for `depth_mode`. Reverse-Z perspective uses an
+    /// infinite far plane; in logarithmic mode the fragment shader replaces z,
+    /// so the matrix only has to clip at the near plane.
+    pub fn matrix(&self, aspect: f32, depth_mode: DepthMode) -> Mat4 {
+        match (*self, depth_mode) {
+            (Projection::Perspective { fov_y, near, far }, DepthMode::Standard) => {
+                Mat4::perspective_rh(fov_y, aspect, near, far)
+            }
+            (Projection::Perspective { fov_y, near, .. }, _) => {
+                Mat4::perspective_infinite_reverse_rh(fov_y, aspect, near)
+            }
+            (Projection::Orthographic { height, near, far }, mode) => {
+                let (w, h) = (height * aspect * 0.5, height * 0.5);
+                // swapping the planes maps near to 1 and far to 0
+                let (near, far) = if mode == DepthMode::ReverseZ { (far, near) } else { (near, far) };
+                Mat4::orthographic_rh(-w, w, -h, h, near, far)
+            }
+        }
+    }
+}
+
+/// Camera pose and projection. How the pose changes is up to the active
+/// `CameraController`. World positions are kept in f64; rendering happens in
+/// a camera-relative space (the eye sits at the origin) so nothing large ever
+/// reaches the GPU in f32.
 pub struct Camera {
-    pub eye: Vec3,
-    pub target: Vec3,
-    pub up: Vec3,
-    pub distance: f32,
-    pub yaw: f32,   // in radians
-    pub pitch: f32, // in radians
+    pub eye: DVec3,
+    pub target: DVec3, // only its direction from the eye matters for the view
+    pub up: DVec3,
+    pub projection: Projection,
+    pub aspect: f32, // width / height of the viewport
 }
 
 impl Default for Camera {
     fn default() -> Self {
         Self {
-            target: Vec3::ZERO, // Looking at the origin
-            distance: 5.0,      // 5 units away from target
-            yaw: 0.0,           // Facing along +Z by default
-            pitch: 0.0,
-
-            // Initial eye position will be computed each frame from yaw/pitch/distance
-            eye: Vec3::new(0.0, 0.0, 5.0),
-            up: Vec3::Y, // World "up" is positive Y
+            eye: DVec3::new(5.0, 0.0, 0.0),
+            target: DVec3::ZERO, // Looking at the origin
+            up: DVec3::Y,        // World "up" is positive Y
+
+            projection: Projection::default(),
+            aspect: 1.0, // set from the surface size
         }
     }
 }
+
+impl Camera {
+    pub fn forward(&self) -> DVec3 {
+        (self.target - self.eye).normalize()
+    }
+
+    pub fn distance(&self) -> f64 {
+        self.eye.distance(self.target)
+    }
+
+    /// View matrix of the camera-relative render space: rotation only.
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at_rh(Vec3::ZERO, self.forward().as_vec3(), self.up.as_vec3())
+    }
+
+    pub fn projection_matrix(&self, depth_mode: DepthMode) -> Mat4 {
+        self.projection.matrix(self.aspect, depth_mode)
+    }
+
+    /// Height of the visible area at `distance` in front of the eye.
+    pub fn view_height_at(&self, distance: f64) -> f64 {
+        match self.projection {
+            Projection::Perspective { fov_y, .. } => 2.0 * distance * (fov_y as f64 * 0.5).tan(),
+            Projection::Orthographic { height, .. } => height as f64,
+        }
+    }
+
+    /// Scales the view volume of an orthographic camera by `factor`
+    /// (< 1 zooms in). Returns false for perspective cameras, which zoom by
+    /// moving instead.
+    pub fn scale_orthographic(&mut self, factor: f64) -> bool {
+        match &mut self.projection {
+            Projection::Orthographic { height, .. } => {
+                *height = (*height * factor as f32).max(1e-4);
+                true
+            }
+            Projection::Perspective { .. } => false,
+        }
+    }
+
+    /// Switches between perspective and orthographic, keeping the target
+    /// the same size on screen. Going back to perspective moves the eye.
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Perspective { fov_y, near, far } => Projection::Orthographic {
+                height: 2.0 * self.distance() as f32 * (fov_y * 0.5).tan(),
+                near,
+                far,
+            },
+            Projection::Orthographic { height, near, far } => {
+                let distance = (height * 0.5 / (DEFAULT_FOV_Y * 0.5).tan()) as f64;
+                self.eye = self.target - self.forward() * distance;
+                Projection::Perspective { fov_y: DEFAULT_FOV_Y, near, far }
+            }
+        };
+    }
+
+    /// World-space ray through a point given in normalized device
+    /// coordinates (y up): (origin on the near plane, unit direction).
+    pub fn ray(&self, ndc: Vec2) -> (DVec3, DVec3) {
+        // the depth convention doesn't matter for a direction
+        let inv = (self.projection_matrix(DepthMode::Standard) * self.view_matrix()).inverse();
+        let near = inv.project_point3(ndc.extend(0.0));
+        let far = inv.project_point3(ndc.extend(1.0));
+        (self.eye + near.as_dvec3(), (far - near).as_dvec3().normalize())
+    }
+
+    /// Rebases a world position onto the camera before it is narrowed to f32.
+    pub fn relative(&self, world: DVec3) -> Vec3 {
+        (world - self.eye).as_vec3()
+    }
+}
+
+/// Distance along a ray (unit `dir`) to its first hit with a sphere in front
+/// of `origin`, if any.
+pub fn ray_sphere(origin: DVec3, dir: DVec3, center: DVec3, radius: f64) -> Option<f64> {
+    let oc = origin - center;
+    let b = oc.dot(dir);
+    let c = oc.length_squared() - radius * radius;
+    let disc = b * b - c;
+    if disc < 0.0 {
+        return None;
+    }
+    let s = disc.sqrt();
+    [-b - s, -b + s].into_iter().find(|&t| t > 0.0)
+}
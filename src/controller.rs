@@ -0,0 +1,612 @@
+use std::f64::consts::FRAC_PI_2;
+
+use glam::{DMat3, DQuat, DVec2, DVec3};
+use crate::camera::Camera;
+use crate::input::Action;
+
+pub const PITCH_LIMIT: f64 = 89.0 * std::f64::consts::PI / 180.0;
+
+/// How close the orbiting controllers may zoom to the surface, as a fraction
+/// of the planet radius.
+const SURFACE_MARGIN: f64 = 0.01;
+
+/// Input tuning shared by every controller.
+#[derive(Copy, Clone, Debug)]
+pub struct ControllerSettings {
+    pub mouse_sensitivity: f64, // radians per pixel of drag
+    pub zoom_speed: f64,        // fraction of the distance per scroll line
+    pub smoothing: f64,         // 1/s, how quickly the camera catches up with input
+    pub damping: f64,           // 1/s, how quickly momentum fades after a drag
+}
+
+impl Default for ControllerSettings {
+    fn default() -> Self {
+        Self {
+            mouse_sensitivity: 0.01,
+            zoom_speed: 0.1,
+            smoothing: 20.0,
+            damping: 4.0,
+        }
+    }
+}
+
+impl ControllerSettings {
+    /// Fraction of the remaining distance to cover in `dt` when easing at
+    /// `rate`, independent of the frame rate.
+    fn follow(rate: f64, dt: f64) -> f64 {
+        1.0 - (-rate * dt).exp()
+    }
+
+    /// Scroll lines to a multiplicative zoom factor (< 1 zooms in).
+    fn zoom_factor(&self, lines: f64) -> f64 {
+        (1.0 - lines * self.zoom_speed).max(0.1)
+    }
+}
+
+/// Turns input into camera motion.
+///
+/// A controller owns its own state (angles, speeds, held keys) and writes the
+/// camera pose in `update`. `attach` picks that state up from the current
+/// pose, so switching controllers doesn't make the view jump.
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+
+    /// Takes over from whatever moved `camera` before.
+    fn attach(&mut self, camera: &Camera);
+
+    /// Start or end of a drag (left button).
+    fn set_dragging(&mut self, dragging: bool);
+
+    /// Cursor movement in pixels while dragging.
+    fn mouse_motion(&mut self, dx: f64, dy: f64);
+
+    /// Wheel movement in lines, positive away from the user. `anchor` is the
+    /// world point under the cursor, if it is over the planet.
+    fn scroll(&mut self, lines: f64, anchor: Option<DVec3>);
+
+    /// Drag with the pan binding; deltas are in viewport heights.
+    fn pan(&mut self, _camera: &Camera, _dx: f64, _dy: f64) {}
+
+    /// Start or end of a held action (movement keys).
+    fn action(&mut self, _action: Action, _pressed: bool) {}
+
+    /// Advances by `dt` seconds and writes the resulting pose into `camera`.
+    fn update(&mut self, camera: &mut Camera, dt: f64);
+}
+
+/// Selectable controllers, in the order `next` cycles through them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ControllerKind {
+    #[default]
+    Orbit,
+    Arcball,
+    FreeFly,
+    SurfaceWalk,
+}
+
+impl ControllerKind {
+    pub fn next(self) -> Self {
+        match self {
+            ControllerKind::Orbit => ControllerKind::Arcball,
+            ControllerKind::Arcball => ControllerKind::FreeFly,
+            ControllerKind::FreeFly => ControllerKind::SurfaceWalk,
+            ControllerKind::SurfaceWalk => ControllerKind::Orbit,
+        }
+    }
+
+    /// Builds the controller; the orbiting ones stop zooming at the planet's
+    /// surface, the surface walk needs the planet it walks on.
+    pub fn create(
+        self,
+        settings: ControllerSettings,
+        planet_center: DVec3,
+        planet_radius: f64,
+    ) -> Box<dyn CameraController> {
+        match self {
+            ControllerKind::Orbit => Box::new(OrbitController::new(settings, planet_radius)),
+            ControllerKind::Arcball => Box::new(ArcballController::new(settings, planet_radius)),
+            ControllerKind::FreeFly => Box::new(FreeFlyController::new(settings)),
+            ControllerKind::SurfaceWalk => {
+                Box::new(SurfaceWalkController::new(settings, planet_center, planet_radius))
+            }
+        }
+    }
+}
+
+/// Drag input eased in over a few frames. With `fling`, the motion keeps
+/// going after the drag ends and slows down with `damping`.
+#[derive(Default)]
+struct Inertia {
+    fling: bool,
+    dragging: bool,
+    pending: DVec2,  // pixels not applied yet
+    velocity: DVec2, // pixels per second, once released
+}
+
+impl Inertia {
+    fn new(fling: bool) -> Self {
+        Self { fling, ..Default::default() }
+    }
+
+    fn set_dragging(&mut self, dragging: bool) {
+        self.dragging = dragging;
+        if dragging {
+            self.velocity = DVec2::ZERO; // grabbing stops the spin
+        }
+    }
+
+    fn push(&mut self, delta: DVec2) {
+        self.pending += delta;
+    }
+
+    /// Pixels of motion to apply this frame.
+    fn step(&mut self, dt: f64, settings: &ControllerSettings) -> DVec2 {
+        let mut delta = self.pending * ControllerSettings::follow(settings.smoothing, dt);
+        self.pending -= delta;
+        if self.dragging {
+            if dt > 0.0 && self.fling {
+                self.velocity = delta / dt;
+            }
+        } else {
+            delta += self.velocity * dt;
+            self.velocity *= (-settings.damping * dt).exp();
+        }
+        delta
+    }
+}
+
+/// Zoom requests eased in over a few frames, towards an optional anchor.
+#[derive(Default)]
+struct SmoothZoom {
+    pending: f64, // ln of the zoom factor not applied yet
+    anchor: Option<DVec3>,
+}
+
+impl SmoothZoom {
+    fn push(&mut self, factor: f64, anchor: Option<DVec3>) {
+        self.pending += factor.ln();
+        self.anchor = anchor;
+    }
+
+    /// Zoom factor to apply this frame.
+    fn step(&mut self, dt: f64, settings: &ControllerSettings) -> f64 {
+        let applied = self.pending * ControllerSettings::follow(settings.smoothing, dt);
+        self.pending -= applied;
+        if self.pending.abs() < 1e-6 {
+            self.anchor = None;
+        }
+        applied.exp()
+    }
+
+    /// Rotation around `pivot` that turns the eye towards the anchor as far
+    /// as a zoom by `factor` brings it closer, so the point under the cursor
+    /// stays roughly in place. Zooming out turns away by the same rule.
+    fn turn(&self, pivot: DVec3, eye_dir: DVec3, factor: f64) -> DQuat {
+        let Some(anchor_dir) = self.anchor.and_then(|a| (a - pivot).try_normalize()) else {
+            return DQuat::IDENTITY;
+        };
+        match eye_dir.cross(anchor_dir).try_normalize() {
+            Some(axis) => {
+                let angle = eye_dir.angle_between(anchor_dir) * (1.0 - factor).clamp(-1.0, 1.0);
+                DQuat::from_axis_angle(axis, angle)
+            }
+            None => DQuat::IDENTITY,
+        }
+    }
+}
+
+/// Slides `target` in the view plane so the scene follows the cursor.
+fn pan_target(target: &mut DVec3, camera: &Camera, dx: f64, dy: f64) {
+    let forward = camera.forward();
+    let right = forward.cross(camera.up).normalize();
+    let up = right.cross(forward);
+    let height = camera.view_height_at(camera.distance());
+    *target += (up * dy - right * dx) * height;
+}
+
+/// Dolly a perspective camera, scale an orthographic one. Zooming in stops
+/// at `min_distance`, but a camera already closer isn't pushed back out.
+fn apply_zoom(camera: &mut Camera, distance: &mut f64, factor: f64, min_distance: f64) {
+    if !camera.scale_orthographic(factor) {
+        *distance = (*distance * factor).max(min_distance.min(*distance));
+    }
+}
+
+/// Yaw/pitch of a unit direction, matching `direction`.
+pub fn yaw_pitch(dir: DVec3) -> (f64, f64) {
+    (dir.z.atan2(dir.x), dir.y.clamp(-1.0, 1.0).asin())
+}
+
+/// Unit direction from the orbit center to the eye.
+pub fn direction(yaw: f64, pitch: f64) -> DVec3 {
+    DVec3::new(yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos())
+}
+
+/// Orbits `target` at `distance`; yaw/pitch place the eye, pitch stops short
+/// of the poles.
+pub struct OrbitController {
+    pub target: DVec3,
+    pub distance: f64,
+    pub yaw: f64,   // in radians
+    pub pitch: f64, // in radians
+    min_distance: f64, // just above the planet's surface
+    settings: ControllerSettings,
+    drag: Inertia,
+    zoom: SmoothZoom,
+}
+
+impl OrbitController {
+    pub fn new(settings: ControllerSettings, planet_radius: f64) -> Self {
+        Self {
+            target: DVec3::ZERO,
+            distance: 5.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: planet_radius * (1.0 + SURFACE_MARGIN),
+            settings,
+            drag: Inertia::new(true),
+            zoom: SmoothZoom::default(),
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &'static str {
+        "orbit"
+    }
+
+    fn attach(&mut self, camera: &Camera) {
+        self.target = camera.target;
+        self.distance = camera.distance();
+        (self.yaw, self.pitch) = yaw_pitch((camera.eye - camera.target) / self.distance);
+        self.pitch = self.pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+
+    fn set_dragging(&mut self, dragging: bool) {
+        self.drag.set_dragging(dragging);
+    }
+
+    fn mouse_motion(&mut self, dx: f64, dy: f64) {
+        self.drag.push(DVec2::new(dx, dy));
+    }
+
+    fn pan(&mut self, camera: &Camera, dx: f64, dy: f64) {
+        pan_target(&mut self.target, camera, dx, dy);
+    }
+
+    fn scroll(&mut self, lines: f64, anchor: Option<DVec3>) {
+        self.zoom.push(self.settings.zoom_factor(lines), anchor);
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f64) {
+        let turn = self.drag.step(dt, &self.settings) * self.settings.mouse_sensitivity;
+        self.yaw += turn.x;
+        self.pitch = (self.pitch + turn.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+
+        let factor = self.zoom.step(dt, &self.settings);
+        let eye_dir = self.zoom.turn(self.target, direction(self.yaw, self.pitch), factor)
+            * direction(self.yaw, self.pitch);
+        (self.yaw, self.pitch) = yaw_pitch(eye_dir);
+        self.pitch = self.pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        apply_zoom(camera, &mut self.distance, factor, self.min_distance);
+
+        camera.eye = self.target + direction(self.yaw, self.pitch) * self.distance;
+        camera.target = self.target;
+        camera.up = DVec3::Y;
+    }
+}
+
+/// Orbits `target` with a free rotation: dragging always turns around the
+/// current screen axes, so there is no gimbal lock at the poles.
+pub struct ArcballController {
+    pub target: DVec3,
+    pub distance: f64,
+    pub rotation: DQuat, // camera-to-world; the eye sits on its +Z axis
+    min_distance: f64,   // just above the planet's surface
+    settings: ControllerSettings,
+    drag: Inertia,
+    zoom: SmoothZoom,
+}
+
+impl ArcballController {
+    pub fn new(settings: ControllerSettings, planet_radius: f64) -> Self {
+        Self {
+            target: DVec3::ZERO,
+            distance: 5.0,
+            rotation: DQuat::IDENTITY,
+            min_distance: planet_radius * (1.0 + SURFACE_MARGIN),
+            settings,
+            drag: Inertia::new(true),
+            zoom: SmoothZoom::default(),
+        }
+    }
+}
+
+impl CameraController for ArcballController {
+    fn name(&self) -> &'static str {
+        "arcball"
+    }
+
+    fn attach(&mut self, camera: &Camera) {
+        self.target = camera.target;
+        self.distance = camera.distance();
+        let back = -camera.forward();
+        let right = camera.up.cross(back).try_normalize().unwrap_or_else(|| back.any_orthonormal_vector());
+        let up = back.cross(right);
+        self.rotation = DQuat::from_mat3(&DMat3::from_cols(right, up, back));
+    }
+
+    fn set_dragging(&mut self, dragging: bool) {
+        self.drag.set_dragging(dragging);
+    }
+
+    fn mouse_motion(&mut self, dx: f64, dy: f64) {
+        self.drag.push(DVec2::new(dx, dy));
+    }
+
+    fn pan(&mut self, camera: &Camera, dx: f64, dy: f64) {
+        pan_target(&mut self.target, camera, dx, dy);
+    }
+
+    fn scroll(&mut self, lines: f64, anchor: Option<DVec3>) {
+        self.zoom.push(self.settings.zoom_factor(lines), anchor);
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f64) {
+        let turn = self.drag.step(dt, &self.settings) * self.settings.mouse_sensitivity;
+        let right = self.rotation * DVec3::X;
+        let up = self.rotation * DVec3::Y;
+        let spin = DQuat::from_axis_angle(up, -turn.x) * DQuat::from_axis_angle(right, -turn.y);
+        self.rotation = (spin * self.rotation).normalize();
+
+        let factor = self.zoom.step(dt, &self.settings);
+        self.rotation = (self.zoom.turn(self.target, self.rotation * DVec3::Z, factor) * self.rotation).normalize();
+        apply_zoom(camera, &mut self.distance, factor, self.min_distance);
+
+        camera.eye = self.target + self.rotation * DVec3::Z * self.distance;
+        camera.target = self.target;
+        camera.up = self.rotation * DVec3::Y;
+    }
+}
+
+/// Movement keys shared by the first-person controllers.
+#[derive(Default)]
+struct MoveKeys {
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    boost: bool,
+}
+
+impl MoveKeys {
+    fn set(&mut self, action: Action, pressed: bool) {
+        match action {
+            Action::MoveForward => self.forward = pressed,
+            Action::MoveBack => self.back = pressed,
+            Action::MoveLeft => self.left = pressed,
+            Action::MoveRight => self.right = pressed,
+            Action::MoveUp => self.up = pressed,
+            Action::MoveDown => self.down = pressed,
+            Action::Boost => self.boost = pressed,
+            _ => {}
+        }
+    }
+
+    /// (right, up, forward) in -1..=1, not normalized.
+    fn axes(&self) -> DVec3 {
+        let axis = |pos: bool, neg: bool| pos as i32 as f64 - neg as i32 as f64;
+        DVec3::new(
+            axis(self.right, self.left),
+            axis(self.up, self.down),
+            axis(self.forward, self.back),
+        )
+    }
+
+    fn speed_multiplier(&self) -> f64 {
+        if self.boost { 5.0 } else { 1.0 }
+    }
+}
+
+/// WASD to move (Q/E or Ctrl/Space for down/up, Shift to go faster), drag to
+/// look around; the wheel changes the speed. Movement eases in and out.
+pub struct FreeFlyController {
+    pub eye: DVec3,
+    pub yaw: f64,   // in radians
+    pub pitch: f64, // in radians
+    pub speed: f64, // world units per second
+    settings: ControllerSettings,
+    velocity: DVec3,
+    look: Inertia,
+    keys: MoveKeys,
+}
+
+impl FreeFlyController {
+    pub fn new(settings: ControllerSettings) -> Self {
+        Self {
+            eye: DVec3::new(5.0, 0.0, 0.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 0.5,
+            settings,
+            velocity: DVec3::ZERO,
+            look: Inertia::new(false),
+            keys: MoveKeys::default(),
+        }
+    }
+}
+
+impl CameraController for FreeFlyController {
+    fn name(&self) -> &'static str {
+        "free-fly"
+    }
+
+    fn attach(&mut self, camera: &Camera) {
+        self.eye = camera.eye;
+        (self.yaw, self.pitch) = yaw_pitch(camera.forward());
+        self.pitch = self.pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+
+    fn set_dragging(&mut self, dragging: bool) {
+        self.look.set_dragging(dragging);
+    }
+
+    fn mouse_motion(&mut self, dx: f64, dy: f64) {
+        self.look.push(DVec2::new(dx, dy));
+    }
+
+    fn scroll(&mut self, lines: f64, _anchor: Option<DVec3>) {
+        let factor = self.settings.zoom_factor(lines);
+        self.speed = (self.speed / factor).clamp(1e-4, 1e4);
+    }
+
+    fn action(&mut self, action: Action, pressed: bool) {
+        self.keys.set(action, pressed);
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f64) {
+        let turn = self.look.step(dt, &self.settings) * self.settings.mouse_sensitivity;
+        self.yaw += turn.x;
+        self.pitch = (self.pitch - turn.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+
+        let forward = direction(self.yaw, self.pitch);
+        let right = forward.cross(DVec3::Y).normalize();
+        let axes = self.keys.axes();
+        let wanted = (right * axes.x + DVec3::Y * axes.y + forward * axes.z)
+            * self.speed
+            * self.keys.speed_multiplier();
+        self.velocity += (wanted - self.velocity) * ControllerSettings::follow(self.settings.smoothing, dt);
+        self.eye += self.velocity * dt;
+
+        camera.eye = self.eye;
+        camera.target = self.eye + forward;
+        camera.up = DVec3::Y;
+    }
+}
+
+/// Walks over the planet surface at a fixed altitude: WASD moves along the
+/// sphere, dragging turns and looks up/down, the wheel changes the altitude.
+pub struct SurfaceWalkController {
+    pub planet_center: DVec3,
+    pub planet_radius: f64,
+    pub altitude: f64, // above the sphere, in world units
+    pub speed: f64,    // world units per second along the surface
+    settings: ControllerSettings,
+    normal: DVec3,     // unit, from the planet center to the walker
+    heading: DVec3,    // unit, tangent to the sphere
+    pitch: f64,        // look angle above the horizon, in radians
+    velocity: DVec2,   // (right, forward) in world units per second
+    target_altitude: f64,
+    look: Inertia,
+    keys: MoveKeys,
+}
+
+impl SurfaceWalkController {
+    pub fn new(settings: ControllerSettings, planet_center: DVec3, planet_radius: f64) -> Self {
+        Self {
+            planet_center,
+            planet_radius,
+            altitude: planet_radius * 0.002,
+            speed: planet_radius * 0.01,
+            settings,
+            normal: DVec3::X,
+            heading: DVec3::Y,
+            pitch: 0.0,
+            velocity: DVec2::ZERO,
+            target_altitude: planet_radius * 0.002,
+            look: Inertia::new(false),
+            keys: MoveKeys::default(),
+        }
+    }
+
+    /// Keeps `heading` a unit tangent after `normal` changed.
+    fn reproject_heading(&mut self, fallback: DVec3) {
+        let tangent = |v: DVec3| (v - self.normal * v.dot(self.normal)).try_normalize();
+        self.heading = tangent(self.heading)
+            .or_else(|| tangent(fallback))
+            .unwrap_or_else(|| self.normal.any_orthonormal_vector());
+    }
+}
+
+impl CameraController for SurfaceWalkController {
+    fn name(&self) -> &'static str {
+        "surface walk"
+    }
+
+    fn attach(&mut self, camera: &Camera) {
+        // land below the camera, facing where it was looking
+        self.normal = (camera.eye - self.planet_center).try_normalize().unwrap_or(DVec3::X);
+        self.heading = camera.forward();
+        self.reproject_heading(camera.up);
+        self.pitch = 0.0;
+    }
+
+    fn set_dragging(&mut self, dragging: bool) {
+        self.look.set_dragging(dragging);
+    }
+
+    fn mouse_motion(&mut self, dx: f64, dy: f64) {
+        self.look.push(DVec2::new(dx, dy));
+    }
+
+    fn scroll(&mut self, lines: f64, _anchor: Option<DVec3>) {
+        let factor = self.settings.zoom_factor(lines);
+        self.target_altitude = (self.target_altitude * factor).clamp(1e-6, self.planet_radius);
+    }
+
+    fn action(&mut self, action: Action, pressed: bool) {
+        self.keys.set(action, pressed);
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f64) {
+        let follow = ControllerSettings::follow(self.settings.smoothing, dt);
+        let turn = self.look.step(dt, &self.settings) * self.settings.mouse_sensitivity;
+        self.heading = DQuat::from_axis_angle(self.normal, -turn.x) * self.heading;
+        self.pitch = (self.pitch - turn.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        self.altitude += (self.target_altitude - self.altitude) * follow;
+
+        let axes = self.keys.axes();
+        let wanted = DVec2::new(axes.x, axes.z) * self.speed * self.keys.speed_multiplier();
+        self.velocity += (wanted - self.velocity) * follow;
+
+        let right = self.heading.cross(self.normal);
+        let travel = (right * self.velocity.x + self.heading * self.velocity.y) * dt;
+
+        // a step along the surface is a rotation around the planet center
+        if let Some(axis) = self.normal.cross(travel).try_normalize() {
+            let angle = (travel.length() / self.planet_radius).min(FRAC_PI_2);
+            let rotation = DQuat::from_axis_angle(axis, angle);
+            self.normal = (rotation * self.normal).normalize();
+            self.heading = rotation * self.heading;
+        }
+        self.reproject_heading(right.cross(self.normal));
+
+        let forward = self.heading * self.pitch.cos() + self.normal * self.pitch.sin();
+        camera.eye = self.planet_center + self.normal * (self.planet_radius + self.altitude);
+        camera.target = camera.eye + forward;
+        camera.up = self.normal;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zooming_in_stops_above_the_surface() {
+        let radius = 2.0;
+        for kind in [ControllerKind::Orbit, ControllerKind::Arcball] {
+            let mut camera = Camera::default();
+            let mut controller = kind.create(ControllerSettings::default(), DVec3::ZERO, radius);
+            controller.attach(&camera);
+            for _ in 0..200 {
+                controller.scroll(5.0, None);
+                controller.update(&mut camera, 0.1);
+                assert!(camera.eye.length() > radius, "{kind:?} went below the surface: {}", camera.eye);
+            }
+            assert!(camera.eye.length() < radius * (1.0 + 2.0 * SURFACE_MARGIN), "{kind:?} stopped at {}", camera.eye);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::num::NonZeroU64;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{DVec3, Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::gpu::{load_shader, DepthMode};
+use crate::light::Light;
+
+/// Physical description of the planet's atmosphere.
+///
+/// Heights are in planet radii above the surface and scattering
+/// coefficients per planet radius, so the atmosphere keeps its look at any
+/// planet size. The planet radius itself is the LOD's, passed to
+/// `Atmosphere::update`. The defaults are Earth's ratios with the shell
+/// thickened so it reads well at the `Camera` distances we use.
+pub struct AtmosphereParams {
+    pub atmosphere_height: f32, // planet radii
+
+    pub rayleigh_scattering: Vec3, // per planet radius
+    pub rayleigh_scale_height: f32, // planet radii
+
+    pub mie_scattering: f32, // per planet radius
+    pub mie_scale_height: f32, // planet radii
+    pub mie_g: f32, // Henyey-Greenstein anisotropy, 0.76 is typical for haze
+
+    pub sun_intensity: f32,
+}
+
+impl Default for AtmosphereParams {
+    fn default() -> Self {
+        Self {
+            atmosphere_height: 0.15,
+
+            // Earth: (5.8, 13.5, 33.1)e-6 /m with H = 8 km, rescaled to H = 0.02
+            rayleigh_scattering: Vec3::new(2.32, 5.4, 13.24),
+            rayleigh_scale_height: 0.02,
+
+            // Earth: 21e-6 /m with H = 1.2 km, rescaled to H = 0.003
+            mie_scattering: 8.4,
+            mie_scale_height: 0.003,
+            mie_g: 0.76,
+
+            sun_intensity: 20.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct AtmosphereUniforms {
+    inv_view_proj: [[f32; 4]; 4],                  // 64 bytes @ offset 0
+    camera_pos: [f32; 3], planet_radius: f32,      // 16 bytes @ offset 64
+    sun_dir: [f32; 3], atmosphere_radius: f32,     // 16 bytes @ offset 80
+    sun_color: [f32; 3], sun_intensity: f32,       // 16 bytes @ offset 96
+    rayleigh: [f32; 3], rayleigh_scale_height: f32,// 16 bytes @ offset 112
+    mie: f32, mie_scale_height: f32, mie_g: f32,   // 12 bytes @ offset 128
+    log_depth_coef: f32,                           //  4 bytes @ offset 140
+    camera_forward: [f32; 3], depth_mode: u32,     // 16 bytes @ offset 144
+} // total size = 160 bytes
+
+/// Full-screen ray-marched Rayleigh + Mie single scattering pass.
+///
+/// Runs after the scene pass and reads the scene depth to know where each
+/// view ray stops, then blends the in-scattered light over the frame.
+pub struct Atmosphere {
+    pub params: AtmosphereParams,
+    pub enabled: bool,
+    pub center: DVec3, // world position of the planet it surrounds
+
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+const SHADER_PATH: &str = "src/shaders/atmosphere.wgsl";
+
+impl Atmosphere {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Atmosphere UBO"),
+            contents: bytemuck::bytes_of(&AtmosphereUniforms::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Atmosphere Bind Group Layout"),
+            entries: &[
+                // binding 0 = Atmosphere UBO
+                wgpu::BindGroupLayoutEntry {
+                    binding:    0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty:                wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size:  NonZeroU64::new(size_of::<AtmosphereUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                // binding 1 = scene depth
+                wgpu::BindGroupLayoutEntry {
+                    binding:    1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type:    wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = create_bind_group(device, &bind_group_layout, &uniform_buffer, depth_view);
+        let pipeline = create_pipeline(device, format, &bind_group_layout);
+
+        Self {
+            params: AtmosphereParams::default(),
+            enabled: true,
+            center: DVec3::ZERO,
+
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Rebinds the scene depth after the depth texture was recreated.
+    pub fn set_depth_view(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView) {
+        self.bind_group = create_bind_group(device, &self.bind_group_layout, &self.uniform_buffer, depth_view);
+    }
+
+    pub fn reload_pipeline(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) {
+        self.pipeline = create_pipeline(device, format, &self.bind_group_layout);
+    }
+
+    /// `view_proj` is the camera-relative one used for rendering.
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        planet_radius: f32,
+        view_proj: Mat4,
+        camera: &Camera,
+        light: &Light,
+        depth_mode: DepthMode,
+    ) {
+        let p = &self.params;
+        let uniforms = AtmosphereUniforms {
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            camera_pos: (camera.eye - self.center).as_vec3().to_array(),
+            planet_radius,
+            sun_dir: light.sun_dir().to_array(),
+            atmosphere_radius: planet_radius * (1.0 + p.atmosphere_height),
+            sun_color: light.color.to_array(),
+            sun_intensity: p.sun_intensity,
+            rayleigh: (p.rayleigh_scattering / planet_radius).to_array(),
+            rayleigh_scale_height: p.rayleigh_scale_height * planet_radius,
+            mie: p.mie_scattering / planet_radius,
+            mie_scale_height: p.mie_scale_height * planet_radius,
+            mie_g: p.mie_g,
+            log_depth_coef: DepthMode::log_coefficient(camera.projection.far()),
+            camera_forward: camera.forward().as_vec3().to_array(),
+            depth_mode: depth_mode.shader_index(),
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Encodes the atmosphere pass on top of `target`, which must already
+    /// hold the rendered scene.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Atmosphere Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1); // full-screen triangle
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    depth_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Atmosphere Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(depth_view),
+            },
+        ],
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader = load_shader("Atmosphere", SHADER_PATH, device);
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Atmosphere Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Atmosphere Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                // shader outputs premultiplied in-scatter + (1 - transmittance)
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+    })
+}
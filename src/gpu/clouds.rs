@@ -0,0 +1,305 @@
+use std::num::NonZeroU64;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{create_texture_rgba8, load_shader, DepthMode, SceneLayouts, SceneTarget};
+use crate::vertex;
+
+/// Tunables for the cloud shell.
+pub struct CloudParams {
+    pub altitude: f32,        // shell height above the surface, in planet radii
+    pub rotation_speed: f32,  // radians per second around +Y
+    pub opacity: f32,
+    pub shadow_strength: f32, // how much light the clouds take away from the surface
+    pub shadow_softness: f32, // blur radius of the shadow lookup, in octahedral UV
+}
+
+impl Default for CloudParams {
+    fn default() -> Self {
+        Self {
+            altitude: 0.03,
+            rotation_speed: 0.02,
+            opacity: 0.9,
+            shadow_strength: 0.6,
+            shadow_softness: 0.004,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct CloudUniforms {
+    radius: f32,          // 4 bytes @ offset 0
+    rotation_speed: f32,  // 4 bytes @ offset 4
+    opacity: f32,         // 4 bytes @ offset 8
+    shadow_strength: f32, // 4 bytes @ offset 12
+    shadow_softness: f32, // 4 bytes @ offset 16
+    _pad: [f32; 3],       // 12 bytes @ offset 20
+} // total size = 32 bytes
+
+/// Octahedral cloud texture on a sphere slightly above the surface.
+///
+/// Its bind group (group 3) is shared with the planet pipeline so the
+/// surface shader can look up cloud shadows.
+pub struct Clouds {
+    pub params: CloudParams,
+    pub enabled: bool,
+
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+const SHADER_PATH: &str = "src/shaders/clouds.wgsl";
+const TEXTURE_PATH: &str = "assets/clouds.png";
+const GENERATED_SIZE: u32 = 256;
+
+impl Clouds {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Clouds UBO"),
+            contents: bytemuck::bytes_of(&CloudUniforms::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Clouds Bind Group Layout"),
+            entries: &[
+                // binding 0 = Clouds UBO
+                wgpu::BindGroupLayoutEntry {
+                    binding:    0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty:                wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size:  NonZeroU64::new(size_of::<CloudUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                // binding 1 = cloud coverage texture
+                wgpu::BindGroupLayoutEntry {
+                    binding:    1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count: None,
+                },
+                // binding 2 = sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding:    2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let img = match image::open(TEXTURE_PATH) {
+            Ok(img) => img.flipv().into_rgba8(), // same convention as the planet texture
+            Err(err) => {
+                log::warn!("{TEXTURE_PATH} not loaded ({err}), generating clouds");
+                generate_cloud_image(GENERATED_SIZE)
+            }
+        };
+        let texture = create_texture_rgba8(device, queue, "Clouds Texture", &img, wgpu::TextureFormat::Rgba8Unorm);
+        let texture_view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Clouds Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline = create_pipeline(device, target, layouts, &bind_group_layout, depth_mode);
+
+        Self {
+            params: CloudParams::default(),
+            enabled: true,
+
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn reload_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) {
+        self.pipeline = create_pipeline(device, target, layouts, &self.bind_group_layout, depth_mode);
+    }
+
+    /// `planet_radius` is the LOD's, in world units.
+    pub fn update(&self, queue: &wgpu::Queue, planet_radius: f32) {
+        let p = &self.params;
+        // a disabled layer must not keep shadowing the surface either
+        let visible = if self.enabled { 1.0 } else { 0.0 };
+        let uniforms = CloudUniforms {
+            radius: planet_radius * (1.0 + p.altitude),
+            rotation_speed: p.rotation_speed,
+            opacity: p.opacity * visible,
+            shadow_strength: p.shadow_strength * visible,
+            shadow_softness: p.shadow_softness,
+            _pad: [0.0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draws the shell with whatever sphere mesh, frame bind group and
+    /// planet node groups (see `SceneRenderer::bind_node`) are bound. Call
+    /// after all opaque geometry.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass, num_indices: u32) {
+        if !self.enabled {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(3, &self.bind_group, &[]);
+        rpass.draw_indexed(0..num_indices, 0, 0..1);
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    target: SceneTarget,
+    layouts: &SceneLayouts,
+    clouds_layout: &wgpu::BindGroupLayout,
+    depth_mode: DepthMode,
+) -> wgpu::RenderPipeline {
+    let shader = load_shader("Clouds", SHADER_PATH, device);
+
+    let layout = layouts.pipeline_layout(device, "Clouds Pipeline Layout", Some(clouds_layout));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Clouds Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[vertex::Vertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some(&depth_mode.fragment_entry("fs_main")),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back), // only the near side of the shell
+            ..Default::default()
+        },
+        // transparent: test against the planet, don't occlude
+        depth_stencil: Some(depth_mode.depth_stencil_state(false)),
+        multisample: target.multisample_state(),
+        multiview: None,
+    })
+}
+
+/// Fallback cloud coverage: fBm value noise evaluated on the sphere, stored
+/// in the same octahedral layout the shaders sample with, so it has no seams.
+fn generate_cloud_image(size: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        let uv = [(x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32];
+        let dir = decode_octahedral(uv);
+
+        let mut sum = 0.0;
+        let mut amplitude = 0.5;
+        let mut p = dir * 4.0;
+        for _ in 0..5 {
+            sum += amplitude * value_noise(p);
+            p *= 2.0;
+            amplitude *= 0.5;
+        }
+
+        // sharpen into distinct cloud banks
+        let coverage = ((sum - 0.45) * 3.0).clamp(0.0, 1.0);
+        let c = (coverage * 255.0) as u8;
+        image::Rgba([c, c, c, c])
+    })
+}
+
+/// Inverse of `encode_octahedral` in the WGSL shaders.
+fn decode_octahedral(uv: [f32; 2]) -> Vec3 {
+    let (x, y) = (uv[0] * 2.0 - 1.0, uv[1] * 2.0 - 1.0);
+    let z = 1.0 - x.abs() - y.abs();
+    let (x, y) = if z < 0.0 {
+        ((1.0 - y.abs()) * x.signum(), (1.0 - x.abs()) * y.signum())
+    } else {
+        (x, y)
+    };
+    Vec3::new(x, y, z).normalize()
+}
+
+fn value_noise(p: Vec3) -> f32 {
+    let i = p.floor();
+    let f = p - i;
+    let u = f * f * (Vec3::splat(3.0) - 2.0 * f); // smoothstep
+
+    let corner = |dx: f32, dy: f32, dz: f32| hash(i + Vec3::new(dx, dy, dz));
+    let x00 = corner(0., 0., 0.) + (corner(1., 0., 0.) - corner(0., 0., 0.)) * u.x;
+    let x10 = corner(0., 1., 0.) + (corner(1., 1., 0.) - corner(0., 1., 0.)) * u.x;
+    let x01 = corner(0., 0., 1.) + (corner(1., 0., 1.) - corner(0., 0., 1.)) * u.x;
+    let x11 = corner(0., 1., 1.) + (corner(1., 1., 1.) - corner(0., 1., 1.)) * u.x;
+    let y0 = x00 + (x10 - x00) * u.y;
+    let y1 = x01 + (x11 - x01) * u.y;
+    y0 + (y1 - y0) * u.z
+}
+
+fn hash(p: Vec3) -> f32 {
+    let h = (p.x as i32).wrapping_mul(73_856_093)
+        ^ (p.y as i32).wrapping_mul(19_349_663)
+        ^ (p.z as i32).wrapping_mul(83_492_791);
+    let h = (h as u32).wrapping_mul(0x2c1b_3c6d) ^ ((h as u32) >> 15);
+    (h & 0xffff) as f32 / 65535.0
+}
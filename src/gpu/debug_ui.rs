@@ -0,0 +1,322 @@
+use glam::DVec3;
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::animation::Bookmark;
+use crate::camera::Camera;
+use crate::controller::{direction, yaw_pitch, CameraController};
+use crate::gpu::shader_params::{ParamValue, ParamWidget};
+use crate::gpu::{shader_statuses, Profiler, ShaderParams};
+use crate::light::Light;
+
+/// What the panel asked for that only `GpuState` can do.
+#[derive(Default)]
+pub struct DebugUiRequests {
+    pub reload_shaders: bool,
+    pub save_bookmark: Option<String>,   // under this name
+    pub fly_to_bookmark: Option<String>, // of this name
+}
+
+/// Everything the panel shows or edits.
+pub struct DebugPanel<'a> {
+    pub camera: &'a mut Camera,
+    pub controller: &'a mut dyn CameraController,
+    pub light: &'a mut Light,
+    pub profiler: &'a mut Profiler,
+    pub shader_params: &'a mut ShaderParams,
+    pub bookmarks: &'a [Bookmark],
+}
+
+/// Immediate-mode debug panel (egui), drawn over the finished frame.
+pub struct DebugUi {
+    pub visible: bool,
+
+    ctx: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    textures_to_free: Vec<egui::TextureId>,
+    pixels_per_point: f32,
+    bookmark_name: String, // being typed
+}
+
+impl DebugUi {
+    pub fn new(device: &wgpu::Device, window: &Window, format: wgpu::TextureFormat) -> Self {
+        let ctx = egui::Context::default();
+        let state = egui_winit::State::new(
+            ctx.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        Self {
+            visible: false,
+            ctx,
+            state,
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1, false),
+            paint_jobs: Vec::new(),
+            textures_to_free: Vec::new(),
+            pixels_per_point: window.scale_factor() as f32,
+            bookmark_name: String::new(),
+        }
+    }
+
+    /// Passes `event` to the panel; returns true if the panel used it, in
+    /// which case the app should ignore it.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.visible && self.state.on_window_event(window, event).consumed
+    }
+
+    /// Lays out this frame's panel and uploads what changed.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        window: &Window,
+        mut panel: DebugPanel,
+    ) -> DebugUiRequests {
+        let mut requests = DebugUiRequests::default();
+        self.paint_jobs.clear();
+        if !self.visible {
+            return requests;
+        }
+
+        let input = self.state.take_egui_input(window);
+        let output = self.ctx.run(input, |ctx| {
+            egui::Window::new("Debug").default_width(280.0).show(ctx, |ui| {
+                requests = panel.show(ui, &mut self.bookmark_name);
+            });
+        });
+        self.state.handle_platform_output(window, output.platform_output);
+
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        self.textures_to_free = output.textures_delta.free;
+        self.pixels_per_point = output.pixels_per_point;
+        self.paint_jobs = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        requests
+    }
+
+    /// Draws the panel over `view` in its own pass.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: (u32, u32),
+    ) {
+        if self.paint_jobs.is_empty() {
+            return;
+        }
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [size.0, size.1],
+            pixels_per_point: self.pixels_per_point,
+        };
+        // only paint callbacks produce command buffers, and the panel has none
+        self.renderer.update_buffers(device, queue, encoder, &self.paint_jobs, &screen);
+
+        let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Debug UI Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.renderer.render(&mut rpass.forget_lifetime(), &self.paint_jobs, &screen);
+
+        for id in self.textures_to_free.drain(..) {
+            self.renderer.free_texture(&id);
+        }
+    }
+}
+
+impl DebugPanel<'_> {
+    fn show(&mut self, ui: &mut egui::Ui, bookmark_name: &mut String) -> DebugUiRequests {
+        let mut requests = DebugUiRequests::default();
+
+        let dt = ui.input(|input| input.stable_dt).max(1e-6);
+        ui.label(format!("{:.0} fps, {:.2} ms", 1.0 / dt, dt * 1e3));
+        ui.checkbox(&mut self.profiler.enabled, "Profiler");
+        if self.profiler.enabled {
+            for line in self.profiler.summary() {
+                ui.monospace(line);
+            }
+        }
+
+        egui::CollapsingHeader::new("Camera").default_open(true).show(ui, |ui| self.camera_controls(ui));
+        egui::CollapsingHeader::new("Bookmarks").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(bookmark_name).hint_text("name").desired_width(140.0));
+                let name = bookmark_name.trim();
+                if ui.add_enabled(!name.is_empty(), egui::Button::new("Save view")).clicked() {
+                    requests.save_bookmark = Some(name.to_string());
+                    bookmark_name.clear();
+                }
+            });
+            for bookmark in self.bookmarks {
+                if ui.button(format!("✈ {}", bookmark.name)).clicked() {
+                    requests.fly_to_bookmark = Some(bookmark.name.clone());
+                }
+            }
+        });
+        egui::CollapsingHeader::new("Light").default_open(true).show(ui, |ui| self.light_controls(ui));
+        egui::CollapsingHeader::new("Shader parameters").default_open(true).show(ui, |ui| self.param_controls(ui));
+        egui::CollapsingHeader::new("Shaders").show(ui, |ui| {
+            if ui.button("Reload").clicked() {
+                requests.reload_shaders = true;
+            }
+            for shader in shader_statuses() {
+                let age = shader.loaded_at.elapsed().as_secs();
+                match &shader.error {
+                    None => ui.label(format!("✔ {} (loaded {}×, {age} s ago)", shader.path, shader.loads)),
+                    Some(error) => {
+                        ui.colored_label(egui::Color32::LIGHT_RED, format!("✖ {} ({age} s ago)", shader.path));
+                        ui.label(egui::RichText::new(error).monospace().small())
+                    }
+                };
+            }
+        });
+        requests
+    }
+
+    /// Yaw, pitch and distance of the eye around the target. Edits move the
+    /// camera and hand the new pose to the controller.
+    fn camera_controls(&mut self, ui: &mut egui::Ui) {
+        let camera = &mut *self.camera;
+        ui.label(format!("controller: {}", self.controller.name()));
+
+        let mut distance = camera.distance();
+        let (yaw, pitch) = yaw_pitch((camera.eye - camera.target) / distance);
+        let (mut yaw, mut pitch) = (yaw.to_degrees(), pitch.to_degrees());
+        let mut target = camera.target.to_array();
+
+        let mut changed = false;
+        egui::Grid::new("camera").num_columns(2).show(ui, |ui| {
+            ui.label("yaw");
+            changed |= ui.add(egui::DragValue::new(&mut yaw).speed(0.5).suffix("°")).changed();
+            ui.end_row();
+            ui.label("pitch");
+            changed |= ui
+                .add(egui::DragValue::new(&mut pitch).speed(0.5).range(-89.9..=89.9).suffix("°"))
+                .changed();
+            ui.end_row();
+            ui.label("distance");
+            let speed = distance * 0.01;
+            changed |= ui
+                .add(egui::DragValue::new(&mut distance).speed(speed).range(1e-3..=f64::MAX))
+                .changed();
+            ui.end_row();
+            ui.label("target");
+            ui.horizontal(|ui| {
+                for value in &mut target {
+                    changed |= ui.add(egui::DragValue::new(value).speed(0.01)).changed();
+                }
+            });
+            ui.end_row();
+        });
+        let eye = camera.eye;
+        ui.label(format!("eye: {:.3} {:.3} {:.3}", eye.x, eye.y, eye.z));
+
+        if changed {
+            camera.target = DVec3::from_array(target);
+            camera.eye = camera.target + direction(yaw.to_radians(), pitch.to_radians()) * distance;
+            self.controller.attach(camera);
+        }
+    }
+
+    /// One widget per reflected parameter, as its annotations ask.
+    fn param_controls(&mut self, ui: &mut egui::Ui) {
+        let params = &mut *self.shader_params;
+        ui.label(format!("{}:", params.name));
+        egui::Grid::new("shader params").num_columns(2).show(ui, |ui| {
+            for param in &mut params.params {
+                ui.label(&param.name);
+                match (&mut param.value, param.widget) {
+                    (ParamValue::Float(value), ParamWidget::Slider { min, max }) => {
+                        ui.add(egui::Slider::new(value, min..=max));
+                    }
+                    (ParamValue::Float(value), _) => {
+                        ui.add(egui::DragValue::new(value).speed(0.01));
+                    }
+                    (ParamValue::Int(value), ParamWidget::Checkbox) => {
+                        let mut on = *value != 0;
+                        if ui.checkbox(&mut on, "").changed() {
+                            *value = on as i64;
+                        }
+                    }
+                    (ParamValue::Int(value), ParamWidget::Slider { min, max }) => {
+                        ui.add(egui::Slider::new(value, min as i64..=max as i64));
+                    }
+                    (ParamValue::Int(value), _) => {
+                        ui.add(egui::DragValue::new(value));
+                    }
+                    (ParamValue::Vector(values), ParamWidget::Color) => {
+                        let mut color = [values[0] as f32, values[1] as f32, values[2] as f32];
+                        if ui.color_edit_button_rgb(&mut color).changed() {
+                            for (value, channel) in values.iter_mut().zip(color) {
+                                *value = channel as f64;
+                            }
+                        }
+                    }
+                    (ParamValue::Vector(values), widget) => {
+                        ui.horizontal(|ui| {
+                            for value in values {
+                                match widget {
+                                    ParamWidget::Slider { min, max } => ui.add(egui::Slider::new(value, min..=max)),
+                                    _ => ui.add(egui::DragValue::new(value).speed(0.01)),
+                                };
+                            }
+                        });
+                    }
+                    (ParamValue::Bool(_), _) => {}
+                }
+                ui.end_row();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                match params.save() {
+                    Ok(()) => println!("💾 shader params saved to {}", params.settings_path().display()),
+                    Err(err) => log::warn!("shader params not saved: {err:#}"),
+                }
+            }
+            if ui.button("Defaults").clicked() {
+                params.reset();
+            }
+        });
+    }
+
+    fn light_controls(&mut self, ui: &mut egui::Ui) {
+        let light = &mut *self.light;
+        egui::Grid::new("light").num_columns(2).show(ui, |ui| {
+            ui.label("direction");
+            let mut dir = light.dir;
+            ui.horizontal(|ui| {
+                for value in dir.as_mut() {
+                    ui.add(egui::DragValue::new(value).speed(0.01).range(-1.0..=1.0));
+                }
+            });
+            if dir.length() > 1e-3 {
+                light.dir = dir; // it gets normalized, so it can't be zero
+            }
+            ui.end_row();
+            ui.label("color");
+            let mut color = light.color.to_array();
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                light.color = color.into();
+            }
+            ui.end_row();
+        });
+    }
+}
@@ -0,0 +1,70 @@
+/// Diagnostic render modes for the planet surface.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Shaded,
+    /// Triangle edges over the shaded surface.
+    Wireframe,
+    /// World-space normals as colors.
+    Normals,
+    /// The shaded surface with a line along every vertex normal.
+    NormalVectors,
+    /// The octahedral texture coordinates, with a grid every 1/16.
+    OctahedralUv,
+    /// Linear view depth, from white at the nearest point of the planet to
+    /// black at its horizon.
+    Depth,
+    /// Checkerboard of the surface texture's texels, green at one texel per
+    /// pixel, red where texels are smaller and blue where they are larger.
+    TexelDensity,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Shaded => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Normals,
+            DebugView::Normals => DebugView::NormalVectors,
+            DebugView::NormalVectors => DebugView::OctahedralUv,
+            DebugView::OctahedralUv => DebugView::Depth,
+            DebugView::Depth => DebugView::TexelDensity,
+            DebugView::TexelDensity => DebugView::Shaded,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Shaded => "shaded",
+            DebugView::Wireframe => "wireframe",
+            DebugView::Normals => "normals",
+            DebugView::NormalVectors => "normal vectors",
+            DebugView::OctahedralUv => "octahedral UV",
+            DebugView::Depth => "linear depth",
+            DebugView::TexelDensity => "texel density",
+        }
+    }
+
+    /// Whether the surface color is replaced by the diagnostic one; the
+    /// cloud shell is left out then, as it would hide it.
+    pub fn replaces_shading(self) -> bool {
+        matches!(
+            self,
+            DebugView::Normals | DebugView::OctahedralUv | DebugView::Depth | DebugView::TexelDensity
+        )
+    }
+
+    /// Index shared with the WGSL side (`VIEW_*` constants in cube.frag.wgsl).
+    /// The wireframe is only drawn by the surface shader, from barycentrics,
+    /// when there is no line overlay pipeline to draw it.
+    pub fn shader_index(self, line_overlay: bool) -> u32 {
+        match self {
+            DebugView::Shaded | DebugView::NormalVectors => 0,
+            DebugView::Wireframe if line_overlay => 0,
+            DebugView::Wireframe => 1,
+            DebugView::Normals => 2,
+            DebugView::OctahedralUv => 3,
+            DebugView::Depth => 4,
+            DebugView::TexelDensity => 5,
+        }
+    }
+}
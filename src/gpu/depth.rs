@@ -0,0 +1,98 @@
+use crate::camera::Projection;
+
+/// How depth is projected, stored and compared.
+///
+/// Every pipeline with a depth attachment and every pass that reads the depth
+/// buffer goes through this, so switching modes keeps them consistent.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum DepthMode {
+    /// Regular `[0, 1]` perspective depth, `Less`, cleared to 1.
+    Standard,
+    /// Reverse-Z with an infinite far plane, `Greater`, cleared to 0.
+    #[default]
+    ReverseZ,
+    /// `log2(1 + w)` written from the fragment shader, `Less`, cleared to 1.
+    Logarithmic,
+}
+
+impl DepthMode {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub fn next(self) -> Self {
+        match self {
+            DepthMode::Standard => DepthMode::ReverseZ,
+            DepthMode::ReverseZ => DepthMode::Logarithmic,
+            DepthMode::Logarithmic => DepthMode::Standard,
+        }
+    }
+
+    /// Value the depth buffer holds where nothing was drawn.
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthMode::ReverseZ => 0.0,
+            DepthMode::Standard | DepthMode::Logarithmic => 1.0,
+        }
+    }
+
+    pub fn compare(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::ReverseZ => wgpu::CompareFunction::Greater,
+            DepthMode::Standard | DepthMode::Logarithmic => wgpu::CompareFunction::Less,
+        }
+    }
+
+    /// Mode actually used with `projection`. Orthographic depth is already
+    /// linear in distance and has a constant `w`, so it has no use for (and
+    /// cannot drive) the logarithmic mode; it falls back to standard depth.
+    pub fn for_projection(self, projection: &Projection) -> Self {
+        match (self, projection) {
+            (DepthMode::Logarithmic, Projection::Orthographic { .. }) => DepthMode::Standard,
+            (mode, _) => mode,
+        }
+    }
+
+    /// Scale for the logarithmic depth: `depth = log2(1 + w) * coef`.
+    pub fn log_coefficient(far: f32) -> f32 {
+        1.0 / (far + 1.0).log2()
+    }
+
+    /// Fragment entry point for shaders that provide a log-depth variant
+    /// (`<name>_log`, which writes `@builtin(frag_depth)`).
+    pub fn fragment_entry(self, name: &'static str) -> String {
+        match self {
+            DepthMode::Logarithmic => format!("{name}_log"),
+            DepthMode::Standard | DepthMode::ReverseZ => name.to_string(),
+        }
+    }
+
+    /// Index shared with the WGSL side (`DEPTH_*` constants).
+    pub fn shader_index(self) -> u32 {
+        match self {
+            DepthMode::Standard => 0,
+            DepthMode::ReverseZ => 1,
+            DepthMode::Logarithmic => 2,
+        }
+    }
+
+    /// For overlays redrawn over geometry already in the depth buffer: passes
+    /// on equal depth as well, never writes.
+    pub fn overlay_depth_stencil_state(self) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            depth_compare: match self.compare() {
+                wgpu::CompareFunction::Greater => wgpu::CompareFunction::GreaterEqual,
+                _ => wgpu::CompareFunction::LessEqual,
+            },
+            ..self.depth_stencil_state(false)
+        }
+    }
+
+    pub fn depth_stencil_state(self, depth_write_enabled: bool) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: Self::FORMAT,
+            depth_write_enabled,
+            depth_compare: self.compare(),
+            stencil: Default::default(),
+            bias: Default::default(),
+        }
+    }
+}
@@ -2,27 +2,80 @@ use crate::gpu::{
     create_depth_view,
     VertexShader,
     FragmentShader,
-    load_shader
+    load_shader,
+    create_texture_rgba8,
+    Atmosphere,
+    Clouds,
+    DebugPanel,
+    DebugUi,
+    DebugView,
+    DepthMode,
+    Graticule,
+    InstancedMesh,
+    MarkerRenderer,
+    Material,
+    MaterialDesc,
+    material::create_material_bind_group_layout,
+    Msaa,
+    OverlayText,
+    SceneTarget,
+    Planet,
+    planet::ChunkInstance,
+    GpuPick,
+    PickObject,
+    Picker,
+    PostProcess,
+    PostSettings,
+    HDR_FORMAT,
+    Profiler,
+    RenderGraph,
+    ScalarLayer,
+    SceneLayouts,
+    SceneRenderer,
+    scene_renderer::{create_object_bind_group_layout, MaterialId},
+    Screenshot,
+    ShaderParams,
+    TransientPool,
+    VectorLayers,
 };
 
-use std::num::{NonZeroU32, NonZeroU64};
+use std::num::NonZeroU64;
 use std::sync::Arc;
-use std::time::Instant;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use anyhow::{Context, Result};
-use wgpu::hal::Surface;
 use wgpu::util::DeviceExt;
 use wgpu::StoreOp;
 use winit::window::Window;
-use glam::{Mat4, Vec3};
+use glam::{DVec3, Mat4, Vec2};
 
-use crate::camera::Camera;
+use crate::animation::{Bookmarks, CameraAnimation, CameraPath, CameraView};
+use crate::camera::{ray_sphere, Camera};
+use crate::controller::{CameraController, ControllerKind, ControllerSettings};
+use crate::light::Light;
+use crate::markers::{Marker, MarkerId, Markers};
+use crate::moon::{MoonAssets, MoonSystem};
+use crate::picking::{PickResult, FIRST_NODE_ID, PLANET_ID};
+use crate::scene::{NodeId, SceneGraph, Transform};
+use crate::swarm::Swarm;
+use crate::uniform::{CameraUniforms, Uniforms};
 
 use crate::vertex;
 
+const BOOKMARKS_PATH: &str = "bookmarks.toml";
+const VECTOR_LAYERS_PATH: &str = "overlays.toml";
+const SCALAR_LAYER_PATH: &str = "data.toml";
+const POST_SETTINGS_PATH: &str = "post.toml";
+const SHADER_PARAMS_PATH: &str = "shader_params.toml";
+const PLANET_FS_PATH: &str = "src/shaders/cube.frag.wgsl";
+const RENDER_GRAPH_DOT_PATH: &str = "render_graph.dot";
+const PREFERRED_SAMPLE_COUNT: u32 = 4;
+const SWARM_SIZE: usize = 100_000;
+
 struct UBOs {
     camera_buffer: wgpu::Buffer,
-    model_buffer:  wgpu::Buffer,
     light_buffer:  wgpu::Buffer,
+    globals_buffer: wgpu::Buffer,
 }
 
 pub struct GpuState {
@@ -30,27 +83,74 @@ pub struct GpuState {
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
-    pipeline: wgpu::RenderPipeline,
+    debug_pipelines: DebugPipelines,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
 
     ubos: UBOs,
-    ubo_bind_group: wgpu::BindGroup,
+    layouts: SceneLayouts, // bind groups by update frequency: per frame, per material, per object
+    frame_bind_group: wgpu::BindGroup,
 
     start_time: Instant,
+    globals: Uniforms,
 
     pub camera: Camera,
+    pub controller: Box<dyn CameraController>,
+    controller_kind: ControllerKind,
+    animation: Option<CameraAnimation>, // drives the camera instead of the controller while set
+    pub bookmarks: Bookmarks,
+    pub controller_settings: ControllerSettings,
+    last_frame: Instant,
+    pub light: Light,
+    pub planet: Planet,
+    pub clouds: Clouds,
+    pub graticule: Graticule,
+    pub vector_layers: VectorLayers,
+    pub scalar_layer: ScalarLayer,
+    pub satellites: InstancedMesh,
+    swarm: Option<Swarm>, // drives `satellites` while set
+    pub scene: SceneGraph,
+    planet_node: NodeId, // root of the scene graph; the planet follows its translation
+    planet_material: MaterialId, // surface texture, factors from `planet_params`
+    pub scene_renderer: SceneRenderer,
+    moon_assets: MoonAssets,
+    moon: Option<MoonSystem>, // animates its nodes of `scene` while set
+    markers: Markers,
+    pub overlay_text: OverlayText,
+    pub marker_renderer: MarkerRenderer,
+    pub atmosphere: Atmosphere,
+    pub post: PostProcess,
+    pub planet_params: ShaderParams, // tunables of the planet fragment shader, uploaded as `planet_material`'s factors
+    msaa: Msaa,
+    transients: TransientPool,
+    pub profiler: Profiler,
+    pub debug_ui: DebugUi,
     pub dragging: bool,
-    pub last_mouse_pos: (f32, f32),
+    pub panning: bool,
+    pub last_mouse_pos: Option<(f32, f32)>, // None until the cursor is over the window
 
     pub depth_view: wgpu::TextureView,
+    depth_mode: DepthMode,
+    debug_view: DebugView,
+    screenshot_requested: bool,
+    graph_dump_requested: bool,
+
+    picker: Picker,
+    surface_image: image::RgbaImage, // the planet texture as uploaded, for CPU lookups
+    pub last_pick: Option<PickResult>,
 }
 
 fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
     pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
         label: None,
-        required_features: wgpu::Features::empty(),
+        // wireframe is optional, as are MSAA sample counts other than 1 and 4
+        // and GPU timings
+        required_features: adapter.features()
+            & (wgpu::Features::POLYGON_MODE_LINE
+                | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                | wgpu::Features::TIMESTAMP_QUERY
+                | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS),
         required_limits: if cfg!(target_arch = "wasm32") {
             wgpu::Limits::downlevel_webgl2_defaults()
         } else {
@@ -70,7 +170,7 @@ fn create_surface_static(instance: &wgpu::Instance, window: &Arc<Window>) -> wgp
 fn request_adapter(instance: &wgpu::Instance, surface: &wgpu::Surface) -> Result<wgpu::Adapter> {
     pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
         power_preference: wgpu::PowerPreference::HighPerformance,
-        compatible_surface: Some(&surface),
+        compatible_surface: Some(surface),
         force_fallback_adapter: false,
     })).context("Failed to request adapter")
 }
@@ -82,10 +182,14 @@ pub fn create_gpu_state(window: &Arc<Window>) -> Result<GpuState> {
     let adapter = request_adapter(&instance, &surface)?;
     let (device, queue) = request_device(&adapter)?;
     let surface_caps = surface.get_capabilities(&adapter);
-    let surface_format = surface_caps.formats[0]; // choose a supported format?
+    // post-processing writes linear colors and relies on the target to
+    // encode them; without an sRGB format the final passes encode instead
+    let surface_format = surface_caps.formats.iter().copied().find(|f| f.is_srgb()).unwrap_or(surface_caps.formats[0]);
 
     let config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        // screenshots copy straight from the frame when the surface allows it
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
         format: surface_format,
         width: window.inner_size().width,
         height: window.inner_size().height,
@@ -96,137 +200,59 @@ pub fn create_gpu_state(window: &Arc<Window>) -> Result<GpuState> {
     };
     surface.configure(&device, &config);
 
-    let uniform_bind_group_layout =
-        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("UBO Bind Group Layout"),
-            entries: &[
-                // binding 0 = Camera UBO (mat4x4)
-                wgpu::BindGroupLayoutEntry {
-                    binding:    0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty:                wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size:  Some( NonZeroU64::new(64).unwrap() ), // 4×4 f32
-                    },
-                    count: None,
-                },
-                // binding 1 = Model UBO (mat4x4)
-                wgpu::BindGroupLayoutEntry {
-                    binding:    1,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty:                wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size:  Some( NonZeroU64::new(64).unwrap() ),
-                    },
-                    count: None,
-                },
-                // binding 2 = Light UBO (vec3 + padding)
-                wgpu::BindGroupLayoutEntry {
-                    binding:    2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty:                wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size:  Some( NonZeroU64::new(32).unwrap() ), // vec3 + pad
-                    },
-                    count: None,
-                },
-                // binding=3: the texture view
-                wgpu::BindGroupLayoutEntry {
-                    binding:    3,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type:     wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension:  wgpu::TextureViewDimension::D2,
-                        multisampled:    false,
-                    },
-                    count: None,
-                },
-                // binding=4: the sampler
-                wgpu::BindGroupLayoutEntry {
-                    binding:    4,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-    });
+    let layouts = SceneLayouts {
+        frame: create_frame_bind_group_layout(&device),
+        material: create_material_bind_group_layout(&device),
+        object: create_object_bind_group_layout(&device),
+    };
+    let msaa = Msaa::new(&adapter, &device, PREFERRED_SAMPLE_COUNT);
+    let scene_target = msaa.scene_target();
 
     // 2.1 Camera UBO
-    let aspect = config.width as f32 / config.height as f32;
-    let proj   = Mat4::perspective_rh_gl(45f32.to_radians(), aspect, 0.1, 100.0);
-    let view   = Mat4::look_at_rh(Vec3::new(3.,2.,4.), Vec3::ZERO, Vec3::Y);
-    let view_proj: [[f32;4];4] = (proj * view).to_cols_array_2d();
+    let depth_mode = DepthMode::default();
+    let camera = Camera {
+        aspect: config.width as f32 / config.height as f32,
+        ..Default::default()
+    };
+    let camera_uniforms = CameraUniforms::new(
+        camera.projection_matrix(depth_mode) * camera.view_matrix(),
+        DepthMode::log_coefficient(camera.projection.far()),
+    );
 
     let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("Camera UBO"),
-        contents: bytemuck::cast_slice(&view_proj),
+        contents: bytemuck::bytes_of(&camera_uniforms),
         usage:  wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
     });
 
-    // 2.2 Model UBO (we’ll rotate around Y)
-    let model_mat: [[f32;4];4] = Mat4::IDENTITY.to_cols_array_2d();
-    let model_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Model UBO"),
-        contents: bytemuck::cast_slice(&model_mat),
-        usage:  wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-    });
-
-    // 2.3 Light UBO
+    // 2.2 Light UBO
     // direction + color, pad to 16 bytes
-    let light_dir_color: [[f32;4];2] = [
-        [ -0.8, -1.0, -1.0, 0.0 ],  // light direction
-        [ 0.0,  1.0,  1.0, 0.0 ],  // light color
-    ];
+    let light = Light::default();
+    let light_dir_color = light.to_uniform();
     let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label:    Some("Light UBO"),
         contents: bytemuck::cast_slice(&light_dir_color),  // &[ [f32;4];2 ]
         usage:    wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
     });
 
+    // 2.3 Globals UBO (time is advanced every frame)
+    let globals = Uniforms::new();
+    let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label:    Some("Globals UBO"),
+        contents: bytemuck::bytes_of(&globals),
+        usage:    wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
     // 1. Load and flip Y so UV [0,0] is bottom-left
     let img = image::open("assets/texture.png")
         .expect("texture.png not found")
         .flipv()
         .into_rgba8();
 
-    let (width, height) = img.dimensions();
-    let size = wgpu::Extent3d {
-        width, height, depth_or_array_layers: 1,
-    };
-
-    // 2. Create the GPU texture
-    let texture = device.create_texture(&wgpu::TextureDescriptor {
-        label: Some("Cube Texture"),
-        size,
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-        view_formats: &[],
-    });
+    // 2. Create the GPU texture & upload pixel data
+    let texture = create_texture_rgba8(&device, &queue, "Cube Texture", &img, wgpu::TextureFormat::Rgba8UnormSrgb);
 
-    // 3. Upload pixel data
-    queue.write_texture(
-        wgpu::TexelCopyTextureInfo {
-            texture: &texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        &img,
-        wgpu::TexelCopyBufferLayout {
-            offset: 0,
-            bytes_per_row: Some(NonZeroU32::new(4 * width).unwrap().into()),
-            rows_per_image: Some(NonZeroU32::new(height).unwrap().into()),
-        },
-        size,
-    );
-
-    // 4. Create a view & sampler
+    // 3. Create a view & sampler
     let texture_view = texture.create_view(&Default::default());
     let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
         address_mode_u: wgpu::AddressMode::Repeat,
@@ -236,9 +262,9 @@ pub fn create_gpu_state(window: &Arc<Window>) -> Result<GpuState> {
         ..Default::default()
     });
 
-    // 2.4 Single bind group with 3 entries
-    let ubo_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &uniform_bind_group_layout,
+    // 2.4 Bind group for the per-frame data
+    let frame_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &layouts.frame,
         entries: &[
             wgpu::BindGroupEntry {
                 binding: 0,
@@ -246,114 +272,310 @@ pub fn create_gpu_state(window: &Arc<Window>) -> Result<GpuState> {
             },
             wgpu::BindGroupEntry {
                 binding: 1,
-                resource: model_buffer.as_entire_binding(),
-            },
-            wgpu::BindGroupEntry {
-                binding: 2,
                 resource: light_buffer.as_entire_binding(),
             },
             wgpu::BindGroupEntry {
-                binding: 3,
-                resource: wgpu::BindingResource::TextureView(&texture_view),
-            },
-            wgpu::BindGroupEntry {
-                binding: 4,
-                resource: wgpu::BindingResource::Sampler(&sampler),
+                binding: 2,
+                resource: globals_buffer.as_entire_binding(),
             },
         ],
-        label: Some("UBO Bind Group"),
+        label: Some("Frame Bind Group"),
     });
 
     let vs_module = VertexShader(load_shader("Cube VS", "src/shaders/cube.vert.wgsl", &device));
-    let fs_module = FragmentShader(load_shader("Cube FS", "src/shaders/cube.frag.wgsl", &device));
+    let fs_module = FragmentShader(load_shader("Cube FS", PLANET_FS_PATH, &device));
 
-    let pipeline = create_pipeline(&device, &config, &uniform_bind_group_layout, &vs_module, &fs_module);
+    let clouds = Clouds::new(
+        &device, &queue, scene_target,
+        &layouts,
+        depth_mode,
+    );
+    let graticule = Graticule::new(
+        &device, scene_target,
+        &layouts,
+        depth_mode,
+    );
+    let mut vector_layers = VectorLayers::new(
+        &device, scene_target,
+        &layouts,
+        depth_mode,
+        VECTOR_LAYERS_PATH,
+    );
+    let mut scalar_layer = ScalarLayer::new(
+        &device, &queue, scene_target,
+        &layouts,
+        depth_mode,
+        SCALAR_LAYER_PATH,
+    );
+    scalar_layer.reload(&device, &queue);
+    let planet_params = ShaderParams::new("planet", PLANET_FS_PATH, SHADER_PARAMS_PATH);
+    let planet_layout = layouts.pipeline_layout(&device, "Planet Pipeline Layout", Some(clouds.bind_group_layout()));
+    let pipeline = create_pipeline(
+        &device, scene_target,
+        &planet_layout,
+        &vs_module, &fs_module,
+        depth_mode,
+        PlanetStyle::Filled,
+    );
+    let debug_pipelines = create_debug_pipelines(&device, scene_target, &planet_layout, &vs_module, &fs_module, depth_mode);
     let depth_view = create_depth_view(&device, &config);
+    let atmosphere = Atmosphere::new(&device, HDR_FORMAT, &depth_view);
+    let post = PostProcess::new(&device, &queue, &config, PostSettings::load(POST_SETTINGS_PATH));
+
+    let profiler = Profiler::new(&device, &queue);
+    let planet = Planet::new(&device);
+    let satellites = InstancedMesh::new(
+        &device, scene_target,
+        &layouts,
+        depth_mode,
+        (vertex::VERTICES, vertex::INDICES),
+        planet.position,
+    );
+    let mut scene_renderer = SceneRenderer::new(&device, &queue, scene_target, &layouts, depth_mode);
+    // the planet is a node like any other, drawn through its own pipeline
+    let planet_mesh = scene_renderer.add_external_mesh();
+    let planet_material = scene_renderer.add_material(
+        &device,
+        Material::new(&device, &queue, &layouts.material, MaterialDesc {
+            name: "planet",
+            factors: &planet_params.bytes(),
+            base_color_map: Some(texture_view),
+            sampler: Some(sampler),
+            unlit: false,
+        }),
+    );
+    scene_renderer.set_custom_pipeline(planet_material, pipeline, Some(clouds.bind_group().clone()));
+    let mut scene = SceneGraph::default();
+    let planet_node = scene
+        .add(None, Transform::from_translation(planet.position))
+        .context("Failed to add the planet node")?;
+    if let Some(node) = scene.node_mut(planet_node) {
+        node.mesh = Some(planet_mesh);
+        node.material = Some(planet_material);
+    }
+    let moon_assets = MoonAssets::load(&device, &queue, &mut scene_renderer, &layouts.material);
+    let picker = Picker::new(&device);
+    let overlay_text = OverlayText::new(&device, &queue, &config);
+    let marker_renderer = MarkerRenderer::new(&overlay_text);
+    let debug_ui = DebugUi::new(&device, window, config.format);
+    vector_layers.reload(&device, planet.lod.params.planet_radius as f64);
+    let controller_kind = ControllerKind::default();
+    let controller_settings = ControllerSettings::default();
+    let mut controller = controller_kind.create(
+        controller_settings,
+        planet.position,
+        planet.lod.params.planet_radius as f64,
+    );
+    controller.attach(&camera);
+
+    // unit cube-sphere for the cloud shell
+    let (vertices, indices) = vertex::cube_sphere(32);
 
     let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Cube Vertex Buffer"),
-        contents: bytemuck::cast_slice(vertex::VERTICES),
+        label: Some("Sphere Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
         usage: wgpu::BufferUsages::VERTEX,
     });
 
     let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Cube Index Buffer"),
-        contents: bytemuck::cast_slice(vertex::INDICES),
+        label: Some("Sphere Index Buffer"),
+        contents: bytemuck::cast_slice(&indices),
         usage: wgpu::BufferUsages::INDEX,
     });
 
-    let num_indices = vertex::INDICES.len() as u32;
+    let num_indices = indices.len() as u32;
 
     Ok(GpuState {
         surface,
         device,
         queue,
         config,
-        pipeline,
+        debug_pipelines,
 
         vertex_buffer,
         index_buffer,
         num_indices,
 
-        ubos: UBOs { camera_buffer, model_buffer, light_buffer },
-        ubo_bind_group,
+        ubos: UBOs { camera_buffer, light_buffer, globals_buffer },
+        layouts,
+        frame_bind_group,
 
         start_time: std::time::Instant::now(),
+        globals,
 
-        camera: Camera::default(),
+        camera,
+        controller,
+        controller_kind,
+        animation: None,
+        bookmarks: Bookmarks::load(BOOKMARKS_PATH),
+        controller_settings,
+        last_frame: Instant::now(),
+        light,
+        planet,
+        clouds,
+        graticule,
+        vector_layers,
+        scalar_layer,
+        satellites,
+        swarm: None,
+        scene,
+        planet_node,
+        planet_material,
+        scene_renderer,
+        moon_assets,
+        moon: None,
+        markers: Markers::default(),
+        overlay_text,
+        marker_renderer,
+        atmosphere,
+        post,
+        planet_params,
+        msaa,
+        transients: TransientPool::default(),
+        profiler,
+        debug_ui,
         dragging: false,
-        last_mouse_pos: (0.0, 0.0),
+        panning: false,
+        last_mouse_pos: None,
 
         depth_view,
+        depth_mode,
+        debug_view: DebugView::default(),
+        screenshot_requested: false,
+        graph_dump_requested: false,
+
+        picker,
+        surface_image: img,
+        last_pick: None,
+    })
+}
+
+fn create_frame_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Frame Bind Group Layout"),
+        entries: &[
+            // binding 0 = Camera UBO (mat4x4 + log-depth coefficient)
+            wgpu::BindGroupLayoutEntry {
+                binding:    0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty:                wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size:  NonZeroU64::new(size_of::<CameraUniforms>() as u64),
+                },
+                count: None,
+            },
+            // binding 1 = Light UBO (vec3 + padding)
+            wgpu::BindGroupLayoutEntry {
+                binding:    1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty:                wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size:  Some( NonZeroU64::new(32).unwrap() ), // vec3 + pad
+                },
+                count: None,
+            },
+            // binding 2 = Globals UBO (time etc.)
+            wgpu::BindGroupLayoutEntry {
+                binding:    2,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty:                wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size:  NonZeroU64::new(size_of::<Uniforms>() as u64),
+                },
+                count: None,
+            },
+        ],
     })
 }
 
+/// What a planet pipeline draws. All of them run `vs_main` over the chunk
+/// vertices and share the bind groups.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum PlanetStyle {
+    Filled,
+    WireframeOverlay, // triangle edges over the surface already drawn
+    NormalVectors,    // line list along the vertex normals, see `Planet::draw_normals`
+}
+
+/// Line pipelines for the debug views that draw over the surface.
+struct DebugPipelines {
+    wireframe: Option<wgpu::RenderPipeline>, // needs POLYGON_MODE_LINE; otherwise the surface shader draws the edges
+    normal_vectors: wgpu::RenderPipeline,
+}
+
+fn create_debug_pipelines(
+    device: &wgpu::Device,
+    target: SceneTarget,
+    layout: &wgpu::PipelineLayout,
+    vs_shader: &VertexShader,
+    fs_shader: &FragmentShader,
+    depth_mode: DepthMode,
+) -> DebugPipelines {
+    let pipeline = |style| create_pipeline(device, target, layout, vs_shader, fs_shader, depth_mode, style);
+    DebugPipelines {
+        wireframe: device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+            .then(|| pipeline(PlanetStyle::WireframeOverlay)),
+        normal_vectors: pipeline(PlanetStyle::NormalVectors),
+    }
+}
+
 fn create_pipeline(
     device: &wgpu::Device,
-    config: &wgpu::SurfaceConfiguration,
-    uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    target: SceneTarget,
+    layout: &wgpu::PipelineLayout,
     vs_shader: &VertexShader,
     fs_shader: &FragmentShader,
+    depth_mode: DepthMode,
+    style: PlanetStyle,
 ) -> wgpu::RenderPipeline {
-    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("Pipeline Layout"),
-        bind_group_layouts: &[uniform_bind_group_layout],
-        push_constant_ranges: &[],
-    });
-
+    let (label, fragment_entry) = match style {
+        PlanetStyle::Filled => ("Render Pipeline", "fs_main"),
+        PlanetStyle::WireframeOverlay => ("Wireframe Overlay Pipeline", "fs_wireframe"),
+        PlanetStyle::NormalVectors => ("Normal Vectors Pipeline", "fs_normal_vectors"),
+    };
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         cache: None,
-        label: Some("Render Pipeline"),
-        layout: Some(&layout),
+        label: Some(label),
+        layout: Some(layout),
         vertex: wgpu::VertexState {
             compilation_options: Default::default(),
-            module: &vs_shader,
+            module: vs_shader,
             entry_point: Some("vs_main"),
-            buffers: &[vertex::Vertex::desc()],
+            buffers: &[vertex::Vertex::desc(), ChunkInstance::desc()],
         },
         fragment: Some(wgpu::FragmentState {
             compilation_options: Default::default(),
-            module: &fs_shader,
-            entry_point: Some("fs_main"),
+            module: fs_shader,
+            entry_point: Some(&depth_mode.fragment_entry(fragment_entry)),
             targets: &[Some(wgpu::ColorTargetState {
-                format: config.format,
+                format: target.format,
                 blend: None,
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }),
         primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
+            topology: match style {
+                PlanetStyle::NormalVectors => wgpu::PrimitiveTopology::LineList,
+                _ => wgpu::PrimitiveTopology::TriangleList,
+            },
             strip_index_format: None,
+            polygon_mode: match style {
+                PlanetStyle::WireframeOverlay => wgpu::PolygonMode::Line,
+                PlanetStyle::Filled | PlanetStyle::NormalVectors => wgpu::PolygonMode::Fill,
+            },
             ..Default::default()
         },
-        depth_stencil: Some(wgpu::DepthStencilState {
-            format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less, // passes if new depth < old
-            stencil: Default::default(),
-            bias: Default::default(),
+        depth_stencil: Some(match style {
+            PlanetStyle::Filled => depth_mode.depth_stencil_state(true),
+            PlanetStyle::WireframeOverlay => depth_mode.overlay_depth_stencil_state(),
+            PlanetStyle::NormalVectors => depth_mode.depth_stencil_state(false),
         }),
-        multisample: Default::default(),
+        multisample: target.multisample_state(),
         multiview: None,
     })
 }
@@ -361,101 +583,507 @@ fn create_pipeline(
 impl GpuState {
     pub fn reload_shader_pipeline(&mut self) {
         let vs_module = VertexShader(load_shader("Cube VS", "src/shaders/cube.vert.wgsl", &self.device));
-        let fs_module = FragmentShader(load_shader("Cube FS", "src/shaders/cube.frag.wgsl", &self.device));
-
-        let uniform_bind_group_layout =
-            self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("UBO Bind Group Layout"),
-                entries: &[
-                    // binding 0 = Camera UBO (mat4x4)
-                    wgpu::BindGroupLayoutEntry {
-                        binding:    0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty:                wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size:  Some( NonZeroU64::new(64).unwrap() ), // 4×4 f32
-                        },
-                        count: None,
-                    },
-                    // binding 1 = Model UBO (mat4x4)
-                    wgpu::BindGroupLayoutEntry {
-                        binding:    1,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty:                wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size:  Some( NonZeroU64::new(64).unwrap() ),
-                        },
-                        count: None,
-                    },
-                    // binding 2 = Light UBO (vec3 + padding)
-                    wgpu::BindGroupLayoutEntry {
-                        binding:    2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty:                wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size:  Some( NonZeroU64::new(32).unwrap() ), // vec3 + pad
-                        },
-                        count: None,
-                    },
-                    // binding=3: the texture view
-                    wgpu::BindGroupLayoutEntry {
-                        binding:    3,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type:     wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension:  wgpu::TextureViewDimension::D2,
-                            multisampled:    false,
-                        },
-                        count: None,
-                    },
-                    // binding=4: the sampler
-                    wgpu::BindGroupLayoutEntry {
-                        binding:    4,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-        });
+        let fs_module = FragmentShader(load_shader("Cube FS", PLANET_FS_PATH, &self.device));
 
-        let pipeline = create_pipeline(&self.device, &self.config, &uniform_bind_group_layout, &vs_module, &fs_module);
-        self.pipeline = pipeline;
+        self.planet_params.reflect();
+
+        let depth_mode = self.active_depth_mode();
+        let scene_target = self.msaa.scene_target();
+        let planet_layout =
+            self.layouts.pipeline_layout(&self.device, "Planet Pipeline Layout", Some(self.clouds.bind_group_layout()));
+        let pipeline = create_pipeline(
+            &self.device, scene_target,
+            &planet_layout,
+            &vs_module, &fs_module,
+            depth_mode,
+            PlanetStyle::Filled,
+        );
+        self.debug_pipelines =
+            create_debug_pipelines(&self.device, scene_target, &planet_layout, &vs_module, &fs_module, depth_mode);
+        let layouts = &self.layouts;
+        self.clouds.reload_pipeline(&self.device, scene_target, layouts, depth_mode);
+        self.graticule.reload_pipeline(&self.device, scene_target, layouts, depth_mode);
+        self.vector_layers.reload_pipeline(&self.device, scene_target, layouts, depth_mode);
+        self.scalar_layer.reload_pipeline(&self.device, scene_target, layouts, depth_mode);
+        self.satellites.reload_pipeline(&self.device, scene_target, layouts, depth_mode);
+        self.scene_renderer.reload_pipeline(&self.device, scene_target, layouts, depth_mode);
+        let clouds = Some(self.clouds.bind_group().clone());
+        self.scene_renderer.set_custom_pipeline(self.planet_material, pipeline, clouds);
+        self.atmosphere.reload_pipeline(&self.device, HDR_FORMAT);
+        self.post.reload_pipeline(&self.device, &self.config);
+        self.msaa.reload_pipeline(&self.device);
+        self.overlay_text.reload_pipeline(&self.device, &self.config);
 
         println!("✅ shader pipeline reloaded");
     }
 
+    /// The selected depth mode; see `active_depth_mode` for the one in use.
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    fn active_depth_mode(&self) -> DepthMode {
+        self.depth_mode.for_projection(&self.camera.projection)
+    }
+
+    /// Switches the depth strategy; pipelines are rebuilt to match.
+    pub fn set_depth_mode(&mut self, mode: DepthMode) {
+        self.depth_mode = mode;
+        self.reload_shader_pipeline();
+    }
+
+    pub fn controller_kind(&self) -> ControllerKind {
+        self.controller_kind
+    }
+
+    /// Swaps the camera controller; the new one starts from the current view.
+    pub fn set_controller(&mut self, kind: ControllerKind) {
+        let mut controller = kind.create(
+            self.controller_settings,
+            self.planet.position,
+            self.planet.lod.params.planet_radius as f64,
+        );
+        controller.attach(&self.camera);
+        controller.set_dragging(self.dragging);
+        self.controller = controller;
+        self.controller_kind = kind;
+    }
+
+    /// Stores the current view as bookmark `name`, or under a generated
+    /// name if None.
+    pub fn save_bookmark(&mut self, name: Option<&str>) {
+        let name = name.map_or_else(|| self.bookmarks.unused_name(), str::to_string);
+        match self.bookmarks.add(&name, CameraView::from_camera(&self.camera)) {
+            Ok(bookmark) => println!("🔖 saved bookmark '{}'", bookmark.name),
+            Err(err) => log::warn!("bookmark not saved: {err:#}"),
+        }
+    }
+
+    /// Flies along a great circle to the next bookmark.
+    pub fn fly_to_next_bookmark(&mut self) {
+        let Some(bookmark) = self.bookmarks.next() else {
+            println!("no bookmarks yet");
+            return;
+        };
+        let name = bookmark.name.clone();
+        self.fly_to_bookmark(&name);
+    }
+
+    /// Flies along a great circle to the bookmark called `name`.
+    pub fn fly_to_bookmark(&mut self, name: &str) {
+        let from = CameraView::from_camera(&self.camera);
+        let Some(bookmark) = self.bookmarks.get(name) else {
+            log::warn!("no bookmark '{name}'");
+            return;
+        };
+        println!("✈️ flying to '{}'", bookmark.name);
+        self.animation = Some(CameraAnimation::new(CameraPath::flight(from, bookmark.view)));
+    }
+
+    /// Plays a keyframed camera path from `path`.
+    pub fn play_camera_path(&mut self, path: &str) {
+        match CameraPath::load(path) {
+            Ok(camera_path) => {
+                println!("🎬 playing {path} ({:.1}s)", camera_path.duration());
+                self.animation = Some(CameraAnimation::new(camera_path));
+            }
+            Err(err) => log::warn!("camera path not played: {err:#}"),
+        }
+    }
+
+    /// Ends a running flight or path and hands the camera back to the controller.
+    pub fn stop_animation(&mut self) {
+        if self.animation.take().is_some() {
+            self.controller.attach(&self.camera);
+        }
+    }
+
+    /// Puts the camera back where it started; projection and controller stay.
+    pub fn reset_camera(&mut self) {
+        self.animation = None;
+        self.camera = Camera {
+            projection: self.camera.projection,
+            aspect: self.camera.aspect,
+            ..Default::default()
+        };
+        self.set_controller(self.controller_kind);
+    }
+
+    /// Moves to the next MSAA sample count the adapter supports and rebuilds
+    /// the scene pipelines for it.
+    pub fn cycle_sample_count(&mut self) -> u32 {
+        let next = self.msaa.next_sample_count();
+        if self.msaa.set_sample_count(&self.device, next) {
+            self.reload_shader_pipeline();
+        }
+        self.msaa.sample_count()
+    }
+
+    /// Switches between the wireframe debug view and the shaded surface.
+    pub fn toggle_wireframe(&mut self) -> DebugView {
+        let view = if self.debug_view == DebugView::Wireframe { DebugView::Shaded } else { DebugView::Wireframe };
+        self.set_debug_view(view)
+    }
+
+    /// Switches to the next diagnostic view of the planet surface.
+    pub fn cycle_debug_view(&mut self) -> DebugView {
+        self.set_debug_view(self.debug_view.next())
+    }
+
+    fn set_debug_view(&mut self, view: DebugView) -> DebugView {
+        self.debug_view = view;
+        self.planet.show_normals = view == DebugView::NormalVectors;
+        if view == DebugView::Wireframe && self.debug_pipelines.wireframe.is_none() {
+            log::warn!("no POLYGON_MODE_LINE on this adapter, drawing the wireframe from barycentrics");
+        }
+        view
+    }
+
+    /// Spawns a swarm of satellites around the planet, or removes it.
+    pub fn toggle_swarm(&mut self) -> usize {
+        match self.swarm.take() {
+            Some(swarm) => swarm.despawn(&mut self.satellites),
+            None => {
+                let radius = self.planet.lod.params.planet_radius as f64;
+                self.swarm = Some(Swarm::spawn(&mut self.satellites, self.planet.position, radius, SWARM_SIZE));
+            }
+        }
+        self.satellites.len()
+    }
+
+    /// Puts a moon in orbit around the planet, or removes it.
+    pub fn toggle_moon(&mut self) -> bool {
+        match self.moon.take() {
+            Some(moon) => moon.despawn(&mut self.scene),
+            None => {
+                let radius = self.planet.lod.params.planet_radius as f64;
+                self.moon = MoonSystem::spawn(&mut self.scene, &self.moon_assets, self.planet_node, radius);
+            }
+        }
+        self.moon.is_some()
+    }
+
+    /// Places a marker; it shows from the next frame on.
+    pub fn add_marker(&mut self, marker: Marker) -> MarkerId {
+        self.markers.add(marker)
+    }
+
+    pub fn remove_marker(&mut self, id: MarkerId) -> Option<Marker> {
+        self.markers.remove(id)
+    }
+
+    /// Edits a marker in place, e.g. to move it or change its label.
+    pub fn marker_mut(&mut self, id: MarkerId) -> Option<&mut Marker> {
+        self.markers.get_mut(id)
+    }
+
+    /// The marker drawn closest to `world`, within a few pixels' worth of
+    /// distance at that depth.
+    pub fn marker_near(&self, world: DVec3) -> Option<MarkerId> {
+        let reach = 0.03 * self.camera.eye.distance(world);
+        let radius = self.planet.lod.params.planet_radius as f64;
+        self.markers.nearest(world, self.planet.position, radius, reach)
+    }
+
+    pub fn clear_markers(&mut self) {
+        self.markers.clear();
+    }
+
+    pub fn markers(&self) -> &Markers {
+        &self.markers
+    }
+
+    /// Re-reads the GeoJSON layers and the scalar grid, e.g. after one of
+    /// their files changed.
+    pub fn reload_overlays(&mut self) {
+        self.vector_layers.reload(&self.device, self.planet.lod.params.planet_radius as f64);
+        println!("🗺️ reloaded {} vector layer(s)", self.vector_layers.layers.len());
+        self.scalar_layer.reload(&self.device, &self.queue);
+        if self.scalar_layer.is_loaded() {
+            println!("🌡️ reloaded scalar layer");
+        }
+    }
+
+    /// Saves the next frame as a PNG in the working directory.
+    pub fn request_screenshot(&mut self) {
+        if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            log::warn!("the surface can't be copied from, screenshots are unavailable");
+            return;
+        }
+        self.screenshot_requested = true;
+    }
+
+    /// Saves the profiler's recent frames as a Chrome trace in the working
+    /// directory.
+    pub fn export_trace(&mut self) {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = PathBuf::from(format!("trace-{secs}.json"));
+        match self.profiler.write_chrome_trace(&path) {
+            Ok(()) => println!("⏱️ saved {}", path.display()),
+            Err(err) => log::warn!("trace export failed: {err:#}"),
+        }
+    }
+
+    /// Writes the next frame's render graph to `render_graph.dot`.
+    pub fn request_graph_dump(&mut self) {
+        self.graph_dump_requested = true;
+    }
+
+    fn cursor_ndc(&self) -> Option<Vec2> {
+        let (x, y) = self.last_mouse_pos?;
+        Some(Vec2::new(
+            x / self.config.width as f32 * 2.0 - 1.0,
+            1.0 - y / self.config.height as f32 * 2.0,
+        ))
+    }
+
+    /// Point on the planet under the cursor, if any.
+    pub fn cursor_anchor(&self) -> Option<DVec3> {
+        let (origin, dir) = self.camera.ray(self.cursor_ndc()?);
+        let radius = self.planet.lod.params.planet_radius as f64;
+        ray_sphere(origin, dir, self.planet.position, radius).map(|t| origin + dir * t)
+    }
+
+    /// Picks what is under the cursor and keeps it in `last_pick`.
+    ///
+    /// The planet is hit-tested on the CPU against its drawn chunk meshes.
+    /// The scene graph's nodes go through the GPU id/depth pass, which also
+    /// confirms the planet isn't hidden behind one of them.
+    pub fn pick_at_cursor(&mut self) -> Option<PickResult> {
+        let ndc = self.cursor_ndc()?;
+        let (origin, dir) = self.camera.ray(ndc);
+
+        let view_proj = self.camera.projection_matrix(DepthMode::ReverseZ) * self.camera.view_matrix();
+        let (width, height) = self.resolution();
+        // chunk offsets are camera-relative already
+        let mut objects = vec![PickObject { id: PLANET_ID, model: Mat4::IDENTITY, mesh: &self.planet }];
+        let mut nodes = Vec::new();
+        for (node, model, mesh) in self.scene_renderer.pick_objects() {
+            objects.push(PickObject { id: FIRST_NODE_ID + nodes.len() as u32, model, mesh });
+            nodes.push(node);
+        }
+        let gpu = self.picker.pick(
+            &self.device,
+            &self.queue,
+            Picker::pixel_matrix(view_proj, ndc, Vec2::new(width, height)),
+            &objects,
+        );
+        let gpu = match gpu {
+            Ok(gpu) => gpu,
+            Err(err) => {
+                log::warn!("GPU pick failed: {err:#}");
+                None
+            }
+        };
+
+        let center = self.planet.position;
+        let radius = self.planet.lod.params.planet_radius as f64;
+        let cpu = self.planet.intersect(origin, dir).or_else(|| {
+            // chunks can lag behind the camera for a frame; the sphere is close enough
+            ray_sphere(origin, dir, center, radius).map(|t| (t, (origin + dir * t - center).normalize()))
+        });
+
+        self.last_pick = match (cpu, gpu) {
+            (Some((t, normal)), None | Some(GpuPick { object_id: PLANET_ID, .. })) => {
+                Some(PickResult::on_planet(origin + dir * t, normal, center, &self.surface_image))
+            }
+            (_, Some(GpuPick { object_id, depth })) => {
+                let relative = view_proj.as_dmat4().inverse().project_point3(ndc.as_dvec2().extend(depth as f64));
+                let position = self.camera.eye + relative;
+                if object_id == PLANET_ID {
+                    let normal = (position - center).normalize();
+                    Some(PickResult::on_planet(position, normal, center, &self.surface_image))
+                } else {
+                    let node = object_id.checked_sub(FIRST_NODE_ID).and_then(|i| nodes.get(i as usize).copied());
+                    Some(PickResult::on_node(object_id, node, position, -dir))
+                }
+            }
+            (None, None) => None,
+        };
+        self.last_pick
+    }
+
+    /// Flips the camera between perspective and orthographic. Pipelines are
+    /// rebuilt if that changes the depth mode in use.
+    pub fn toggle_projection(&mut self) {
+        let before = self.active_depth_mode();
+        self.camera.toggle_projection();
+        self.controller.attach(&self.camera); // the eye may have moved
+        if self.active_depth_mode() != before {
+            self.reload_shader_pipeline();
+        }
+    }
+
+    /// Reconfigures the surface and every size-dependent resource.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return; // minimized
+        }
+        self.config.width = width;
+        self.config.height = height;
+        self.surface.configure(&self.device, &self.config);
+
+        self.depth_view = create_depth_view(&self.device, &self.config);
+        self.atmosphere.set_depth_view(&self.device, &self.depth_view);
+        self.post.resize(&self.device, &self.config);
+        self.camera.aspect = width as f32 / height as f32;
+    }
+
     pub fn resolution(&self) -> (f32, f32) {
         (self.config.width as f32, self.config.height as f32)
     }
 
     pub fn render(&mut self, window: &Option<Arc<Window>>) {
-        // 1) state already ready
+        self.profiler.begin_frame();
+        let window = window.as_ref().unwrap();
+
+        // 0) debug panel edits apply to this frame
+        let panel = DebugPanel {
+            camera: &mut self.camera,
+            controller: self.controller.as_mut(),
+            light: &mut self.light,
+            profiler: &mut self.profiler,
+            shader_params: &mut self.planet_params,
+            bookmarks: &self.bookmarks.bookmarks,
+        };
+        let requests = self.debug_ui.prepare(&self.device, &self.queue, window, panel);
+        if requests.reload_shaders {
+            self.reload_shader_pipeline();
+        }
+        if let Some(name) = requests.save_bookmark {
+            self.save_bookmark(Some(&name));
+        }
+        if let Some(name) = requests.fly_to_bookmark {
+            self.fly_to_bookmark(&name);
+        }
+
+        // 1) update camera & per-frame uniforms
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame).as_secs_f64().min(0.1); // don't leap after a stall
+        self.last_frame = now;
+        match self.animation.as_mut() {
+            Some(animation) => {
+                if !animation.advance(&mut self.camera, dt) {
+                    self.stop_animation();
+                }
+            }
+            None => self.controller.update(&mut self.camera, dt),
+        }
+
+        let depth_mode = self.active_depth_mode();
+        let proj = self.camera.projection_matrix(depth_mode);
+        let view = self.camera.view_matrix(); // camera-relative, see Camera::relative
+
+        let view_proj = proj * view;
+
+        if let Some(moon) = &self.moon {
+            moon.update(&mut self.scene, self.start_time.elapsed().as_secs_f64());
+        }
+        self.scene.update();
+        if let Some(node) = self.scene.node(self.planet_node) {
+            self.planet.position = node.world().w_axis.truncate();
+        }
+
+        let log_depth_coef = DepthMode::log_coefficient(self.camera.projection.far());
+        self.queue.write_buffer(
+            &self.ubos.camera_buffer,
+            0,
+            bytemuck::bytes_of(&CameraUniforms::new(view_proj, log_depth_coef)),
+        );
+        self.queue.write_buffer(
+            &self.ubos.light_buffer,
+            0,
+            bytemuck::cast_slice(&self.light.to_uniform()),
+        );
+        self.globals.time = self.start_time.elapsed().as_secs_f32();
+        self.globals.debug_view = self.debug_view.shader_index(self.debug_pipelines.wireframe.is_some());
+        // the depth view spans the visible surface, from the nearest point to the horizon
+        let planet_distance = (self.camera.eye - self.planet.position).length() as f32;
+        let radius = self.planet.lod.params.planet_radius;
+        self.globals.depth_near = (planet_distance - radius).max(0.0);
+        self.globals.depth_far = if planet_distance > radius {
+            (planet_distance * planet_distance - radius * radius).sqrt()
+        } else {
+            planet_distance + radius
+        };
+        self.queue.write_buffer(&self.ubos.globals_buffer, 0, bytemuck::bytes_of(&self.globals));
+        self.clouds.update(&self.queue, radius);
+        self.graticule.update(&self.queue);
+        self.vector_layers.update(&self.queue, self.resolution());
+        self.scalar_layer.update(&self.queue);
+        self.post.update(&self.queue);
+        if let Some(material) = self.scene_renderer.material_mut(self.planet_material) {
+            material.write_factors(&self.device, &self.queue, &self.planet_params.bytes());
+        }
+        if let Some(swarm) = &self.swarm {
+            swarm.update(&mut self.satellites, self.globals.time as f64);
+        }
+        self.satellites.update_buffers(&self.device, &self.queue, &self.camera);
+        self.scene_renderer.update(&self.device, &self.queue, &self.scene, &self.camera);
+        self.planet.update(
+            &self.device,
+            &self.queue,
+            &self.camera,
+            view_proj,
+            self.config.height as f32,
+        );
+        self.atmosphere.center = self.planet.position;
+        self.atmosphere.update(
+            &self.queue,
+            self.planet.lod.params.planet_radius,
+            view_proj,
+            &self.camera,
+            &self.light,
+            depth_mode,
+        );
+
+        self.overlay_text.begin(self.resolution());
+        self.marker_renderer.update(&mut self.overlay_text, &self.markers, &self.camera, &self.planet);
+        self.scalar_layer.layout_legend(&mut self.overlay_text);
+        self.profiler.layout_summary(&mut self.overlay_text);
+        self.overlay_text.upload(&self.device, &self.queue);
 
         // 2) acquire next frame
-        let window = window.as_ref().unwrap();
         let frame = self.surface.get_current_texture().unwrap();
         let view = frame.texture.create_view(&Default::default());
 
-        // 3) encode a render pass that clears green and draws the quad
-        let mut encoder = self.device.create_command_encoder(&Default::default());
-        {
+        // 3) declare the frame's passes; the graph orders them and allocates
+        // the transient targets
+        let mut screenshot = None;
+        let mut graph = RenderGraph::new();
+        let scene = graph.import_texture("HDR Scene", self.post.scene_view());
+        let depth = graph.import_texture("Depth", &self.depth_view);
+        let output = graph.import_texture("Frame", &view);
+        let multisampled = self
+            .msaa
+            .targets()
+            .map(|(color, depth)| (graph.create_texture(color), graph.create_texture(depth)));
+
+        // clears to space black and draws the planet into the HDR target
+        let (scene_color, scene_depth) = multisampled.unwrap_or((scene, depth));
+        let mut pass = graph.add_pass("Scene").write(scene_color).write(scene_depth);
+        if multisampled.is_some() {
+            pass = pass.write(scene);
+        }
+        pass.execute(|encoder, res| {
+            let (view, resolve_target, store) = match multisampled {
+                Some(_) => (res.view(scene_color), Some(res.view(scene)), StoreOp::Discard),
+                None => (res.view(scene), None, StoreOp::Store),
+            };
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::RED),
-                        store: wgpu::StoreOp::Store,
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_view,
+                    view: res.view(scene_depth),
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(depth_mode.clear_value()),
                         store: StoreOp::Store,
                     }),
                     stencil_ops: None,
@@ -464,38 +1092,105 @@ impl GpuState {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &self.frame_bind_group, &[]);
+
+            // opaque geometry first: the planet and the other nodes …
+            self.scene_renderer.draw(&mut rpass, &[&self.planet]);
+            self.scene_renderer.bind_world(&mut rpass);
+            self.satellites.draw(&mut rpass);
+
+            // … then transparent layers, which test against but don't write
+            // depth; the ones on the planet draw in its object space
+            if !self.scene_renderer.bind_node(&mut rpass, self.planet_node) {
+                return; // the planet is hidden, and so is all that's on it
+            }
             rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            if !self.debug_view.replaces_shading() {
+                self.clouds.draw(&mut rpass, self.num_indices);
+            }
 
-            rpass.set_bind_group(0, &self.ubo_bind_group, &[]);
+            // overlays on the surface stay readable above the clouds
+            self.scalar_layer.draw(&mut rpass, &self.planet);
+            self.graticule.draw(&mut rpass, &self.planet);
+            self.vector_layers.draw(&mut rpass);
 
-            rpass.draw_indexed(0..self.num_indices, 0, 0..1);
+            // debug lines go over everything else in the scene
+            let debug_lines = match self.debug_view {
+                DebugView::Wireframe => self.debug_pipelines.wireframe.as_ref(),
+                DebugView::NormalVectors => Some(&self.debug_pipelines.normal_vectors),
+                _ => None,
+            };
+            if let Some(pipeline) = debug_lines {
+                rpass.set_pipeline(pipeline);
+                rpass.set_bind_group(3, self.clouds.bind_group(), &[]);
+                match self.debug_view {
+                    DebugView::NormalVectors => self.planet.draw_normals(&mut rpass),
+                    _ => self.planet.draw(&mut rpass),
+                }
+            }
+        });
+
+        if let Some((_, multisampled_depth)) = multisampled {
+            let (msaa, device) = (&mut self.msaa, &self.device);
+            graph.add_pass("Depth Resolve").read(multisampled_depth).write(depth).execute(move |encoder, res| {
+                msaa.resolve_depth(device, encoder, res.view(multisampled_depth), res.view(depth));
+            });
         }
 
-        let yaw = self.camera.yaw;
-        let pitch = self.camera.pitch;
+        // atmosphere reads the scene depth, so it needs its own pass
+        graph.add_pass("Atmosphere").read(depth).write(scene).execute(|encoder, res| {
+            self.atmosphere.render(encoder, res.view(scene));
+        });
 
-        self.camera.eye = Vec3::new(
-            self.camera.distance * yaw.cos() * pitch.cos(),
-            self.camera.distance * pitch.sin(),
-            self.camera.distance * yaw.sin() * pitch.cos(),
-        ) + self.camera.target;
+        // bloom, tonemapping, grading and FXAA into the frame
+        graph.add_pass("Post").read(scene).write(output).execute(|encoder, res| {
+            self.post.render(encoder, res.view(output));
+        });
 
-        let aspect = self.config.width as f32 / self.config.height as f32;
-        let proj = Mat4::perspective_rh_gl(45f32.to_radians(), aspect, 0.1, 100.0);
-        let view = Mat4::look_at_rh(self.camera.eye, self.camera.target, self.camera.up);
+        // markers, labels, the legend and timings stay crisp on top of everything
+        graph.add_pass("Overlay").write(output).execute(|encoder, res| {
+            self.overlay_text.render(encoder, res.view(output));
+        });
 
-        let view_proj = proj * view;
+        let surface_size = (self.config.width, self.config.height);
+        graph.add_pass("Debug UI").write(output).execute(|encoder, res| {
+            self.debug_ui.render(&self.device, &self.queue, encoder, res.view(output), surface_size);
+        });
 
-        self.queue.write_buffer(
-            &self.ubos.camera_buffer,
-            0,
-            bytemuck::cast_slice(&view_proj.to_cols_array_2d()),
-        );
+        if std::mem::take(&mut self.screenshot_requested) {
+            graph.add_pass("Screenshot").read(output).side_effect().execute(|encoder, _| {
+                screenshot = Some(Screenshot::capture(&self.device, encoder, &frame.texture));
+            });
+        }
+
+        if std::mem::take(&mut self.graph_dump_requested) {
+            let written = graph
+                .to_dot(surface_size)
+                .and_then(|dot| std::fs::write(RENDER_GRAPH_DOT_PATH, dot).map_err(Into::into));
+            match written {
+                Ok(()) => println!("🕸️ wrote {RENDER_GRAPH_DOT_PATH}"),
+                Err(err) => log::warn!("writing {RENDER_GRAPH_DOT_PATH} failed: {err:#}"),
+            }
+        }
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        if let Err(err) = graph.execute(&self.device, &mut encoder, &mut self.transients, &mut self.profiler, surface_size) {
+            log::error!("frame not rendered: {err:#}");
+        }
+        self.profiler.end_frame(&mut encoder);
 
         // 4) submit + present
         self.queue.submit(Some(encoder.finish()));
+        self.profiler.after_submit(&self.device);
+        if let Some(screenshot) = screenshot {
+            let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            let path = PathBuf::from(format!("screenshot-{secs}.png"));
+            match screenshot.save(&self.device, &path) {
+                Ok(()) => println!("📸 saved {}", path.display()),
+                Err(err) => log::warn!("screenshot failed: {err:#}"),
+            }
+        }
         frame.present();
 
         // 5) schedule next frame (for continuous rendering)
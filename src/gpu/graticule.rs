@@ -0,0 +1,190 @@
+use std::num::NonZeroU64;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec4;
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{load_shader, DepthMode, FragmentShader, Planet, SceneLayouts, SceneTarget, VertexShader};
+use crate::gpu::planet::ChunkInstance;
+use crate::vertex;
+
+/// Tunables for the latitude/longitude grid.
+pub struct GraticuleParams {
+    pub spacing: f32,       // degrees between major lines
+    pub subdivisions: u32,  // minor lines per major interval, shown when zoomed in; 0 or 1 for none
+    pub line_width: f32,    // pixels
+    pub fade_px: f32,       // lines fade out once they are closer than this on screen
+    pub color: Vec4,        // rgb + opacity
+    pub special_color: Vec4, // equator, prime meridian and tropics
+}
+
+impl Default for GraticuleParams {
+    fn default() -> Self {
+        Self {
+            spacing: 15.0,
+            subdivisions: 3,
+            line_width: 1.5,
+            fade_px: 6.0,
+            color: Vec4::new(1.0, 1.0, 1.0, 0.35),
+            special_color: Vec4::new(1.0, 0.8, 0.3, 0.8),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct GraticuleUniforms {
+    color: [f32; 4],         // 16 bytes @ offset 0
+    special_color: [f32; 4], // 16 bytes @ offset 16
+    spacing: f32,            //  4 bytes @ offset 32
+    minor_spacing: f32,      //  4 bytes @ offset 36
+    line_width: f32,         //  4 bytes @ offset 40
+    fade_px: f32,            //  4 bytes @ offset 44
+} // total size = 48 bytes
+
+/// Latitude/longitude lines, computed per pixel on a second pass over the
+/// planet mesh so they keep a constant screen width at any distance.
+pub struct Graticule {
+    pub params: GraticuleParams,
+    pub enabled: bool,
+
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+const VERTEX_SHADER_PATH: &str = "src/shaders/cube.vert.wgsl";
+const SHADER_PATH: &str = "src/shaders/graticule.wgsl";
+
+impl Graticule {
+    pub fn new(
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Graticule UBO"),
+            contents: bytemuck::bytes_of(&GraticuleUniforms::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Graticule Bind Group Layout"),
+            entries: &[
+                // binding 0 = Graticule UBO
+                wgpu::BindGroupLayoutEntry {
+                    binding:    0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty:                wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size:  NonZeroU64::new(size_of::<GraticuleUniforms>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Graticule Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline = create_pipeline(device, target, layouts, &bind_group_layout, depth_mode);
+
+        Self {
+            params: GraticuleParams::default(),
+            enabled: false,
+
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn reload_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) {
+        self.pipeline = create_pipeline(device, target, layouts, &self.bind_group_layout, depth_mode);
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let p = &self.params;
+        let uniforms = GraticuleUniforms {
+            color: p.color.to_array(),
+            special_color: p.special_color.to_array(),
+            spacing: p.spacing,
+            minor_spacing: if p.subdivisions > 1 { p.spacing / p.subdivisions as f32 } else { 0.0 },
+            line_width: p.line_width,
+            fade_px: p.fade_px,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Redraws the planet's visible chunks with the grid shader. Needs the
+    /// frame bind group and the planet node's groups (see
+    /// `SceneRenderer::bind_node`) bound and the planet already in the
+    /// depth buffer.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass, planet: &Planet) {
+        if !self.enabled {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(3, &self.bind_group, &[]);
+        planet.draw(rpass);
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    target: SceneTarget,
+    layouts: &SceneLayouts,
+    graticule_layout: &wgpu::BindGroupLayout,
+    depth_mode: DepthMode,
+) -> wgpu::RenderPipeline {
+    // the planet's own vertex stage, so depths match what it wrote
+    let vs_module = VertexShader(load_shader("Graticule VS", VERTEX_SHADER_PATH, device));
+    let fs_module = FragmentShader(load_shader("Graticule FS", SHADER_PATH, device));
+
+    let layout = layouts.pipeline_layout(device, "Graticule Pipeline Layout", Some(graticule_layout));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Graticule Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &vs_module,
+            entry_point: Some("vs_main"),
+            buffers: &[vertex::Vertex::desc(), ChunkInstance::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: Default::default(),
+            module: &fs_module,
+            entry_point: Some(&depth_mode.fragment_entry("fs_main")),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: Some(depth_mode.overlay_depth_stencil_state()),
+        multisample: target.multisample_state(),
+        multiview: None,
+    })
+}
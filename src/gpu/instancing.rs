@@ -0,0 +1,335 @@
+use std::num::NonZeroU64;
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{DVec3, Quat};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::gpu::{load_shader, DepthMode, SceneLayouts, SceneTarget};
+use crate::vertex::Vertex;
+
+const SHADER_PATH: &str = "src/shaders/instanced.wgsl";
+const INITIAL_CAPACITY: usize = 1024;
+
+/// One placement of the mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+    pub position: DVec3, // world
+    pub rotation: Quat,
+    pub scale: f32,
+    pub color: [f32; 4], // linear RGBA
+}
+
+/// Stays valid until its instance is removed; a stale id matches nothing,
+/// even after its slot was reused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId {
+    slot: u32,
+    generation: u32,
+}
+
+/// Where an id's instance currently sits in the packed list.
+struct Slot {
+    generation: u32,
+    index: u32, // into `instances`; unused while the slot is free
+}
+
+/// Per-instance data, fed to the shader as an instance vertex buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct InstanceRaw {
+    translation: [f32; 3], // relative to the set's origin
+    scale: f32,
+    rotation: [f32; 4], // xyzw
+    color: [f32; 4],
+} // total size = 48 bytes
+
+impl InstanceRaw {
+    const ATTRIBS: [wgpu::VertexAttribute; 4] =
+        wgpu::vertex_attr_array![
+            5 => Float32x3,  // translation
+            6 => Float32,    // scale
+            7 => Float32x4,  // rotation
+            8 => Float32x4   // color
+        ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<InstanceRaw>() as _,
+            step_mode:    wgpu::VertexStepMode::Instance,
+            attributes:   &Self::ATTRIBS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct SetUniforms {
+    origin: [f32; 3], // 12 bytes @ offset 0, camera-relative
+    _pad: f32,        //  4 bytes @ offset 12
+} // total size = 16 bytes
+
+/// Many copies of one mesh in a single draw call.
+///
+/// Instances are kept packed in the order the GPU draws them: removing one
+/// moves the last instance into its slot, so add, update and remove are all
+/// O(1), and only the slots changed since the last `update_buffers` are uploaded.
+/// Positions are stored in f32 relative to a fixed origin (e.g. the center
+/// of the planet the objects orbit), which is rebased on the camera every
+/// frame.
+pub struct InstancedMesh {
+    pub visible: bool,
+    origin: DVec3,
+
+    instances: Vec<InstanceRaw>,
+    ids: Vec<InstanceId>, // same order as `instances`
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    dirty: Option<Range<usize>>,
+
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    instance_buffer: wgpu::Buffer,
+    capacity: usize,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl InstancedMesh {
+    pub fn new(
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+        (vertices, indices): (&[Vertex], &[u16]),
+        origin: DVec3,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instanced Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instanced Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance Set UBO"),
+            contents: bytemuck::bytes_of(&SetUniforms::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Instance Set Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding:    0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty:                wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size:  NonZeroU64::new(size_of::<SetUniforms>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Instance Set Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() }],
+        });
+        let pipeline = create_pipeline(device, target, layouts, &bind_group_layout, depth_mode);
+
+        Self {
+            visible: true,
+            origin,
+
+            instances: Vec::new(),
+            ids: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            dirty: None,
+
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+            instance_buffer: create_instance_buffer(device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn add(&mut self, instance: Instance) -> InstanceId {
+        let index = self.instances.len() as u32;
+        let id = match self.free_slots.pop() {
+            Some(slot) => {
+                let entry = &mut self.slots[slot as usize];
+                entry.index = index;
+                InstanceId { slot, generation: entry.generation }
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, index });
+                InstanceId { slot: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        };
+        self.ids.push(id);
+        self.instances.push(self.raw(&instance));
+        self.mark_dirty(index as usize);
+        id
+    }
+
+    /// Replaces an instance; false if `id` was removed.
+    pub fn update(&mut self, id: InstanceId, instance: Instance) -> bool {
+        let Some(index) = self.index(id) else {
+            return false;
+        };
+        self.instances[index] = self.raw(&instance);
+        self.mark_dirty(index);
+        true
+    }
+
+    pub fn remove(&mut self, id: InstanceId) -> bool {
+        let Some(index) = self.index(id) else {
+            return false;
+        };
+        let slot = &mut self.slots[id.slot as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.slot);
+
+        self.instances.swap_remove(index);
+        self.ids.swap_remove(index);
+        if let Some(moved) = self.ids.get(index) {
+            self.slots[moved.slot as usize].index = index as u32;
+            self.mark_dirty(index);
+        }
+        true
+    }
+
+    fn index(&self, id: InstanceId) -> Option<usize> {
+        let slot = self.slots.get(id.slot as usize)?;
+        (slot.generation == id.generation).then_some(slot.index as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    fn raw(&self, instance: &Instance) -> InstanceRaw {
+        InstanceRaw {
+            translation: (instance.position - self.origin).as_vec3().to_array(),
+            scale: instance.scale,
+            rotation: instance.rotation.to_array(),
+            color: instance.color,
+        }
+    }
+
+    fn mark_dirty(&mut self, slot: usize) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(range) => range.start.min(slot)..range.end.max(slot + 1),
+            None => slot..slot + 1,
+        });
+    }
+
+    pub fn reload_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) {
+        self.pipeline = create_pipeline(device, target, layouts, &self.bind_group_layout, depth_mode);
+    }
+
+    /// Uploads the instances changed since the last call, growing the
+    /// buffer if needed, and rebases the origin on the camera.
+    pub fn update_buffers(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &Camera) {
+        if self.instances.len() > self.capacity {
+            self.capacity = self.instances.len().next_power_of_two();
+            self.instance_buffer = create_instance_buffer(device, self.capacity);
+            self.dirty = Some(0..self.instances.len());
+        }
+        if let Some(range) = self.dirty.take() {
+            // slots past the end were removed and are no longer drawn
+            let range = range.start..range.end.min(self.instances.len());
+            if !range.is_empty() {
+                let offset = (range.start * size_of::<InstanceRaw>()) as u64;
+                queue.write_buffer(&self.instance_buffer, offset, bytemuck::cast_slice(&self.instances[range]));
+            }
+        }
+        let uniforms = SetUniforms { origin: camera.relative(self.origin).to_array(), _pad: 0.0 };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draws every instance with the frame bind group and the world groups
+    /// (see `SceneRenderer::bind_world`) bound.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        if !self.visible || self.instances.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(3, &self.bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as u32);
+    }
+}
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Instance Buffer"),
+        size: (capacity * size_of::<InstanceRaw>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    target: SceneTarget,
+    layouts: &SceneLayouts,
+    set_layout: &wgpu::BindGroupLayout,
+    depth_mode: DepthMode,
+) -> wgpu::RenderPipeline {
+    let shader = load_shader("Instanced", SHADER_PATH, device);
+
+    let layout = layouts.pipeline_layout(device, "Instanced Pipeline Layout", Some(set_layout));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Instanced Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc(), InstanceRaw::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some(&depth_mode.fragment_entry("fs_main")),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target.format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(depth_mode.depth_stencil_state(true)),
+        multisample: target.multisample_state(),
+        multiview: None,
+    })
+}
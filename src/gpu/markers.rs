@@ -0,0 +1,84 @@
+use glam::{Vec2, Vec4Swizzles};
+
+use crate::camera::{ray_sphere, Camera};
+use crate::gpu::{DepthMode, OverlayText, Planet};
+use crate::markers::Markers;
+
+const LABEL_GAP: f32 = 4.0; // pixels between icon and label
+
+/// Screen rectangle in pixels, for decluttering.
+#[derive(Copy, Clone)]
+struct Rect {
+    min: Vec2,
+    max: Vec2,
+}
+
+impl Rect {
+    fn overlaps(&self, other: &Rect) -> bool {
+        self.min.x < other.max.x && other.min.x < self.max.x
+            && self.min.y < other.max.y && other.min.y < self.max.y
+    }
+}
+
+/// Draws markers as screen-facing icons with text labels, through the
+/// overlay on top of the finished frame. Markers behind the planet are
+/// hidden and labels that would overlap a more important one are dropped.
+pub struct MarkerRenderer {
+    pub labels: bool,
+}
+
+impl MarkerRenderer {
+    pub fn new(overlay: &OverlayText) -> Self {
+        Self { labels: overlay.atlas().has_font() }
+    }
+
+    /// Projects the markers for this frame and lays out their quads.
+    pub fn update(&self, overlay: &mut OverlayText, markers: &Markers, camera: &Camera, planet: &Planet) {
+        let viewport = overlay.viewport();
+        let view_proj = camera.projection_matrix(DepthMode::Standard) * camera.view_matrix();
+        let radius = planet.lod.params.planet_radius as f64;
+
+        // (priority, distance, screen position, marker) of every visible marker
+        let mut visible = Vec::new();
+        for (_, marker) in markers.iter() {
+            let world = marker.position.world(planet.position, radius);
+            let clip = view_proj * camera.relative(world).extend(1.0);
+            if clip.w <= 0.0 {
+                continue; // behind the camera
+            }
+            let ndc = clip.xy() / clip.w;
+            if ndc.abs().max_element() > 1.2 {
+                continue; // well off screen
+            }
+            // the planet hides it if the view ray meets the surface first
+            let (origin, dir) = camera.ray(ndc);
+            let distance = (world - origin).dot(dir);
+            if ray_sphere(origin, dir, planet.position, radius * (1.0 - 1e-4)).is_some_and(|t| t < distance) {
+                continue;
+            }
+            let screen = ((Vec2::new(ndc.x, -ndc.y) + 1.0) * 0.5 * viewport).round();
+            visible.push((marker.priority, distance, screen, marker));
+        }
+        visible.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.total_cmp(&b.1)));
+
+        let mut taken: Vec<Rect> = Vec::new();
+        for &(_, _, screen, marker) in &visible {
+            let icon = overlay.atlas().icon(marker.icon);
+            let size = Vec2::splat(marker.size);
+            let icon_rect = Rect { min: screen - size * 0.5, max: screen + size * 0.5 };
+            overlay.quad(icon_rect.min, size, &icon, marker.color);
+
+            if self.labels && !marker.label.is_empty() {
+                let (_, width) = overlay.atlas().layout(&marker.label);
+                let line_height = overlay.atlas().line_height;
+                let top_left = Vec2::new(icon_rect.max.x + LABEL_GAP, screen.y - (line_height * 0.5).round());
+                let label_rect = Rect { min: top_left, max: top_left + Vec2::new(width, line_height) };
+                if !taken.iter().any(|r| r.overlaps(&label_rect)) {
+                    overlay.text(top_left, &marker.label, marker.color);
+                    taken.push(label_rect);
+                }
+            }
+            taken.push(icon_rect);
+        }
+    }
+}
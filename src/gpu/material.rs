@@ -0,0 +1,202 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::create_texture_rgba8;
+
+/// Which variant of a material's shader to build, passed to the shader as
+/// pipeline-overridable constants. Materials that share a permutation share
+/// a pipeline.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Permutation {
+    pub base_color_map: bool, // multiply the base color by the texture
+    pub unlit: bool,          // base color and emission only, no lighting
+}
+
+impl Permutation {
+    pub fn constants(self) -> [(&'static str, f64); 2] {
+        [
+            ("BASE_COLOR_MAP", self.base_color_map as u32 as f64),
+            ("UNLIT", self.unlit as u32 as f64),
+        ]
+    }
+}
+
+/// Factor uniforms of the scene shader's materials.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MaterialFactors {
+    pub base_color: [f32; 4], // linear RGBA
+    pub emissive: [f32; 3],   // linear RGB, added after lighting
+}
+
+impl Default for MaterialFactors {
+    fn default() -> Self {
+        Self { base_color: [1.0; 4], emissive: [0.0; 3] }
+    }
+}
+
+impl MaterialFactors {
+    pub fn bytes(&self) -> Vec<u8> {
+        bytemuck::bytes_of(&FactorsRaw {
+            base_color: self.base_color,
+            emissive: self.emissive,
+            _pad: 0.0,
+        })
+        .to_vec()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct FactorsRaw {
+    base_color: [f32; 4], // 16 bytes @ offset 0
+    emissive: [f32; 3],   // 12 bytes @ offset 16
+    _pad: f32,            //  4 bytes @ offset 28
+} // total size = 32 bytes
+
+/// Layout of bind group 1 of every material: the factor uniform, whose
+/// struct is up to the shader, then the base color texture and its sampler.
+pub fn create_material_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Material Bind Group Layout"),
+        entries: &[
+            // binding 0 = factors
+            wgpu::BindGroupLayoutEntry {
+                binding:    0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty:                 wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size:   None, // differs between shaders
+                },
+                count: None,
+            },
+            // binding 1 = base color texture
+            wgpu::BindGroupLayoutEntry {
+                binding:    1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled:   false,
+                },
+                count: None,
+            },
+            // binding 2 = its sampler
+            wgpu::BindGroupLayoutEntry {
+                binding:    2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    })
+}
+
+/// What a material is made from.
+pub struct MaterialDesc<'a> {
+    pub name: &'a str,
+    pub factors: &'a [u8], // initial content of the factor uniform
+    pub base_color_map: Option<wgpu::TextureView>, // a white texel if None
+    pub sampler: Option<wgpu::Sampler>,            // repeating and linear if None
+    pub unlit: bool,
+}
+
+/// How a surface is shaded: its textures, sampler and factor uniforms in
+/// one bind group, and the shader permutation it needs.
+pub struct Material {
+    pub name: String,
+    pub permutation: Permutation,
+
+    base_color_map: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    factor_buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        desc: MaterialDesc,
+    ) -> Self {
+        let permutation = Permutation { base_color_map: desc.base_color_map.is_some(), unlit: desc.unlit };
+        let base_color_map = desc.base_color_map.unwrap_or_else(|| {
+            let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+            create_texture_rgba8(device, queue, "White Texture", &white, wgpu::TextureFormat::Rgba8UnormSrgb)
+                .create_view(&Default::default())
+        });
+        let sampler = desc.sampler.unwrap_or_else(|| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Material Sampler"),
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        });
+        let factor_buffer = create_factor_buffer(device, desc.factors);
+        let bind_group = create_bind_group(device, desc.name, layout, &factor_buffer, &base_color_map, &sampler);
+
+        Self {
+            name: desc.name.to_string(),
+            permutation,
+            base_color_map,
+            sampler,
+            factor_buffer,
+            layout: layout.clone(),
+            bind_group,
+        }
+    }
+
+    /// Uploads new factors. A different size, e.g. after the shader's
+    /// factor struct changed, replaces the buffer and the bind group.
+    pub fn write_factors(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, factors: &[u8]) {
+        if factors.len() as u64 != self.factor_buffer.size() {
+            self.factor_buffer = create_factor_buffer(device, factors);
+            self.bind_group = create_bind_group(
+                device,
+                &self.name,
+                &self.layout,
+                &self.factor_buffer,
+                &self.base_color_map,
+                &self.sampler,
+            );
+            return;
+        }
+        queue.write_buffer(&self.factor_buffer, 0, factors);
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+fn create_factor_buffer(device: &wgpu::Device, factors: &[u8]) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Material Factors UBO"),
+        contents: factors,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    name: &str,
+    layout: &wgpu::BindGroupLayout,
+    factors: &wgpu::Buffer,
+    base_color_map: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(&format!("Material Bind Group ({name})")),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: factors.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(base_color_map) },
+            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(sampler) },
+        ],
+    })
+}
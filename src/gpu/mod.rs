@@ -1,6 +1,48 @@
+pub mod atmosphere;
+pub mod clouds;
+pub mod debug_ui;
+pub mod debug_view;
+pub mod depth;
 pub mod gpu_state;
+pub mod graticule;
+pub mod instancing;
+pub mod markers;
+pub mod material;
+pub mod msaa;
+pub mod overlay_text;
+pub mod picking;
+pub mod planet;
+pub mod post;
+pub mod profiler;
+pub mod render_graph;
+pub mod scalar_layer;
+pub mod scene_renderer;
+pub mod screenshot;
+pub mod shader_params;
 pub mod utils;
+pub mod vector_layers;
 
+pub use atmosphere::Atmosphere;
+pub use clouds::Clouds;
+pub use debug_ui::{DebugPanel, DebugUi};
+pub use debug_view::DebugView;
+pub use depth::DepthMode;
+pub use graticule::Graticule;
+pub use instancing::InstancedMesh;
+pub use markers::MarkerRenderer;
+pub use material::{Material, MaterialDesc};
+pub use msaa::{Msaa, SceneTarget};
+pub use overlay_text::OverlayText;
+pub use picking::{GpuPick, PickObject, Picker};
+pub use planet::Planet;
+pub use post::{PostProcess, PostSettings, HDR_FORMAT};
+pub use profiler::Profiler;
+pub use render_graph::{RenderGraph, TextureDesc, TransientPool};
+pub use scalar_layer::ScalarLayer;
+pub use scene_renderer::{SceneLayouts, SceneRenderer};
+pub use screenshot::Screenshot;
+pub use shader_params::ShaderParams;
+pub use vector_layers::VectorLayers;
 pub use gpu_state::GpuState;
 pub use gpu_state::create_gpu_state;
 
@@ -0,0 +1,214 @@
+use crate::gpu::{load_shader, DepthMode, TextureDesc, HDR_FORMAT};
+
+const RESOLVE_SHADER_PATH: &str = "src/shaders/depth_resolve.wgsl";
+
+/// What the scene pass renders into; every pipeline drawn in it must match.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SceneTarget {
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+}
+
+impl SceneTarget {
+    pub fn multisample_state(self) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count: self.sample_count,
+            ..Default::default()
+        }
+    }
+}
+
+/// Sample count of the scene pass. Its multisampled color and depth are
+/// render graph transients; color resolves into the post-processing input and
+/// depth is resolved by a small full-screen pass into the single-sample depth
+/// texture that later passes (the atmosphere) read.
+pub struct Msaa {
+    supported: Vec<u32>,
+    sample_count: u32,
+
+    resolve_layout: wgpu::BindGroupLayout,
+    resolve_bind_group: Option<(wgpu::TextureView, wgpu::BindGroup)>, // for the depth it was made for
+    resolve_pipeline: Option<wgpu::RenderPipeline>,
+}
+
+impl Msaa {
+    pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+    /// Starts at `preferred` samples, or the most below it the adapter
+    /// supports for both the HDR and the depth format.
+    pub fn new(adapter: &wgpu::Adapter, device: &wgpu::Device, preferred: u32) -> Self {
+        // beyond 1 and 4, sample counts depend on the adapter
+        let format_features = |format: wgpu::TextureFormat| {
+            if device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                adapter.get_texture_format_features(format)
+            } else {
+                format.guaranteed_format_features(device.features())
+            }
+        };
+        let (color, depth) = (format_features(HDR_FORMAT), format_features(DepthMode::FORMAT));
+        let supported: Vec<u32> = Self::SAMPLE_COUNTS
+            .into_iter()
+            .filter(|&n| color.flags.sample_count_supported(n) && depth.flags.sample_count_supported(n))
+            .collect();
+
+        let resolve_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Resolve Bind Group Layout"),
+            entries: &[
+                // binding 0 = multisampled scene depth
+                wgpu::BindGroupLayoutEntry {
+                    binding:    0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type:    wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled:   true,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sample_count = supported.iter().copied().filter(|&n| n <= preferred).max().unwrap_or(1);
+        let mut msaa = Self {
+            supported,
+            sample_count,
+            resolve_layout,
+            resolve_bind_group: None,
+            resolve_pipeline: None,
+        };
+        msaa.reload_pipeline(device);
+        msaa
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn scene_target(&self) -> SceneTarget {
+        SceneTarget { format: HDR_FORMAT, sample_count: self.sample_count }
+    }
+
+    /// The next supported sample count after the current one, wrapping.
+    pub fn next_sample_count(&self) -> u32 {
+        let index = self.supported.iter().position(|&n| n == self.sample_count).unwrap_or(0);
+        self.supported[(index + 1) % self.supported.len()]
+    }
+
+    /// Switches to `sample_count` if the adapter supports it; returns whether
+    /// it did. Pipelines drawn in the scene pass must be rebuilt afterwards.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) -> bool {
+        if !self.supported.contains(&sample_count) {
+            return false;
+        }
+        self.sample_count = sample_count;
+        self.reload_pipeline(device);
+        true
+    }
+
+    /// Multisampled color and depth textures for the scene pass, or None
+    /// without multisampling, where it draws into the single-sample targets.
+    pub fn targets(&self) -> Option<(TextureDesc, TextureDesc)> {
+        if self.sample_count == 1 {
+            return None;
+        }
+        let target = |label, format, usage| TextureDesc {
+            label,
+            scale: 1.0,
+            format,
+            sample_count: self.sample_count,
+            usage,
+        };
+        Some((
+            target("MSAA Scene", HDR_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT),
+            target(
+                "MSAA Depth",
+                DepthMode::FORMAT,
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            ),
+        ))
+    }
+
+    pub fn reload_pipeline(&mut self, device: &wgpu::Device) {
+        self.resolve_pipeline = (self.sample_count > 1).then(|| create_resolve_pipeline(device, &self.resolve_layout));
+    }
+
+    /// Copies the first sample of `multisampled` into `single`.
+    pub fn resolve_depth(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        multisampled: &wgpu::TextureView,
+        single: &wgpu::TextureView,
+    ) {
+        let Some(pipeline) = &self.resolve_pipeline else {
+            return;
+        };
+        if self.resolve_bind_group.as_ref().is_none_or(|(view, _)| view != multisampled) {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Depth Resolve Bind Group"),
+                layout: &self.resolve_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(multisampled),
+                }],
+            });
+            self.resolve_bind_group = Some((multisampled.clone(), bind_group));
+        }
+        let bind_group = self.resolve_bind_group.as_ref().map(|(_, bind_group)| bind_group);
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Resolve Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: single,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load, // every pixel is overwritten
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+fn create_resolve_pipeline(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+    let shader = load_shader("Depth Resolve", RESOLVE_SHADER_PATH, device);
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Depth Resolve Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Depth Resolve Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[],
+        }),
+        primitive: Default::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DepthMode::FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        multisample: Default::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,271 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec2;
+
+use crate::atlas::{AtlasRect, MarkerAtlas};
+use crate::gpu::{create_texture_r8, load_shader};
+
+const SHADER_PATH: &str = "src/shaders/overlay_text.wgsl";
+const FONT_PATH: &str = "assets/fonts/DejaVuSans.ttf";
+const FONT_SIZE: f32 = 14.0;
+const SHADOW: [f32; 4] = [0.0, 0.0, 0.0, 0.8]; // drop shadow behind text
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ScreenVertex {
+    position: [f32; 2], // NDC
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl ScreenVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![
+            0 => Float32x2,  // position
+            1 => Float32x2,  // uv
+            2 => Float32x4   // color
+        ];
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ScreenVertex>() as _,
+            step_mode:    wgpu::VertexStepMode::Vertex,
+            attributes:   &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Screen-space text and quads drawn on top of the finished frame. Each
+/// frame starts with `begin`; markers, the scalar legend and the profiler
+/// then lay out what they show, and `upload` sends it all as one batch.
+/// Positions are in pixels from the top-left corner.
+pub struct OverlayText {
+    atlas: MarkerAtlas,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    num_vertices: u32,
+
+    vertices: Vec<ScreenVertex>, // this frame's batch, until `upload`
+    viewport: Vec2,
+}
+
+impl OverlayText {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, config: &wgpu::SurfaceConfiguration) -> Self {
+        let atlas = MarkerAtlas::new(FONT_PATH.as_ref(), FONT_SIZE);
+        let texture = create_texture_r8(device, queue, "Overlay Atlas", &atlas.image);
+        let texture_view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Overlay Bind Group Layout"),
+            entries: &[
+                // binding 0 = atlas texture
+                wgpu::BindGroupLayoutEntry {
+                    binding:    0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count: None,
+                },
+                // binding 1 = sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding:    1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Overlay Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline = create_pipeline(device, config, &bind_group_layout);
+        let vertex_capacity = 1024;
+
+        Self {
+            atlas,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            vertex_buffer: create_vertex_buffer(device, vertex_capacity),
+            vertex_capacity,
+            num_vertices: 0,
+
+            vertices: Vec::new(),
+            viewport: Vec2::ONE,
+        }
+    }
+
+    pub fn reload_pipeline(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.pipeline = create_pipeline(device, config, &self.bind_group_layout);
+    }
+
+    /// Icons, glyphs and font metrics, for measuring before laying out.
+    pub fn atlas(&self) -> &MarkerAtlas {
+        &self.atlas
+    }
+
+    pub fn viewport(&self) -> Vec2 {
+        self.viewport
+    }
+
+    /// Line height, rounded to whole pixels so text stays crisp.
+    pub fn line_height(&self) -> f32 {
+        self.atlas.line_height.round()
+    }
+
+    /// Starts a new frame's batch for a `viewport`-sized image.
+    pub fn begin(&mut self, viewport: (f32, f32)) {
+        self.vertices.clear();
+        self.viewport = Vec2::new(viewport.0, viewport.1);
+    }
+
+    /// One line of `text` with its top-left corner at `top_left`, over a
+    /// drop shadow. Returns its width.
+    pub fn text(&mut self, top_left: Vec2, text: &str, color: [f32; 4]) -> f32 {
+        let (glyphs, width) = self.atlas.layout(text);
+        let pen = top_left + Vec2::new(0.0, self.atlas.ascent.round());
+        for (offset, rect) in &glyphs {
+            self.quad(pen + *offset + 1.0, rect.size, rect, SHADOW);
+        }
+        for (offset, rect) in &glyphs {
+            self.quad(pen + *offset, rect.size, rect, color);
+        }
+        width
+    }
+
+    /// Two triangles covering `size` pixels from `min`, sampling `rect`.
+    pub fn quad(&mut self, min: Vec2, size: Vec2, rect: &AtlasRect, color: [f32; 4]) {
+        self.gradient(min, size, rect, [color, color]);
+    }
+
+    /// A flat-colored rectangle, e.g. a backdrop.
+    pub fn solid(&mut self, min: Vec2, size: Vec2, color: [f32; 4]) {
+        let solid = self.atlas.solid();
+        self.quad(min, size, &solid, color);
+    }
+
+    /// Like `quad`, blending from the left color to the right one.
+    pub fn gradient(&mut self, min: Vec2, size: Vec2, rect: &AtlasRect, [left, right]: [[f32; 4]; 2]) {
+        let viewport = self.viewport;
+        let corner = |x: f32, y: f32| {
+            let t = Vec2::new(x, y);
+            let ndc = (min + size * t) / viewport * 2.0 - 1.0;
+            ScreenVertex {
+                position: [ndc.x, -ndc.y],
+                uv: (rect.uv_min + (rect.uv_max - rect.uv_min) * t).to_array(),
+                color: if x == 0.0 { left } else { right },
+            }
+        };
+        let quad = [corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0), corner(0.0, 1.0)];
+        self.vertices.extend([0, 1, 2, 0, 2, 3].map(|i| quad[i]));
+    }
+
+    /// Sends this frame's batch to the GPU.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.num_vertices = self.vertices.len() as u32;
+    }
+
+    /// Draws over `view` in its own pass; call after everything else.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        if self.num_vertices == 0 {
+            return;
+        }
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.draw(0..self.num_vertices, 0..1);
+    }
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Overlay Vertex Buffer"),
+        size: (capacity * size_of::<ScreenVertex>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader = load_shader("Overlay Text", SHADER_PATH, device);
+    let constants = [("ENCODE_SRGB", if config.format.is_srgb() { 0.0 } else { 1.0 })];
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Overlay Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Overlay Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[ScreenVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: config.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None, // occlusion is decided on the CPU
+        multisample: Default::default(),
+        multiview: None,
+    })
+}
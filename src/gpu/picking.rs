@@ -0,0 +1,264 @@
+use std::num::NonZeroU64;
+use std::sync::mpsc;
+
+use anyhow::{Context, Result};
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{load_shader, DepthMode, Planet};
+use crate::gpu::planet::ChunkInstance;
+use crate::vertex::Vertex;
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+const SLOT_SIZE: u64 = 256; // uniform offset and texel copy alignment
+const MAX_OBJECTS: u64 = 256;
+
+/// Geometry the pick pass can draw. It uses the chunk layout: `Vertex` data
+/// in slot 0 and one camera-relative `ChunkInstance` offset per instance in
+/// slot 1. Slot 1 starts out holding a single zero offset, so meshes without
+/// chunks only bind slot 0.
+pub trait Pickable {
+    fn draw_pick(&self, rpass: &mut wgpu::RenderPass);
+}
+
+/// One object of a pick pass.
+pub struct PickObject<'a> {
+    pub id: u32,    // written to the id target, never 0
+    pub model: Mat4, // camera-relative
+    pub mesh: &'a dyn Pickable,
+}
+
+impl Pickable for Planet {
+    fn draw_pick(&self, rpass: &mut wgpu::RenderPass) {
+        self.draw(rpass);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct PickUniforms {
+    view_proj: [[f32; 4]; 4], // offset 0
+    object_id: u32,           // offset 64
+    _pad: [u32; 3],
+}
+
+/// The nearest object under a pixel, as read back from the GPU.
+#[derive(Copy, Clone, Debug)]
+pub struct GpuPick {
+    pub object_id: u32,
+    pub depth: f32, // reverse-Z
+}
+
+/// Renders object ids and depth into a 1×1 target around one pixel and reads
+/// them back. Works for any `Pickable` mesh, not just the planet.
+pub struct Picker {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    id_view: wgpu::TextureView,
+    id_texture: wgpu::Texture,
+    depth_view: wgpu::TextureView,
+    depth_texture: wgpu::Texture,
+    readback: wgpu::Buffer, // id at 0, depth at SLOT_SIZE
+    no_offset: wgpu::Buffer, // one zero `ChunkInstance`, for meshes without chunks
+}
+
+impl Picker {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Pick Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding:    0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty:                 wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true, // one slot per object
+                    min_binding_size:   NonZeroU64::new(size_of::<PickUniforms>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick UBO"),
+            size: SLOT_SIZE * MAX_OBJECTS,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Pick Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &uniform_buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(size_of::<PickUniforms>() as u64),
+                }),
+            }],
+        });
+
+        let shader = load_shader("Pick Shader", "src/shaders/pick.wgsl", device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            cache: None,
+            label: Some("Pick Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                compilation_options: Default::default(),
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc(), ChunkInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: Default::default(),
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(ID_FORMAT.into())],
+            }),
+            primitive: Default::default(), // no culling, so picks work from inside too
+            depth_stencil: Some(DepthMode::ReverseZ.depth_stencil_state(true)),
+            multisample: Default::default(),
+            multiview: None,
+        });
+
+        let id_texture = create_target(device, "Pick ID Target", ID_FORMAT);
+        let depth_texture = create_target(device, "Pick Depth Target", DepthMode::FORMAT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback"),
+            size: SLOT_SIZE * 2,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let no_offset = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pick Zero Offset"),
+            contents: bytemuck::bytes_of(&ChunkInstance::zeroed()),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            id_view: id_texture.create_view(&Default::default()),
+            id_texture,
+            depth_view: depth_texture.create_view(&Default::default()),
+            depth_texture,
+            readback,
+            no_offset,
+        }
+    }
+
+    /// Narrows a camera-relative `view_proj` (reverse-Z) to the single pixel
+    /// at `ndc` of a `viewport`-sized image.
+    pub fn pixel_matrix(view_proj: Mat4, ndc: Vec2, viewport: Vec2) -> Mat4 {
+        Mat4::from_scale(viewport.extend(1.0))
+            * Mat4::from_translation(Vec3::new(-ndc.x, -ndc.y, 0.0))
+            * view_proj
+    }
+
+    /// Draws `objects` with their ids through `view_proj` (from `pixel_matrix`)
+    /// and waits for the result. `None` if nothing covers the pixel.
+    pub fn pick(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        view_proj: Mat4,
+        objects: &[PickObject],
+    ) -> Result<Option<GpuPick>> {
+        if objects.len() > MAX_OBJECTS as usize {
+            log::warn!("picking only the first {MAX_OBJECTS} of {} objects", objects.len());
+        }
+        for (slot, object) in objects.iter().take(MAX_OBJECTS as usize).enumerate() {
+            let uniforms = PickUniforms {
+                view_proj: (view_proj * object.model).to_cols_array_2d(),
+                object_id: object.id,
+                _pad: [0; 3],
+            };
+            queue.write_buffer(&self.uniform_buffer, slot as u64 * SLOT_SIZE, bytemuck::bytes_of(&uniforms));
+        }
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pick Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.id_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), // id 0
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(DepthMode::ReverseZ.clear_value()),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            rpass.set_pipeline(&self.pipeline);
+            for (slot, object) in objects.iter().take(MAX_OBJECTS as usize).enumerate() {
+                rpass.set_bind_group(0, &self.bind_group, &[slot as u32 * SLOT_SIZE as u32]);
+                rpass.set_vertex_buffer(1, self.no_offset.slice(..));
+                object.mesh.draw_pick(&mut rpass);
+            }
+        }
+        for (texture, offset) in [(&self.id_texture, 0), (&self.depth_texture, SLOT_SIZE)] {
+            encoder.copy_texture_to_buffer(
+                wgpu::TexelCopyTextureInfo {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &self.readback,
+                    layout: wgpu::TexelCopyBufferLayout { offset, bytes_per_row: None, rows_per_image: None },
+                },
+                texture.size(),
+            );
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let slice = self.readback.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        device.poll(wgpu::PollType::Wait).context("waiting for the pick readback")?;
+        rx.try_recv()
+            .context("pick readback not mapped after waiting")?
+            .context("mapping the pick readback")?;
+        let (object_id, depth) = {
+            let data = slice.get_mapped_range();
+            let word = |offset: usize| [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
+            (u32::from_le_bytes(word(0)), f32::from_le_bytes(word(SLOT_SIZE as usize)))
+        };
+        self.readback.unmap();
+
+        Ok((object_id != 0).then_some(GpuPick { object_id, depth }))
+    }
+}
+
+fn create_target(device: &wgpu::Device, label: &str, format: wgpu::TextureFormat) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
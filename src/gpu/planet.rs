@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{DVec3, Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::lod::{self, ChunkKey, LodParams, LodView, PlanetLod};
+use crate::vertex::Vertex;
+
+/// Per-chunk data, fed to the planet shader as an instance vertex buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct ChunkInstance {
+    pub offset: [f32; 3], // chunk origin relative to the camera (computed in f64)
+    pub origin: [f32; 3], // chunk origin relative to the planet center, for shading
+}
+
+impl ChunkInstance {
+    pub const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![
+            3 => Float32x3,  // offset
+            4 => Float32x3   // origin
+        ];
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ChunkInstance>() as _,
+            step_mode:    wgpu::VertexStepMode::Instance,
+            attributes:   &Self::ATTRIBS,
+        }
+    }
+}
+
+struct Chunk {
+    buffer: wgpu::Buffer,
+    origin: DVec3,
+    vertices: Vec<Vertex>, // CPU copy for picking
+    bound_radius: f64,     // around `origin`, covers every vertex
+    normal_lines: Option<wgpu::Buffer>, // built while `Planet::show_normals` is set
+}
+
+const NORMAL_LINE_VERTICES: u32 = 2 * (lod::GRID as u32 + 1) * (lod::GRID as u32 + 1);
+
+/// GPU buffers for the chunks picked by `PlanetLod`.
+pub struct Planet {
+    pub lod: PlanetLod,
+    pub position: DVec3, // world position of the planet center
+    pub show_normals: bool, // keep line lists of the vertex normals for `draw_normals`
+
+    chunks: HashMap<ChunkKey, Chunk>,
+    indices: Vec<u16>, // shared by every chunk; the surface grid comes first, then skirts
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    drawn: Vec<ChunkKey>, // same order as the instance buffer
+}
+
+impl Planet {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let indices = lod::chunk_indices();
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let instance_capacity = 256;
+        let instance_buffer = create_instance_buffer(device, instance_capacity);
+
+        Self {
+            lod: PlanetLod::new(LodParams::default()),
+            position: DVec3::ZERO,
+            show_normals: false,
+
+            chunks: HashMap::new(),
+            num_indices: indices.len() as u32,
+            indices,
+            index_buffer,
+            instance_buffer,
+            instance_capacity,
+            drawn: Vec::new(),
+        }
+    }
+
+    /// Runs the LOD selection, uploads/frees chunk meshes accordingly and
+    /// rebases the visible chunks on the camera.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: &Camera,
+        view_proj: Mat4,
+        viewport_height: f32,
+    ) {
+        let changes = self.lod.update(&LodView {
+            eye: (camera.eye - self.position).as_vec3(),
+            view_proj,
+            forward: camera.forward().as_vec3(),
+            projection: camera.projection,
+            viewport_height,
+        });
+
+        for key in changes.evicted {
+            self.chunks.remove(&key);
+        }
+        for geometry in changes.uploads {
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Chunk Vertex Buffer"),
+                contents: bytemuck::cast_slice(&geometry.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            let bound_radius = geometry
+                .vertices
+                .iter()
+                .map(|v| Vec3::from(v.position).length() as f64)
+                .fold(0.0, f64::max);
+            self.chunks.insert(geometry.key, Chunk {
+                buffer,
+                origin: geometry.origin,
+                vertices: geometry.vertices,
+                bound_radius,
+                normal_lines: None,
+            });
+        }
+        for chunk in self.chunks.values_mut() {
+            if !self.show_normals {
+                chunk.normal_lines = None;
+            } else if chunk.normal_lines.is_none() {
+                chunk.normal_lines = Some(create_normal_lines(device, chunk));
+            }
+        }
+
+        self.drawn.clear();
+        let mut instances = Vec::with_capacity(self.lod.visible().len());
+        for key in self.lod.visible() {
+            if let Some(chunk) = self.chunks.get(key) {
+                self.drawn.push(*key);
+                instances.push(ChunkInstance {
+                    offset: camera.relative(self.position + chunk.origin).to_array(),
+                    origin: chunk.origin.as_vec3().to_array(),
+                });
+            }
+        }
+
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    /// Nearest drawn surface triangle along a world-space ray (unit `dir`):
+    /// distance and the triangle's normal, facing the ray. Skirts are ignored.
+    pub fn intersect(&self, origin: DVec3, dir: DVec3) -> Option<(f64, DVec3)> {
+        let grid = &self.indices[..(lod::GRID as usize).pow(2) * 6];
+        let mut nearest: Option<(f64, DVec3)> = None;
+        for key in &self.drawn {
+            let chunk = &self.chunks[key];
+            let local = origin - (self.position + chunk.origin);
+            // cheap reject against the chunk's bounding sphere
+            let b = local.dot(dir);
+            if b * b - local.length_squared() + chunk.bound_radius * chunk.bound_radius < 0.0 {
+                continue;
+            }
+            for tri in grid.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(chunk.vertices[tri[i] as usize].position).as_dvec3());
+                if let Some(t) = ray_triangle(local, dir, a, b, c)
+                    && nearest.is_none_or(|(n, _)| t < n)
+                {
+                    let normal = (b - a).cross(c - a).normalize();
+                    nearest = Some((t, if normal.dot(dir) > 0.0 { -normal } else { normal }));
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Draws the visible chunks with the currently bound pipeline and bind groups.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for (i, key) in self.drawn.iter().enumerate() {
+            let chunk = &self.chunks[key];
+            let instance = i as u32;
+            rpass.set_vertex_buffer(0, chunk.buffer.slice(..));
+            rpass.draw_indexed(0..self.num_indices, 0, instance..instance + 1);
+        }
+    }
+
+    /// Draws the normal lines of the visible chunks with the currently bound
+    /// line-list pipeline; nothing unless `show_normals` was set at the last
+    /// `update`.
+    pub fn draw_normals(&self, rpass: &mut wgpu::RenderPass) {
+        rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for (i, key) in self.drawn.iter().enumerate() {
+            if let Some(lines) = &self.chunks[key].normal_lines {
+                let instance = i as u32;
+                rpass.set_vertex_buffer(0, lines.slice(..));
+                rpass.draw(0..NORMAL_LINE_VERTICES, instance..instance + 1);
+            }
+        }
+    }
+}
+
+/// One line per surface vertex (skirts left out), about a grid cell long,
+/// in the chunk's vertex format so it goes through the planet vertex shader.
+fn create_normal_lines(device: &wgpu::Device, chunk: &Chunk) -> wgpu::Buffer {
+    let length = (chunk.bound_radius / lod::GRID as f64) as f32;
+    let surface = &chunk.vertices[..NORMAL_LINE_VERTICES as usize / 2];
+    let lines: Vec<Vertex> = surface
+        .iter()
+        .flat_map(|v| {
+            let tip = Vec3::from(v.position) + Vec3::from(v.normal) * length;
+            [*v, Vertex { position: tip.to_array(), ..*v }]
+        })
+        .collect();
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Chunk Normal Lines"),
+        contents: bytemuck::cast_slice(&lines),
+        usage: wgpu::BufferUsages::VERTEX,
+    })
+}
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Chunk Instance Buffer"),
+        size: (capacity * size_of::<ChunkInstance>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Möller–Trumbore, both faces; distance along `dir` if the ray hits.
+fn ray_triangle(origin: DVec3, dir: DVec3, a: DVec3, b: DVec3, c: DVec3) -> Option<f64> {
+    let (e1, e2) = (b - a, c - a);
+    let p = dir.cross(e2);
+    let det = e1.dot(p);
+    if det.abs() < 1e-18 {
+        return None; // parallel
+    }
+    let inv = 1.0 / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(e1);
+    let v = dir.dot(q) * inv;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = e2.dot(q) * inv;
+    (t > 0.0).then_some(t)
+}
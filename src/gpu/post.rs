@@ -0,0 +1,622 @@
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use bytemuck::{Pod, Zeroable};
+use serde::Deserialize;
+use wgpu::util::DeviceExt;
+
+use crate::gpu::load_shader;
+
+/// The scene is rendered into this and only turned into display colors by
+/// the post chain.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Between display-referred passes; sRGB so 8 bits are spent evenly.
+const LDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+const BLOOM_SHADER_PATH: &str = "src/shaders/bloom.wgsl";
+const TONEMAP_SHADER_PATH: &str = "src/shaders/tonemap.wgsl";
+const GRADING_SHADER_PATH: &str = "src/shaders/color_grading.wgsl";
+const FXAA_SHADER_PATH: &str = "src/shaders/fxaa.wgsl";
+const MAX_BLOOM_LEVELS: u32 = 6;
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+/// How HDR colors are compressed into the displayable range.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tonemapper {
+    None, // clamp
+    Reinhard,
+    #[default]
+    Aces,
+    Agx,
+}
+
+impl Tonemapper {
+    pub fn next(self) -> Self {
+        match self {
+            Tonemapper::None => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::Aces,
+            Tonemapper::Aces => Tonemapper::Agx,
+            Tonemapper::Agx => Tonemapper::None,
+        }
+    }
+
+    /// Must match the constants in tonemap.wgsl.
+    fn shader_index(self) -> u32 {
+        match self {
+            Tonemapper::None => 0,
+            Tonemapper::Reinhard => 1,
+            Tonemapper::Aces => 2,
+            Tonemapper::Agx => 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    pub threshold: f32, // brightness where bloom starts
+    pub knee: f32,      // width of the soft transition around the threshold
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self { enabled: true, threshold: 1.0, knee: 0.5, intensity: 0.05 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ColorGradingSettings {
+    pub enabled: bool,
+    pub lut: Option<PathBuf>, // N²×N strip PNG, relative to the config file; identity if unset
+    pub strength: f32,        // 0 keeps the tonemapped colors, 1 applies the LUT fully
+}
+
+impl Default for ColorGradingSettings {
+    fn default() -> Self {
+        Self { enabled: false, lut: None, strength: 1.0 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FxaaSettings {
+    pub enabled: bool,
+}
+
+impl Default for FxaaSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// `post.toml`: which passes run and how.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PostSettings {
+    pub tonemapper: Tonemapper,
+    pub exposure: f32, // stops
+    pub bloom: BloomSettings,
+    pub color_grading: ColorGradingSettings,
+    pub fxaa: FxaaSettings,
+}
+
+impl PostSettings {
+    /// Loads `path`, falling back to the defaults (with a warning) if it is
+    /// missing or invalid.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                log::warn!("{} not loaded ({err}), using default post-processing", path.display());
+                return Self::default();
+            }
+        };
+        match toml::from_str::<PostSettings>(&text) {
+            Ok(mut settings) => {
+                let base = path.parent().unwrap_or(Path::new(""));
+                settings.color_grading.lut = settings.color_grading.lut.map(|lut| base.join(lut));
+                settings
+            }
+            Err(err) => {
+                log::warn!("{} is invalid ({err}), using default post-processing", path.display());
+                Self::default()
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct PostUniforms {
+    exposure: f32,         // 4 bytes @ offset 0, linear scale
+    tonemapper: u32,       // 4 bytes @ offset 4
+    bloom_intensity: f32,  // 4 bytes @ offset 8, 0 while bloom is off
+    bloom_threshold: f32,  // 4 bytes @ offset 12
+    bloom_knee: f32,       // 4 bytes @ offset 16
+    grading_strength: f32, // 4 bytes @ offset 20
+    _pad: [f32; 2],        // 8 bytes @ offset 24
+} // total size = 32 bytes
+
+/// Everything that depends on the frame size.
+struct Targets {
+    scene_view: wgpu::TextureView,
+    ldr_views: [wgpu::TextureView; 2], // ping-pong between display-referred passes
+    bloom_views: Vec<wgpu::TextureView>, // one per mip, half resolution and down
+    bloom_prefilter: wgpu::BindGroup,    // reads the scene
+    bloom_levels: Vec<wgpu::BindGroup>,  // reads bloom mip i
+    tonemap: wgpu::BindGroup,
+    grading: [wgpu::BindGroup; 2], // reads ldr_views[i]
+    fxaa: [wgpu::BindGroup; 2],
+}
+
+struct Pipelines {
+    bloom_prefilter: wgpu::RenderPipeline,
+    bloom_downsample: wgpu::RenderPipeline,
+    bloom_upsample: wgpu::RenderPipeline,
+    // display-referred passes: [into the ping-pong targets, into the output]
+    tonemap: [wgpu::RenderPipeline; 2],
+    grading: [wgpu::RenderPipeline; 2],
+    fxaa: [wgpu::RenderPipeline; 2],
+}
+
+/// The HDR scene target and the full-screen passes that turn it into the
+/// displayed frame: bloom, exposure and tonemapping, a color grading LUT
+/// and FXAA. Each pass has its own shader and can be switched off.
+pub struct PostProcess {
+    pub settings: PostSettings,
+
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    lut_view: wgpu::TextureView,
+    input_layout: wgpu::BindGroupLayout,   // input + sampler + uniforms
+    tonemap_layout: wgpu::BindGroupLayout, // … + bloom
+    grading_layout: wgpu::BindGroupLayout, // … + 3D LUT
+    targets: Targets,
+    pipelines: Pipelines,
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        settings: PostSettings,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post UBO"),
+            contents: bytemuck::bytes_of(&PostUniforms::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let lut = match &settings.color_grading.lut {
+            Some(path) => load_lut(path).unwrap_or_else(|err| {
+                log::warn!("color grading LUT not loaded: {err:#}");
+                identity_lut(IDENTITY_LUT_SIZE)
+            }),
+            None => identity_lut(IDENTITY_LUT_SIZE),
+        };
+        let lut_view = create_lut_texture(device, queue, &lut).create_view(&Default::default());
+
+        let input_layout = create_layout(device, "Post Input Bind Group Layout", None);
+        let tonemap_layout = create_layout(device, "Tonemap Bind Group Layout", Some(wgpu::TextureViewDimension::D2));
+        let grading_layout = create_layout(device, "Grading Bind Group Layout", Some(wgpu::TextureViewDimension::D3));
+
+        let post = Self {
+            settings,
+            targets: create_targets(
+                device,
+                config,
+                &uniform_buffer,
+                &sampler,
+                &lut_view,
+                [&input_layout, &tonemap_layout, &grading_layout],
+            ),
+            pipelines: create_pipelines(device, config.format, &input_layout, &tonemap_layout, &grading_layout),
+            uniform_buffer,
+            sampler,
+            lut_view,
+            input_layout,
+            tonemap_layout,
+            grading_layout,
+        };
+        post.update(queue);
+        post
+    }
+
+    /// Where the scene passes draw.
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.targets.scene_view
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.targets = create_targets(
+            device,
+            config,
+            &self.uniform_buffer,
+            &self.sampler,
+            &self.lut_view,
+            [&self.input_layout, &self.tonemap_layout, &self.grading_layout],
+        );
+    }
+
+    pub fn reload_pipeline(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.pipelines = create_pipelines(
+            device,
+            config.format,
+            &self.input_layout,
+            &self.tonemap_layout,
+            &self.grading_layout,
+        );
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let s = &self.settings;
+        let uniforms = PostUniforms {
+            exposure: s.exposure.exp2(),
+            tonemapper: s.tonemapper.shader_index(),
+            bloom_intensity: if s.bloom.enabled { s.bloom.intensity } else { 0.0 },
+            bloom_threshold: s.bloom.threshold,
+            bloom_knee: s.bloom.knee,
+            grading_strength: s.color_grading.strength,
+            _pad: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Runs the chain from the scene target into `output`.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        let (t, p) = (&self.targets, &self.pipelines);
+
+        if self.settings.bloom.enabled {
+            // bright parts down the mip chain, then back up, accumulating
+            let (prefilter, clear) = (&t.bloom_prefilter, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+            full_screen_pass(encoder, "Bloom Prefilter", &t.bloom_views[0], &p.bloom_prefilter, prefilter, clear);
+            for level in 1..t.bloom_views.len() {
+                let (view, source) = (&t.bloom_views[level], &t.bloom_levels[level - 1]);
+                full_screen_pass(encoder, "Bloom Downsample", view, &p.bloom_downsample, source, clear);
+            }
+            // adds onto what the downsample left in the level
+            for level in (0..t.bloom_views.len() - 1).rev() {
+                let (view, source) = (&t.bloom_views[level], &t.bloom_levels[level + 1]);
+                full_screen_pass(encoder, "Bloom Upsample", view, &p.bloom_upsample, source, wgpu::LoadOp::Load);
+            }
+        }
+
+        // display-referred passes after tonemapping; the last one writes `output`
+        let mut passes = vec![("Tonemap", &p.tonemap, None)];
+        if self.settings.color_grading.enabled {
+            passes.push(("Color Grading", &p.grading, Some(&t.grading)));
+        }
+        if self.settings.fxaa.enabled {
+            passes.push(("FXAA", &p.fxaa, Some(&t.fxaa)));
+        }
+        let last = passes.len() - 1;
+        for (i, (label, pipelines, inputs)) in passes.into_iter().enumerate() {
+            // pass i reads what pass i - 1 wrote to ldr_views[(i - 1) % 2]
+            let bind_group = match inputs {
+                Some(inputs) => &inputs[(i - 1) % 2],
+                None => &t.tonemap,
+            };
+            let view = if i == last { output } else { &t.ldr_views[i % 2] };
+            let pipeline = &pipelines[(i == last) as usize];
+            full_screen_pass(encoder, label, view, pipeline, bind_group, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+        }
+    }
+}
+
+fn full_screen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    view: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+    rpass.set_pipeline(pipeline);
+    rpass.set_bind_group(0, bind_group, &[]);
+    rpass.draw(0..3, 0..1);
+}
+
+/// Color cube from an N²×N strip: N slices side by side, blue increasing to
+/// the right, red across each slice and green down it.
+struct Lut {
+    size: u32,
+    texels: Vec<u8>, // RGBA, red fastest, then green, then blue
+}
+
+fn load_lut(path: &Path) -> Result<Lut> {
+    let image = image::open(path).with_context(|| format!("loading {}", path.display()))?.into_rgba8();
+    let size = image.height();
+    ensure!(
+        size >= 2 && image.width() == size * size,
+        "{} is {}x{}, expected an N²xN strip",
+        path.display(),
+        image.width(),
+        image.height(),
+    );
+    let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                texels.extend_from_slice(&image.get_pixel(b * size + r, g).0);
+            }
+        }
+    }
+    Ok(Lut { size, texels })
+}
+
+fn identity_lut(size: u32) -> Lut {
+    let level = |i: u32| (i * 255 / (size - 1)) as u8;
+    let mut texels = Vec::with_capacity((size * size * size * 4) as usize);
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                texels.extend_from_slice(&[level(r), level(g), level(b), 255]);
+            }
+        }
+    }
+    Lut { size, texels }
+}
+
+fn create_lut_texture(device: &wgpu::Device, queue: &wgpu::Queue, lut: &Lut) -> wgpu::Texture {
+    let size = wgpu::Extent3d { width: lut.size, height: lut.size, depth_or_array_layers: lut.size };
+    // sRGB-encoded in and out, so no conversion on sampling
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("Color Grading LUT"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &lut.texels,
+    )
+}
+
+fn create_render_target(
+    device: &wgpu::Device,
+    label: &str,
+    (width, height): (u32, u32),
+    format: wgpu::TextureFormat,
+    mip_level_count: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    uniform_buffer: &wgpu::Buffer,
+    sampler: &wgpu::Sampler,
+    lut_view: &wgpu::TextureView,
+    [input_layout, tonemap_layout, grading_layout]: [&wgpu::BindGroupLayout; 3],
+) -> Targets {
+    let size = (config.width, config.height);
+    let scene_view = create_render_target(device, "HDR Scene", size, HDR_FORMAT, 1).create_view(&Default::default());
+    let ldr_views = ["Post Ping", "Post Pong"]
+        .map(|label| create_render_target(device, label, size, LDR_FORMAT, 1).create_view(&Default::default()));
+
+    // half resolution and down, stopping before the smaller side gets tiny
+    let bloom_size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+    let mut levels = 1;
+    while levels < MAX_BLOOM_LEVELS && bloom_size.0.min(bloom_size.1) >> levels >= 4 {
+        levels += 1;
+    }
+    let bloom = create_render_target(device, "Bloom", bloom_size, HDR_FORMAT, levels);
+    let bloom_views: Vec<_> = (0..levels)
+        .map(|level| {
+            bloom.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    let bind_group = |label: &str, layout: &wgpu::BindGroupLayout, input: &wgpu::TextureView, extra: Option<&wgpu::TextureView>| {
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+        ];
+        if let Some(extra) = extra {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(extra),
+            });
+        }
+        device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some(label), layout, entries: &entries })
+    };
+
+    Targets {
+        bloom_prefilter: bind_group("Bloom Prefilter Bind Group", input_layout, &scene_view, None),
+        bloom_levels: bloom_views.iter().map(|v| bind_group("Bloom Bind Group", input_layout, v, None)).collect(),
+        tonemap: bind_group("Tonemap Bind Group", tonemap_layout, &scene_view, Some(&bloom_views[0])),
+        grading: [0, 1].map(|i| bind_group("Grading Bind Group", grading_layout, &ldr_views[i], Some(lut_view))),
+        fxaa: [0, 1].map(|i| bind_group("FXAA Bind Group", input_layout, &ldr_views[i], None)),
+        scene_view,
+        ldr_views,
+        bloom_views,
+    }
+}
+
+/// Input texture, sampler and the post uniforms, plus an optional second
+/// texture of the given dimension at binding 3.
+fn create_layout(
+    device: &wgpu::Device,
+    label: &str,
+    extra: Option<wgpu::TextureViewDimension>,
+) -> wgpu::BindGroupLayout {
+    let texture = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension,
+            multisampled:   false,
+        },
+        count: None,
+    };
+    let mut entries = vec![
+        // binding 0 = input
+        texture(0, wgpu::TextureViewDimension::D2),
+        // binding 1 = linear clamp sampler
+        wgpu::BindGroupLayoutEntry {
+            binding:    1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+        // binding 2 = Post UBO
+        wgpu::BindGroupLayoutEntry {
+            binding:    2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty:                wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size:  NonZeroU64::new(size_of::<PostUniforms>() as u64),
+            },
+            count: None,
+        },
+    ];
+    // binding 3 = bloom (tonemap) or LUT (grading)
+    entries.extend(extra.map(|dimension| texture(3, dimension)));
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some(label), entries: &entries })
+}
+
+/// Pipelines writing the output encode to sRGB themselves when
+/// `surface_format` doesn't.
+fn create_pipelines(
+    device: &wgpu::Device,
+    surface_format: wgpu::TextureFormat,
+    input_layout: &wgpu::BindGroupLayout,
+    tonemap_layout: &wgpu::BindGroupLayout,
+    grading_layout: &wgpu::BindGroupLayout,
+) -> Pipelines {
+    let bloom = load_shader("Bloom", BLOOM_SHADER_PATH, device);
+    let tonemap = load_shader("Tonemap", TONEMAP_SHADER_PATH, device);
+    let grading = load_shader("Color Grading", GRADING_SHADER_PATH, device);
+    let fxaa = load_shader("FXAA", FXAA_SHADER_PATH, device);
+    let additive = wgpu::BlendState {
+        color: wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendComponent::REPLACE,
+    };
+    let upsample_target = wgpu::ColorTargetState {
+        format: HDR_FORMAT,
+        blend: Some(additive),
+        write_mask: wgpu::ColorWrites::ALL,
+    };
+
+    let bloom_pipeline = |label: &str, entry_point, target| {
+        create_pipeline(device, label, &bloom, entry_point, input_layout, target, &[])
+    };
+    let encode_srgb = if surface_format.is_srgb() { 0.0 } else { 1.0 };
+    let display_pipelines = |label: &str, module, layout| {
+        [
+            create_pipeline(device, label, module, "fs_main", layout, LDR_FORMAT.into(), &[("ENCODE_SRGB", 0.0)]),
+            create_pipeline(device, label, module, "fs_main", layout, surface_format.into(), &[("ENCODE_SRGB", encode_srgb)]),
+        ]
+    };
+    Pipelines {
+        bloom_prefilter: bloom_pipeline("Bloom Prefilter Pipeline", "fs_prefilter", HDR_FORMAT.into()),
+        bloom_downsample: bloom_pipeline("Bloom Downsample Pipeline", "fs_downsample", HDR_FORMAT.into()),
+        bloom_upsample: bloom_pipeline("Bloom Upsample Pipeline", "fs_upsample", upsample_target),
+        tonemap: display_pipelines("Tonemap Pipeline", &tonemap, tonemap_layout),
+        grading: display_pipelines("Color Grading Pipeline", &grading, grading_layout),
+        fxaa: display_pipelines("FXAA Pipeline", &fxaa, input_layout),
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    module: &wgpu::ShaderModule,
+    entry_point: &str,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    target: wgpu::ColorTargetState,
+    constants: &[(&str, f64)],
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some(label),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: wgpu::PipelineCompilationOptions { constants, ..Default::default() },
+            module,
+            entry_point: Some(entry_point),
+            targets: &[Some(target)],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,333 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use glam::Vec2;
+
+use crate::gpu::OverlayText;
+
+const MAX_PASSES: u32 = 32;
+const READBACK_FRAMES: usize = 3; // frames whose timestamps can be in flight
+const AVERAGE_FRAMES: usize = 60;
+const TRACE_EVENTS: usize = 20_000; // kept for the Chrome trace, oldest dropped first
+const OVERLAY_MARGIN: f32 = 16.0;   // pixels from the top-left corner
+
+/// Mean of the last `AVERAGE_FRAMES` samples.
+#[derive(Default)]
+struct RollingAverage {
+    samples: VecDeque<f64>,
+    sum: f64,
+}
+
+impl RollingAverage {
+    fn push(&mut self, value: f64) {
+        if self.samples.len() == AVERAGE_FRAMES {
+            self.sum -= self.samples.pop_front().unwrap_or_default();
+        }
+        self.samples.push_back(value);
+        self.sum += value;
+    }
+
+    fn get(&self) -> f64 {
+        self.sum / self.samples.len().max(1) as f64
+    }
+}
+
+struct TraceEvent {
+    name: &'static str,
+    gpu: bool,
+    start_us: f64, // since the profiler was created
+    duration_us: f64,
+}
+
+/// A frame's timestamps on their way back from the GPU.
+struct Readback {
+    buffer: wgpu::Buffer,
+    passes: Vec<&'static str>,
+    frame_start_us: f64,
+    mapped: Option<Receiver<bool>>, // Some while in flight
+}
+
+/// Timestamp queries written between render graph passes.
+struct GpuTimestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    period: f32,              // nanoseconds per tick
+    inside_encoders: bool,    // otherwise timestamps are written by empty compute passes
+    recording: Option<usize>, // readback this frame writes into
+}
+
+/// CPU frame timing and, where the adapter supports timestamp queries, GPU
+/// time per render graph pass. Shown as rolling averages and exportable as a
+/// Chrome trace (`chrome://tracing`, Perfetto).
+///
+/// GPU results arrive a few frames late and are never waited for; frames
+/// whose readback is still busy simply go unmeasured.
+pub struct Profiler {
+    pub enabled: bool,
+    gpu: Option<GpuTimestamps>, // None without `TIMESTAMP_QUERY`
+
+    epoch: Instant,
+    frame_start: Instant,
+    last_frame_start: Option<Instant>,
+    frame_time: RollingAverage,  // between frames
+    encode_time: RollingAverage, // CPU time spent building a frame
+    gpu_time: RollingAverage,    // all passes
+    passes: Vec<(&'static str, RollingAverage)>,
+    trace: VecDeque<TraceEvent>,
+}
+
+impl Profiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let gpu = device.features().contains(wgpu::Features::TIMESTAMP_QUERY).then(|| {
+            let size = (MAX_PASSES as u64 + 1) * wgpu::QUERY_SIZE as u64;
+            GpuTimestamps {
+                query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("Profiler Timestamps"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: MAX_PASSES + 1,
+                }),
+                resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Profiler Resolve Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readbacks: (0..READBACK_FRAMES)
+                    .map(|_| Readback {
+                        buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                            label: Some("Profiler Readback Buffer"),
+                            size,
+                            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                            mapped_at_creation: false,
+                        }),
+                        passes: Vec::new(),
+                        frame_start_us: 0.0,
+                        mapped: None,
+                    })
+                    .collect(),
+                period: queue.get_timestamp_period(),
+                inside_encoders: device.features().contains(wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS),
+                recording: None,
+            }
+        });
+        Self {
+            enabled: false,
+            gpu,
+            epoch: Instant::now(),
+            frame_start: Instant::now(),
+            last_frame_start: None,
+            frame_time: RollingAverage::default(),
+            encode_time: RollingAverage::default(),
+            gpu_time: RollingAverage::default(),
+            passes: Vec::new(),
+            trace: VecDeque::new(),
+        }
+    }
+
+    /// Whether the adapter can time passes; CPU timings work regardless.
+    pub fn gpu_supported(&self) -> bool {
+        self.gpu.is_some()
+    }
+
+    pub fn begin_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame_start.replace(now)
+            && self.enabled
+        {
+            self.frame_time.push((now - last).as_secs_f64() * 1e3);
+        }
+        self.frame_start = now;
+    }
+
+    /// Marks the start of the first pass; `end_pass` marks the end of each.
+    pub fn begin_passes(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(gpu) = self.gpu.as_mut().filter(|_| self.enabled) else {
+            return;
+        };
+        gpu.recording = gpu.readbacks.iter().position(|readback| readback.mapped.is_none());
+        if let Some(index) = gpu.recording {
+            gpu.readbacks[index].passes.clear();
+            gpu.write_timestamp(encoder, 0);
+        }
+    }
+
+    pub fn end_pass(&mut self, encoder: &mut wgpu::CommandEncoder, name: &'static str) {
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        let Some(index) = gpu.recording else {
+            return;
+        };
+        let passes = gpu.readbacks[index].passes.len() as u32;
+        if passes < MAX_PASSES {
+            gpu.readbacks[index].passes.push(name);
+            gpu.write_timestamp(encoder, passes + 1);
+        }
+    }
+
+    /// Copies this frame's timestamps out; call before submitting `encoder`.
+    pub fn end_frame(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.enabled {
+            return;
+        }
+        let start_us = (self.frame_start - self.epoch).as_secs_f64() * 1e6;
+        let encode_us = self.frame_start.elapsed().as_secs_f64() * 1e6;
+        self.encode_time.push(encode_us * 1e-3);
+        self.record(TraceEvent { name: "Frame", gpu: false, start_us, duration_us: encode_us });
+
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        let Some(index) = gpu.recording else {
+            return;
+        };
+        let readback = &mut gpu.readbacks[index];
+        let size = (readback.passes.len() as u64 + 1) * wgpu::QUERY_SIZE as u64;
+        encoder.resolve_query_set(&gpu.query_set, 0..readback.passes.len() as u32 + 1, &gpu.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&gpu.resolve_buffer, 0, &readback.buffer, 0, size);
+        readback.frame_start_us = start_us;
+    }
+
+    /// Starts reading back this frame's timestamps and collects those of
+    /// earlier frames that have arrived. Call after submitting.
+    pub fn after_submit(&mut self, device: &wgpu::Device) {
+        let Some(gpu) = &mut self.gpu else {
+            return;
+        };
+        if let Some(index) = gpu.recording.take() {
+            let readback = &mut gpu.readbacks[index];
+            let (tx, rx) = mpsc::channel();
+            let size = (readback.passes.len() as u64 + 1) * wgpu::QUERY_SIZE as u64;
+            readback.buffer.slice(..size).map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result.is_ok());
+            });
+            readback.mapped = Some(rx);
+        }
+        let _ = device.poll(wgpu::PollType::Poll);
+
+        let mut finished = Vec::new();
+        for readback in &mut gpu.readbacks {
+            let Some(mapped) = &readback.mapped else {
+                continue;
+            };
+            match mapped.try_recv() {
+                Err(TryRecvError::Empty) => continue,
+                Ok(true) => {
+                    let size = (readback.passes.len() as u64 + 1) * wgpu::QUERY_SIZE as u64;
+                    let ticks: Vec<u64> =
+                        bytemuck::pod_collect_to_vec(&readback.buffer.slice(..size).get_mapped_range());
+                    readback.buffer.unmap();
+                    finished.push((readback.frame_start_us, readback.passes.clone(), ticks));
+                }
+                Ok(false) | Err(TryRecvError::Disconnected) => {} // lost, e.g. the device was lost
+            }
+            readback.mapped = None;
+        }
+
+        let period_us = gpu.period as f64 * 1e-3;
+        for (frame_start_us, passes, ticks) in finished {
+            // GPU and CPU clocks differ, so the trace lines passes up with
+            // the start of the frame on the CPU
+            let mut total = 0.0;
+            for (name, pair) in passes.into_iter().zip(ticks.windows(2)) {
+                let start_us = frame_start_us + pair[0].saturating_sub(ticks[0]) as f64 * period_us;
+                let duration_us = pair[1].saturating_sub(pair[0]) as f64 * period_us;
+                total += duration_us;
+                self.pass_average(name).push(duration_us * 1e-3);
+                self.record(TraceEvent { name, gpu: true, start_us, duration_us });
+            }
+            self.gpu_time.push(total * 1e-3);
+        }
+    }
+
+    /// Rolling averages, one line each, for the on-screen overlay.
+    pub fn summary(&self) -> Vec<String> {
+        let frame = self.frame_time.get();
+        let fps = if frame > 0.0 { 1e3 / frame } else { 0.0 };
+        let mut lines = vec![
+            format!("Frame    {frame:6.2} ms  {fps:5.0} fps"),
+            format!("CPU      {:6.2} ms", self.encode_time.get()),
+        ];
+        if self.gpu.is_none() {
+            lines.push("GPU      no timestamp queries".to_string());
+            return lines;
+        }
+        lines.push(format!("GPU      {:6.2} ms", self.gpu_time.get()));
+        for (name, average) in &self.passes {
+            lines.push(format!("  {name:<14} {:6.2} ms", average.get()));
+        }
+        lines
+    }
+
+    /// Lays out the summary in the top-left corner while enabled.
+    pub fn layout_summary(&self, overlay: &mut OverlayText) {
+        if !self.enabled {
+            return;
+        }
+        let line_height = overlay.line_height();
+        for (i, line) in self.summary().iter().enumerate() {
+            overlay.text(Vec2::new(OVERLAY_MARGIN, OVERLAY_MARGIN + line_height * i as f32), line, [1.0; 4]);
+        }
+    }
+
+    /// Writes the recorded frames in the Chrome trace event format.
+    pub fn write_chrome_trace(&self, path: &Path) -> Result<()> {
+        let mut json = String::from("{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n");
+        json.push_str("{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":1,\"args\":{\"name\":\"CPU\"}},\n");
+        json.push_str("{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":2,\"args\":{\"name\":\"GPU\"}}");
+        for event in &self.trace {
+            let _ = write!(
+                json,
+                ",\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+                event.name.replace('"', "'"),
+                if event.gpu { "gpu" } else { "cpu" },
+                if event.gpu { 2 } else { 1 },
+                event.start_us,
+                event.duration_us,
+            );
+        }
+        json.push_str("\n]}\n");
+        std::fs::write(path, json).with_context(|| format!("writing {}", path.display()))
+    }
+
+    fn pass_average(&mut self, name: &'static str) -> &mut RollingAverage {
+        let index = match self.passes.iter().position(|(pass, _)| *pass == name) {
+            Some(index) => index,
+            None => {
+                self.passes.push((name, RollingAverage::default()));
+                self.passes.len() - 1
+            }
+        };
+        &mut self.passes[index].1
+    }
+
+    fn record(&mut self, event: TraceEvent) {
+        if self.trace.len() == TRACE_EVENTS {
+            self.trace.pop_front();
+        }
+        self.trace.push_back(event);
+    }
+}
+
+impl GpuTimestamps {
+    fn write_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        if self.inside_encoders {
+            encoder.write_timestamp(&self.query_set, index);
+            return;
+        }
+        // plain TIMESTAMP_QUERY only allows them at pass boundaries
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Profiler Timestamp"),
+            timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: None,
+            }),
+        });
+    }
+}
@@ -0,0 +1,503 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+
+use crate::gpu::Profiler;
+
+/// A texture the graph allocates for the frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureDesc {
+    pub label: &'static str,
+    pub scale: f32, // of the surface size, e.g. 0.5 for half resolution
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TextureDesc {
+    fn size(&self, surface: (u32, u32)) -> (u32, u32) {
+        let scaled = |extent: u32| ((extent as f32 * self.scale).round() as u32).max(1);
+        (scaled(surface.0), scaled(surface.1))
+    }
+}
+
+/// A texture declared in a graph, imported or transient.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextureHandle(usize);
+
+enum TextureSource<'a> {
+    Imported(&'a wgpu::TextureView),
+    Transient(TextureDesc),
+}
+
+struct GraphTexture<'a> {
+    name: &'static str,
+    source: TextureSource<'a>,
+}
+
+type PassFn<'a> = Box<dyn FnOnce(&mut wgpu::CommandEncoder, &PassResources) + 'a>;
+
+struct Pass<'a> {
+    name: &'static str,
+    reads: Vec<TextureHandle>,
+    writes: Vec<TextureHandle>,
+    side_effect: bool, // kept even if nothing reads what it writes
+    run: Option<PassFn<'a>>,
+}
+
+/// One frame's passes and the textures they read and write.
+///
+/// Passes are ordered from their declarations rather than the order they were
+/// added in: every writer of a texture runs before its readers, while writers
+/// of the same texture keep the order they were added in (so later ones draw
+/// over earlier ones). Passes whose output nothing uses are culled; writing an
+/// imported texture or being marked as a side effect counts as being used.
+///
+/// Transient textures come from a `TransientPool` that outlives the frame.
+/// Transients whose lifetimes don't overlap share a texture, and the same
+/// graph gets the same textures every frame, so bind groups over them can be
+/// cached.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    textures: Vec<GraphTexture<'a>>,
+    passes: Vec<Pass<'a>>,
+}
+
+/// Pass order and transient placement worked out from the declarations.
+struct Schedule {
+    order: Vec<usize>,         // live passes, in execution order
+    slots: Vec<Option<usize>>, // pool slot per texture, for transients in use
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A texture owned outside the graph, e.g. the frame being presented.
+    pub fn import_texture(&mut self, name: &'static str, view: &'a wgpu::TextureView) -> TextureHandle {
+        self.textures.push(GraphTexture { name, source: TextureSource::Imported(view) });
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    /// A texture that only lives for this frame.
+    pub fn create_texture(&mut self, desc: TextureDesc) -> TextureHandle {
+        self.textures.push(GraphTexture { name: desc.label, source: TextureSource::Transient(desc) });
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, name: &'static str) -> PassBuilder<'_, 'a> {
+        self.passes.push(Pass { name, reads: Vec::new(), writes: Vec::new(), side_effect: false, run: None });
+        let index = self.passes.len() - 1;
+        PassBuilder { graph: self, index }
+    }
+
+    /// Orders the passes, places the transients in `pool` and records every
+    /// live pass into `encoder`, timing each with `profiler`. Records nothing
+    /// if the passes depend on each other in a cycle.
+    pub fn execute(
+        self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut TransientPool,
+        profiler: &mut Profiler,
+        surface_size: (u32, u32),
+    ) -> Result<()> {
+        let schedule = self.schedule(pool, surface_size)?;
+        pool.allocate(device);
+
+        let views: Vec<_> = self
+            .textures
+            .iter()
+            .zip(&schedule.slots)
+            .map(|(texture, slot)| match (&texture.source, slot) {
+                (TextureSource::Imported(view), _) => Some(*view),
+                (TextureSource::Transient(_), Some(slot)) => pool.view(*slot),
+                (TextureSource::Transient(_), None) => None,
+            })
+            .collect();
+
+        let mut passes: Vec<_> = self.passes.into_iter().map(Some).collect();
+        profiler.begin_passes(encoder);
+        for index in schedule.order {
+            let pass = passes[index].take().expect("passes run once");
+            let resources = PassResources { pass: pass.name, views: &views, reads: &pass.reads, writes: &pass.writes };
+            if let Some(run) = pass.run {
+                run(encoder, &resources);
+            }
+            profiler.end_pass(encoder, pass.name);
+        }
+        pool.release_unused();
+        Ok(())
+    }
+
+    /// The graph in Graphviz DOT: passes are boxes numbered in execution
+    /// order (culled ones dashed), textures are ellipses (transients dashed,
+    /// with their resolved size).
+    pub fn to_dot(&self, surface_size: (u32, u32)) -> Result<String> {
+        let order = self.order()?;
+        let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n    node [fontname=\"Helvetica\"];\n");
+
+        for (index, texture) in self.textures.iter().enumerate() {
+            let (label, style) = match &texture.source {
+                TextureSource::Imported(_) => (format!("{}\\nimported", texture.name), "solid"),
+                TextureSource::Transient(desc) => {
+                    let (width, height) = desc.size(surface_size);
+                    let samples = if desc.sample_count > 1 { format!(" ×{}", desc.sample_count) } else { String::new() };
+                    (format!("{}\\n{width}×{height} {:?}{samples}", texture.name, desc.format), "dashed")
+                }
+            };
+            let _ = writeln!(dot, "    t{index} [shape=ellipse, style={style}, label=\"{label}\"];");
+        }
+        for (index, pass) in self.passes.iter().enumerate() {
+            let (label, style) = match order.iter().position(|&p| p == index) {
+                Some(step) => (format!("{}. {}", step + 1, pass.name), "\"rounded,filled\", fillcolor=\"#dde8f5\""),
+                None => (format!("{} (culled)", pass.name), "dashed"),
+            };
+            let _ = writeln!(dot, "    p{index} [shape=box, style={style}, label=\"{label}\"];");
+            for read in &pass.reads {
+                let _ = writeln!(dot, "    t{} -> p{index};", read.0);
+            }
+            for write in &pass.writes {
+                let _ = writeln!(dot, "    p{index} -> t{};", write.0);
+            }
+        }
+        dot.push_str("}\n");
+        Ok(dot)
+    }
+
+    /// Live passes in execution order, or an error naming the passes that
+    /// wait on each other.
+    fn order(&self) -> Result<Vec<usize>> {
+        let live = self.live_passes();
+
+        // a pass depends on the earlier writers of what it writes, and on
+        // every writer of what it reads
+        let depends_on = |pass: usize, other: usize| {
+            let (this, that) = (&self.passes[pass], &self.passes[other]);
+            that.writes.iter().any(|t| this.reads.contains(t) || (other < pass && this.writes.contains(t)))
+        };
+
+        // Kahn's algorithm, taking the earliest added pass that is ready
+        let mut order = Vec::new();
+        let mut pending: Vec<usize> = (0..self.passes.len()).filter(|&p| live[p]).collect();
+        while !pending.is_empty() {
+            let Some(next) = pending
+                .iter()
+                .position(|&p| !pending.iter().any(|&other| other != p && depends_on(p, other)))
+            else {
+                let names: Vec<_> = pending.iter().map(|&p| self.passes[p].name).collect();
+                bail!("render graph has a cycle between {}", names.join(", "));
+            };
+            order.push(pending.remove(next));
+        }
+        Ok(order)
+    }
+
+    /// Passes whose output reaches an imported texture or a side effect.
+    fn live_passes(&self) -> Vec<bool> {
+        let mut live: Vec<bool> = self
+            .passes
+            .iter()
+            .map(|pass| {
+                pass.side_effect
+                    || pass.writes.iter().any(|t| matches!(self.textures[t.0].source, TextureSource::Imported(_)))
+            })
+            .collect();
+        loop {
+            let mut changed = false;
+            for (index, pass) in self.passes.iter().enumerate() {
+                if live[index] {
+                    continue;
+                }
+                let used = self
+                    .passes
+                    .iter()
+                    .zip(&live)
+                    .any(|(other, &other_live)| other_live && pass.writes.iter().any(|t| other.reads.contains(t)));
+                if used {
+                    live[index] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return live;
+            }
+        }
+    }
+
+    fn schedule(&self, pool: &mut TransientPool, surface_size: (u32, u32)) -> Result<Schedule> {
+        let order = self.order()?;
+
+        // last step each texture is used in
+        let mut last_use = vec![None; self.textures.len()];
+        for (step, &pass) in order.iter().enumerate() {
+            let pass = &self.passes[pass];
+            for texture in pass.reads.iter().chain(&pass.writes) {
+                last_use[texture.0] = Some(step);
+            }
+        }
+
+        pool.begin_frame();
+        let mut slots = vec![None; self.textures.len()];
+        for (step, &pass) in order.iter().enumerate() {
+            let pass = &self.passes[pass];
+            for texture in pass.reads.iter().chain(&pass.writes) {
+                if let (TextureSource::Transient(desc), None) = (&self.textures[texture.0].source, slots[texture.0]) {
+                    slots[texture.0] = Some(pool.acquire(desc, surface_size));
+                }
+            }
+            // textures done with here can be handed to later passes
+            for (texture, last) in last_use.iter().enumerate() {
+                if let (Some(slot), Some(last)) = (slots[texture], last)
+                    && *last == step
+                {
+                    pool.release(slot);
+                }
+            }
+        }
+        Ok(Schedule { order, slots })
+    }
+}
+
+/// Declares what a pass reads and writes; `execute` adds its commands.
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    index: usize,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    pub fn read(self, texture: TextureHandle) -> Self {
+        self.graph.passes[self.index].reads.push(texture);
+        self
+    }
+
+    pub fn write(self, texture: TextureHandle) -> Self {
+        self.graph.passes[self.index].writes.push(texture);
+        self
+    }
+
+    /// Never culled, e.g. a readback nothing else in the graph consumes.
+    pub fn side_effect(self) -> Self {
+        self.graph.passes[self.index].side_effect = true;
+        self
+    }
+
+    pub fn execute(self, run: impl FnOnce(&mut wgpu::CommandEncoder, &PassResources) + 'a) {
+        self.graph.passes[self.index].run = Some(Box::new(run));
+    }
+}
+
+/// Views of the textures a pass declared.
+pub struct PassResources<'r> {
+    pass: &'static str,
+    views: &'r [Option<&'r wgpu::TextureView>],
+    reads: &'r [TextureHandle],
+    writes: &'r [TextureHandle],
+}
+
+impl PassResources<'_> {
+    pub fn view(&self, texture: TextureHandle) -> &wgpu::TextureView {
+        assert!(
+            self.reads.contains(&texture) || self.writes.contains(&texture),
+            "pass {} uses a texture it did not declare",
+            self.pass,
+        );
+        self.views[texture.0].expect("declared textures are allocated")
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct PoolKey {
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    usage: wgpu::TextureUsages,
+}
+
+struct PoolSlot {
+    key: PoolKey,
+    label: &'static str,
+    view: Option<wgpu::TextureView>, // None until allocated
+    in_use: bool,                    // by a pass scheduled so far this frame
+    used_this_frame: bool,
+}
+
+/// Transient textures kept across frames. Slots not used by a frame are
+/// dropped at its end, e.g. after a resize.
+#[derive(Default)]
+pub struct TransientPool {
+    slots: Vec<PoolSlot>,
+}
+
+impl TransientPool {
+    fn begin_frame(&mut self) {
+        for slot in &mut self.slots {
+            slot.in_use = false;
+            slot.used_this_frame = false;
+        }
+    }
+
+    fn acquire(&mut self, desc: &TextureDesc, surface_size: (u32, u32)) -> usize {
+        let key = PoolKey {
+            size: desc.size(surface_size),
+            format: desc.format,
+            sample_count: desc.sample_count,
+            usage: desc.usage,
+        };
+        let index = match self.slots.iter().position(|slot| slot.key == key && !slot.in_use) {
+            Some(index) => index,
+            None => {
+                self.slots.push(PoolSlot { key, label: desc.label, view: None, in_use: false, used_this_frame: false });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        (slot.in_use, slot.used_this_frame) = (true, true);
+        index
+    }
+
+    fn release(&mut self, index: usize) {
+        self.slots[index].in_use = false;
+    }
+
+    fn allocate(&mut self, device: &wgpu::Device) {
+        for slot in self.slots.iter_mut().filter(|slot| slot.view.is_none()) {
+            let (width, height) = slot.key.size;
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(slot.label),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: slot.key.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: slot.key.format,
+                usage: slot.key.usage,
+                view_formats: &[],
+            });
+            slot.view = Some(texture.create_view(&Default::default()));
+        }
+    }
+
+    fn view(&self, index: usize) -> Option<&wgpu::TextureView> {
+        self.slots[index].view.as_ref()
+    }
+
+    fn release_unused(&mut self) {
+        // indices are only held during a frame, so slots can move
+        self.slots.retain(|slot| slot.used_this_frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (u32, u32) = (640, 480);
+
+    fn desc(label: &'static str) -> TextureDesc {
+        TextureDesc {
+            label,
+            scale: 1.0,
+            format: wgpu::TextureFormat::Rgba16Float,
+            sample_count: 1,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+
+    fn names(graph: &RenderGraph, order: &[usize]) -> Vec<&'static str> {
+        order.iter().map(|&p| graph.passes[p].name).collect()
+    }
+
+    #[test]
+    fn runs_writers_before_readers() {
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture(desc("Scene"));
+        let bloom = graph.create_texture(desc("Bloom"));
+        graph.add_pass("Present").read(scene).read(bloom).side_effect();
+        graph.add_pass("Bloom").read(scene).write(bloom);
+        graph.add_pass("Scene").write(scene);
+
+        let order = graph.order().unwrap();
+        assert_eq!(names(&graph, &order), ["Scene", "Bloom", "Present"]);
+    }
+
+    #[test]
+    fn keeps_writers_of_one_texture_in_the_order_added() {
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture(desc("Scene"));
+        graph.add_pass("Present").read(scene).side_effect();
+        graph.add_pass("Planet").write(scene);
+        graph.add_pass("Clouds").write(scene);
+        graph.add_pass("Overlays").write(scene);
+
+        let order = graph.order().unwrap();
+        assert_eq!(names(&graph, &order), ["Planet", "Clouds", "Overlays", "Present"]);
+    }
+
+    #[test]
+    fn culls_passes_whose_output_is_unused() {
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture(desc("Scene"));
+        let unused = graph.create_texture(desc("Unused"));
+        let feeds_unused = graph.create_texture(desc("Feeds Unused"));
+        graph.add_pass("Scene").write(scene);
+        graph.add_pass("Dead Source").write(feeds_unused);
+        graph.add_pass("Dead").read(scene).read(feeds_unused).write(unused);
+        graph.add_pass("Present").read(scene).side_effect();
+
+        let order = graph.order().unwrap();
+        assert_eq!(names(&graph, &order), ["Scene", "Present"]);
+        assert!(graph.to_dot(SIZE).unwrap().contains("Dead (culled)"));
+    }
+
+    #[test]
+    fn reports_cycles_by_pass_name() {
+        let mut graph = RenderGraph::new();
+        let a = graph.create_texture(desc("A"));
+        let b = graph.create_texture(desc("B"));
+        graph.add_pass("Ping").read(a).write(b);
+        graph.add_pass("Pong").read(b).write(a);
+        graph.add_pass("Present").read(b).side_effect();
+
+        let err = graph.order().unwrap_err().to_string();
+        assert!(err.contains("Ping") && err.contains("Pong"), "{err}");
+        assert!(graph.schedule(&mut TransientPool::default(), SIZE).is_err());
+        assert!(graph.to_dot(SIZE).is_err());
+    }
+
+    #[test]
+    fn reuses_transients_once_their_last_reader_ran() {
+        let mut graph = RenderGraph::new();
+        let first = graph.create_texture(desc("First"));
+        let second = graph.create_texture(desc("Second"));
+        let third = graph.create_texture(desc("Third"));
+        let half = graph.create_texture(TextureDesc { scale: 0.5, ..desc("Half") });
+        graph.add_pass("One").write(first);
+        graph.add_pass("Two").read(first).write(second);
+        graph.add_pass("Three").read(second).write(third).write(half);
+        graph.add_pass("Present").read(third).read(half).side_effect();
+
+        let mut pool = TransientPool::default();
+        let schedule = graph.schedule(&mut pool, SIZE).unwrap();
+        let slot = |texture: TextureHandle| schedule.slots[texture.0].unwrap();
+        // `first` is free again once "Two" ran; `second` is still being read
+        assert_eq!(slot(third), slot(first));
+        assert_ne!(slot(second), slot(first));
+        // a different size never shares
+        assert!(![slot(first), slot(second)].contains(&slot(half)));
+        assert_eq!(pool.slots.len(), 3);
+
+        // the same graph gets the same textures next frame
+        let again = graph.schedule(&mut pool, SIZE).unwrap();
+        assert_eq!(again.slots, schedule.slots);
+        assert_eq!(pool.slots.len(), 3);
+
+        // slots a frame didn't touch are dropped at its end
+        let mut small = RenderGraph::new();
+        let only = small.create_texture(desc("Only"));
+        small.add_pass("Present").write(only).side_effect();
+        small.schedule(&mut pool, SIZE).unwrap();
+        pool.release_unused();
+        assert_eq!(pool.slots.len(), 1);
+    }
+}
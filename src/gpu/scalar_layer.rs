@@ -0,0 +1,382 @@
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec2;
+use wgpu::util::DeviceExt;
+
+use crate::gpu::planet::ChunkInstance;
+use crate::gpu::{
+    create_texture, create_texture_r32f, load_shader, DepthMode, FragmentShader, OverlayText, Planet, SceneLayouts, SceneTarget,
+    VertexShader,
+};
+use crate::scalar::{Colormap, ScalarConfig, ScalarGrid};
+use crate::vertex;
+
+const VERTEX_SHADER_PATH: &str = "src/shaders/cube.vert.wgsl";
+const SHADER_PATH: &str = "src/shaders/scalar.wgsl";
+const LUT_SIZE: u32 = 256;
+const LEGEND_STOPS: usize = 32;
+const LEGEND_MARGIN: f32 = 16.0; // pixels from the bottom-left corner
+const LEGEND_BAR: Vec2 = Vec2::new(256.0, 12.0);
+const LEGEND_BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.8];
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ScalarUniforms {
+    min: f32,     // 4 bytes @ offset 0, maps to the low end of the colormap
+    max: f32,     // 4 bytes @ offset 4
+    opacity: f32, // 4 bytes @ offset 8
+    _pad: f32,    // 4 bytes @ offset 12
+} // total size = 16 bytes
+
+/// The loaded grid and what it was loaded from.
+struct ScalarData {
+    config: ScalarConfig,
+    data_range: (f32, f32),
+    bind_group: wgpu::BindGroup,
+}
+
+/// A latitude/longitude grid of values (temperature, elevation, …) colored
+/// through a colormap and drawn over the planet, with a legend.
+pub struct ScalarLayer {
+    pub enabled: bool,
+    pub colormap: Colormap,
+    pub range: (f32, f32), // values mapped to the ends of the colormap
+    pub opacity: f32,
+    config_path: PathBuf,
+    data: Option<ScalarData>,
+
+    uniform_buffer: wgpu::Buffer,
+    lut_texture: wgpu::Texture,
+    lut_colormap: Colormap, // what `lut_texture` currently holds
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ScalarLayer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+        config_path: impl Into<PathBuf>,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Scalar UBO"),
+            contents: bytemuck::bytes_of(&ScalarUniforms::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let colormap = Colormap::default();
+        let lut_texture = create_texture(
+            device,
+            queue,
+            "Colormap LUT",
+            (LUT_SIZE, 1),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            bytemuck::cast_slice(&colormap.lut(LUT_SIZE as usize)),
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Scalar Bind Group Layout"),
+            entries: &[
+                // binding 0 = Scalar UBO (range, opacity)
+                wgpu::BindGroupLayoutEntry {
+                    binding:    0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty:                wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size:  NonZeroU64::new(size_of::<ScalarUniforms>() as u64),
+                    },
+                    count: None,
+                },
+                // binding 1 = data grid, filtered in the shader
+                wgpu::BindGroupLayoutEntry {
+                    binding:    1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type:    wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count: None,
+                },
+                // binding 2 = colormap LUT
+                wgpu::BindGroupLayoutEntry {
+                    binding:    2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type:    wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled:   false,
+                    },
+                    count: None,
+                },
+                // binding 3 = LUT sampler
+                wgpu::BindGroupLayoutEntry {
+                    binding:    3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline = create_pipeline(device, target, layouts, &bind_group_layout, depth_mode);
+
+        Self {
+            enabled: true,
+            colormap,
+            range: (0.0, 1.0),
+            opacity: 0.8,
+            config_path: config_path.into(),
+            data: None,
+
+            uniform_buffer,
+            lut_texture,
+            lut_colormap: colormap,
+            sampler,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    pub fn reload_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) {
+        self.pipeline = create_pipeline(device, target, layouts, &self.bind_group_layout, depth_mode);
+    }
+
+    /// Re-reads the config and its grid, resetting colormap, range and
+    /// opacity to the configured ones. On failure the previous grid stays.
+    pub fn reload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let config = match ScalarConfig::load(&self.config_path) {
+            Ok(Some(config)) => config,
+            Ok(None) => {
+                self.data = None;
+                return;
+            }
+            Err(err) => {
+                log::warn!("scalar layer not reloaded: {err:#}");
+                return;
+            }
+        };
+        let grid = match ScalarGrid::load(&config.path) {
+            Ok(grid) => grid,
+            Err(err) => {
+                log::warn!("scalar layer not reloaded: {err:#}");
+                return;
+            }
+        };
+        let Some(data_range) = grid.range() else {
+            log::warn!("scalar layer not reloaded: {} has no values", config.path.display());
+            return;
+        };
+
+        let texture = create_texture_r32f(device, queue, "Scalar Grid", (grid.width, grid.height), &grid.values);
+        let texture_view = texture.create_view(&Default::default());
+        let lut_view = self.lut_texture.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Scalar Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&lut_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        self.colormap = config.colormap;
+        self.range = config.range.map_or(data_range, |[min, max]| (min, max));
+        self.opacity = config.opacity;
+        self.data = Some(ScalarData { config, data_range, bind_group });
+    }
+
+    /// The config file and the grid it names, for hot reloading.
+    pub fn watched_paths(&self) -> Vec<PathBuf> {
+        std::iter::once(self.config_path.clone())
+            .chain(self.data.as_ref().map(|d| d.config.path.clone()))
+            .collect()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.data.is_some()
+    }
+
+    /// Scales the range about its middle; below 1 narrows it for more
+    /// contrast.
+    pub fn scale_range(&mut self, factor: f32) {
+        let (min, max) = self.range;
+        let (mid, half) = ((min + max) * 0.5, (max - min) * 0.5 * factor);
+        // never collapse to nothing
+        let floor = self.data.as_ref().map_or(0.0, |d| (d.data_range.1 - d.data_range.0) * 1e-3);
+        let half = half.max(floor).max(f32::MIN_POSITIVE);
+        self.range = (mid - half, mid + half);
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        if self.colormap != self.lut_colormap {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.lut_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&self.colormap.lut(LUT_SIZE as usize)),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(LUT_SIZE * 4),
+                    rows_per_image: Some(1),
+                },
+                self.lut_texture.size(),
+            );
+            self.lut_colormap = self.colormap;
+        }
+        let uniforms = ScalarUniforms {
+            min: self.range.0,
+            max: self.range.1,
+            opacity: self.opacity,
+            _pad: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Lays out a color bar for the current colormap and range, with a
+    /// title and tick labels, in the bottom-left corner while the layer shows.
+    pub fn layout_legend(&self, overlay: &mut OverlayText) {
+        let Some(data) = self.data.as_ref().filter(|_| self.enabled) else {
+            return;
+        };
+        let config = &data.config;
+        let title = match (config.name.is_empty(), config.units.is_empty()) {
+            (false, false) => format!("{} ({})", config.name, config.units),
+            (false, true) => config.name.clone(),
+            (true, false) => config.units.clone(),
+            (true, true) => String::new(),
+        };
+        let colors: Vec<[f32; 4]> = self
+            .colormap
+            .lut(LEGEND_STOPS)
+            .into_iter()
+            .map(|[r, g, b, _]| [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), 1.0])
+            .collect();
+        let line_height = overlay.line_height();
+        let solid = overlay.atlas().solid();
+
+        // from the bottom up: tick labels, bar, title
+        let ticks_top = overlay.viewport().y - LEGEND_MARGIN - line_height;
+        let bar_min = Vec2::new(LEGEND_MARGIN, ticks_top - 2.0 - LEGEND_BAR.y);
+        overlay.solid(bar_min - 1.0, LEGEND_BAR + 2.0, LEGEND_BACKDROP);
+        let step = LEGEND_BAR.x / (colors.len() - 1) as f32;
+        for (i, pair) in colors.windows(2).enumerate() {
+            let min = bar_min + Vec2::new(step * i as f32, 0.0);
+            overlay.gradient(min, Vec2::new(step, LEGEND_BAR.y), &solid, [pair[0], pair[1]]);
+        }
+
+        let white = [1.0; 4];
+        overlay.text(Vec2::new(bar_min.x, bar_min.y - 4.0 - line_height), &title, white);
+        let (min, max) = self.range;
+        for t in [0.0, 0.5, 1.0] {
+            let label = format_value(min + (max - min) * t, max - min);
+            let (_, width) = overlay.atlas().layout(&label);
+            let x = (bar_min.x + LEGEND_BAR.x * t - width * 0.5).round().max(LEGEND_MARGIN * 0.5);
+            overlay.text(Vec2::new(x, ticks_top), &label, white);
+        }
+    }
+
+    /// Redraws the planet's visible chunks with the data shader. Needs the
+    /// frame bind group and the planet node's groups (see
+    /// `SceneRenderer::bind_node`) bound and the planet already in the
+    /// depth buffer.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass, planet: &Planet) {
+        let Some(data) = self.data.as_ref().filter(|_| self.enabled) else {
+            return;
+        };
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(3, &data.bind_group, &[]);
+        planet.draw(rpass);
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Enough decimals to tell ticks `span` apart.
+fn format_value(value: f32, span: f32) -> String {
+    let decimals = (2.0 - span.abs().max(1e-6).log10().floor()).clamp(0.0, 6.0) as usize;
+    format!("{value:.decimals$}")
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    target: SceneTarget,
+    layouts: &SceneLayouts,
+    scalar_layout: &wgpu::BindGroupLayout,
+    depth_mode: DepthMode,
+) -> wgpu::RenderPipeline {
+    // the planet's own vertex stage, so depths match what it wrote
+    let vs_module = VertexShader(load_shader("Scalar VS", VERTEX_SHADER_PATH, device));
+    let fs_module = FragmentShader(load_shader("Scalar FS", SHADER_PATH, device));
+
+    let layout = layouts.pipeline_layout(device, "Scalar Pipeline Layout", Some(scalar_layout));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Scalar Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &vs_module,
+            entry_point: Some("vs_main"),
+            buffers: &[vertex::Vertex::desc(), ChunkInstance::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: Default::default(),
+            module: &fs_module,
+            entry_point: Some(&depth_mode.fragment_entry("fs_main")),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: Some(depth_mode.overlay_depth_stencil_state()),
+        multisample: target.multisample_state(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,468 @@
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroU64;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{DVec4, Mat3, Mat4};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::gpu::material::{Material, MaterialDesc, MaterialFactors, Permutation};
+use crate::gpu::{load_shader, DepthMode, Planet, SceneTarget};
+use crate::gpu::picking::Pickable;
+use crate::scene::{NodeId, SceneGraph};
+use crate::vertex::Vertex;
+
+const SHADER_PATH: &str = "src/shaders/scene.wgsl";
+const INITIAL_CAPACITY: usize = 64;
+
+/// A mesh registered with `SceneRenderer::add_mesh` or `add_external_mesh`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MeshId(usize);
+
+/// A material registered with `SceneRenderer::add_material`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(usize);
+
+/// The bind group layouts every pipeline of the scene pass shares, by how
+/// often their groups change: per frame (group 0), per material (group 1)
+/// and per object (group 2, a dynamic offset into one uniform buffer).
+/// Per-feature data, such as the clouds or an instance set, goes in group 3.
+#[derive(Clone)]
+pub struct SceneLayouts {
+    pub frame: wgpu::BindGroupLayout,
+    pub material: wgpu::BindGroupLayout,
+    pub object: wgpu::BindGroupLayout,
+}
+
+impl SceneLayouts {
+    /// The layout of a scene pass pipeline, with `feature` at group 3.
+    pub fn pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        feature: Option<&wgpu::BindGroupLayout>,
+    ) -> wgpu::PipelineLayout {
+        let mut bind_group_layouts = vec![&self.frame, &self.material, &self.object];
+        bind_group_layouts.extend(feature);
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[],
+        })
+    }
+}
+
+/// Bind group 2 of the scene pass: one `ObjectUniforms` slot per object.
+pub fn create_object_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Object Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding:    0,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty:                 wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true, // one slot per object
+                min_binding_size:   NonZeroU64::new(size_of::<ObjectUniforms>() as u64),
+            },
+            count: None,
+        }],
+    })
+}
+
+/// Geometry its owner keeps and draws, such as the planet's chunks, placed
+/// by a node like any other mesh. Its material needs a custom pipeline
+/// (see `SceneRenderer::set_custom_pipeline`) matching its vertex layout.
+pub trait ExternalMesh {
+    fn draw(&self, rpass: &mut wgpu::RenderPass);
+}
+
+impl ExternalMesh for Planet {
+    fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        Planet::draw(self, rpass);
+    }
+}
+
+/// The pipeline a draw runs: the scene shader's permutation for its
+/// material, or the custom pipeline set for that material.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum PipelineKey {
+    Permutation(Permutation),
+    Custom(MaterialId),
+}
+
+/// One node's draw; sorting these orders the draws by pipeline, then
+/// material, then mesh, so each changes as rarely as possible.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Draw {
+    pipeline: PipelineKey,
+    material: MaterialId,
+    mesh: MeshId,
+    slot: u32, // in `object_buffer`
+}
+
+struct MeshBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+}
+
+impl Pickable for MeshBuffers {
+    fn draw_pick(&self, rpass: &mut wgpu::RenderPass) {
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}
+
+enum Mesh {
+    Buffers(MeshBuffers),
+    External(usize), // index into the meshes passed to `draw`
+}
+
+/// A pipeline its owner built for one material, with that material's
+/// feature bind group (group 3), if it has one.
+struct CustomPipeline {
+    pipeline: wgpu::RenderPipeline,
+    feature: Option<wgpu::BindGroup>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct ObjectUniforms {
+    model: [[f32; 4]; 4],  // 64 bytes @ offset 0, camera-relative
+    normal: [[f32; 4]; 3], // 48 bytes @ offset 64, mat3x3 columns padded to vec4
+} // total size = 112 bytes
+
+impl ObjectUniforms {
+    fn new(model: Mat4) -> Self {
+        let normal = Mat3::from_mat4(model).inverse().transpose();
+        Self {
+            model: model.to_cols_array_2d(),
+            normal: [normal.x_axis, normal.y_axis, normal.z_axis].map(|column| column.extend(0.0).to_array()),
+        }
+    }
+}
+
+/// Slot of the identity transform, for features placed in camera-relative
+/// world space by their own data; the nodes' slots follow it.
+const WORLD_SLOT: u32 = 0;
+
+/// Draws the meshes of a `SceneGraph`, one draw per node.
+///
+/// Bind groups follow how often they change (see `SceneLayouts`): the
+/// frame's is bound by the caller, each material's once per run of draws
+/// sharing it, and each node's model matrix per draw, as a dynamic offset
+/// into one uniform buffer. World matrices are rebased on the camera in f64
+/// before they are narrowed to f32.
+pub struct SceneRenderer {
+    meshes: Vec<Mesh>,
+    num_external: usize,
+    materials: Vec<Material>,
+    default_material: MaterialId, // for nodes without one
+    draws: Vec<Draw>,             // sorted
+    nodes: Vec<(NodeId, Mat4)>,   // by slot after `WORLD_SLOT`, with the camera-relative model matrix
+
+    object_buffer: wgpu::Buffer,
+    stride: u64,
+    capacity: usize,
+    object_bind_group: wgpu::BindGroup,
+    pipelines: BTreeMap<Permutation, wgpu::RenderPipeline>, // one per permutation in use
+    custom: HashMap<MaterialId, CustomPipeline>,
+    target: SceneTarget,
+    layouts: SceneLayouts,
+    depth_mode: DepthMode,
+}
+
+impl SceneRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = (size_of::<ObjectUniforms>() as u64).next_multiple_of(alignment);
+        let object_buffer = create_object_buffer(device, stride, INITIAL_CAPACITY);
+        let object_bind_group = create_bind_group(device, &layouts.object, &object_buffer);
+
+        let mut renderer = Self {
+            meshes: Vec::new(),
+            num_external: 0,
+            materials: Vec::new(),
+            default_material: MaterialId(0),
+            draws: Vec::new(),
+            nodes: Vec::new(),
+            object_buffer,
+            stride,
+            capacity: INITIAL_CAPACITY,
+            object_bind_group,
+            pipelines: BTreeMap::new(),
+            custom: HashMap::new(),
+            target,
+            layouts: layouts.clone(),
+            depth_mode,
+        };
+        let factors = MaterialFactors::default().bytes();
+        renderer.default_material = renderer.add_material(
+            device,
+            Material::new(device, queue, &layouts.material, MaterialDesc {
+                name: "default",
+                factors: &factors,
+                base_color_map: None,
+                sampler: None,
+                unlit: false,
+            }),
+        );
+        renderer
+    }
+
+    pub fn add_mesh(&mut self, device: &wgpu::Device, vertices: &[Vertex], indices: &[u16]) -> MeshId {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Scene Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Scene Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        self.meshes.push(Mesh::Buffers(MeshBuffers { vertex_buffer, index_buffer, num_indices: indices.len() as u32 }));
+        MeshId(self.meshes.len() - 1)
+    }
+
+    /// Registers an `ExternalMesh`; `draw` takes the external meshes in
+    /// the order they were added.
+    pub fn add_external_mesh(&mut self) -> MeshId {
+        self.meshes.push(Mesh::External(self.num_external));
+        self.num_external += 1;
+        MeshId(self.meshes.len() - 1)
+    }
+
+    /// Registers a material, building the pipeline for its permutation if
+    /// no earlier material needed it.
+    pub fn add_material(&mut self, device: &wgpu::Device, material: Material) -> MaterialId {
+        let permutation = material.permutation;
+        if !self.pipelines.contains_key(&permutation) {
+            let pipeline = self.create_pipeline(device, permutation);
+            self.pipelines.insert(permutation, pipeline);
+        }
+        self.materials.push(material);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    /// For updating its factors.
+    pub fn material_mut(&mut self, id: MaterialId) -> Option<&mut Material> {
+        self.materials.get_mut(id.0)
+    }
+
+    /// Draws `material` with `pipeline` instead of a scene shader
+    /// permutation, binding `feature` at group 3. The pipeline's layout
+    /// must start with the `SceneLayouts`; it is not rebuilt by
+    /// `reload_pipeline`, so its owner sets it again after a reload.
+    pub fn set_custom_pipeline(
+        &mut self,
+        material: MaterialId,
+        pipeline: wgpu::RenderPipeline,
+        feature: Option<wgpu::BindGroup>,
+    ) {
+        self.custom.insert(material, CustomPipeline { pipeline, feature });
+    }
+
+    pub fn reload_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) {
+        (self.target, self.layouts, self.depth_mode) = (target, layouts.clone(), depth_mode);
+        let permutations: Vec<_> = self.pipelines.keys().copied().collect();
+        for permutation in permutations {
+            let pipeline = self.create_pipeline(device, permutation);
+            self.pipelines.insert(permutation, pipeline);
+        }
+    }
+
+    fn create_pipeline(&self, device: &wgpu::Device, permutation: Permutation) -> wgpu::RenderPipeline {
+        create_pipeline(device, self.target, &self.layouts, self.depth_mode, permutation)
+    }
+
+    /// Uploads the model matrix of every visible node with a mesh and sorts
+    /// their draws. Call after `SceneGraph::update`.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, scene: &SceneGraph, camera: &Camera) {
+        let mut data = Vec::new();
+        let mut push = |model: Mat4| {
+            data.extend_from_slice(bytemuck::bytes_of(&ObjectUniforms::new(model)));
+            data.resize(data.len().next_multiple_of(self.stride as usize), 0);
+        };
+        push(Mat4::IDENTITY); // WORLD_SLOT
+        self.draws.clear();
+        self.nodes.clear();
+        for (node, world, mesh, material) in scene.drawables() {
+            let material = material.filter(|id| id.0 < self.materials.len()).unwrap_or(self.default_material);
+            let mut model = world;
+            model.w_axis -= DVec4::from((camera.eye, 0.0));
+            let model = model.as_mat4();
+            push(model);
+            self.nodes.push((node, model));
+            let pipeline = if self.custom.contains_key(&material) {
+                PipelineKey::Custom(material)
+            } else {
+                PipelineKey::Permutation(self.materials[material.0].permutation)
+            };
+            self.draws.push(Draw { pipeline, material, mesh, slot: self.nodes.len() as u32 });
+        }
+        self.draws.sort_unstable();
+
+        let slots = self.nodes.len() + 1;
+        if slots > self.capacity {
+            self.capacity = slots.next_power_of_two();
+            self.object_buffer = create_object_buffer(device, self.stride, self.capacity);
+            self.object_bind_group = create_bind_group(device, &self.layouts.object, &self.object_buffer);
+        }
+        queue.write_buffer(&self.object_buffer, 0, &data);
+    }
+
+    /// The nodes drawn this frame, for the pick pass. External meshes are
+    /// left to their owners.
+    pub fn pick_objects(&self) -> impl Iterator<Item = (NodeId, Mat4, &dyn Pickable)> {
+        self.draws.iter().filter_map(|draw| {
+            let Mesh::Buffers(mesh) = &self.meshes[draw.mesh.0] else {
+                return None;
+            };
+            let (node, model) = self.nodes[(draw.slot - 1) as usize];
+            Some((node, model, mesh as &dyn Pickable))
+        })
+    }
+
+    /// Draws the nodes with the frame bind group (group 0) that is currently
+    /// bound, setting pipelines, materials and meshes only when they change.
+    /// `external` holds the meshes added with `add_external_mesh`.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass, external: &[&dyn ExternalMesh]) {
+        let mut last: Option<&Draw> = None;
+        for draw in &self.draws {
+            if last.is_none_or(|last| last.pipeline != draw.pipeline) {
+                match draw.pipeline {
+                    PipelineKey::Permutation(permutation) => rpass.set_pipeline(&self.pipelines[&permutation]),
+                    PipelineKey::Custom(material) => {
+                        let custom = &self.custom[&material];
+                        rpass.set_pipeline(&custom.pipeline);
+                        if let Some(feature) = &custom.feature {
+                            rpass.set_bind_group(3, feature, &[]);
+                        }
+                    }
+                }
+            }
+            if last.is_none_or(|last| last.material != draw.material) {
+                rpass.set_bind_group(1, self.materials[draw.material.0].bind_group(), &[]);
+            }
+            self.bind_slot(rpass, draw.slot);
+            match &self.meshes[draw.mesh.0] {
+                Mesh::Buffers(mesh) => {
+                    if last.is_none_or(|last| last.mesh != draw.mesh) {
+                        rpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                        rpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                    }
+                    rpass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+                }
+                Mesh::External(index) => match external.get(*index) {
+                    Some(mesh) => mesh.draw(rpass),
+                    None => log::warn!("external mesh {index} not passed to the scene renderer"),
+                },
+            }
+            last = Some(draw);
+        }
+    }
+
+    /// Binds the default material (group 1) and the identity transform
+    /// (group 2), for features placed in camera-relative world space.
+    pub fn bind_world(&self, rpass: &mut wgpu::RenderPass) {
+        rpass.set_bind_group(1, self.materials[self.default_material.0].bind_group(), &[]);
+        self.bind_slot(rpass, WORLD_SLOT);
+    }
+
+    /// Binds `node`'s material (group 1) and model matrix (group 2), for
+    /// features drawn in its object space, such as the layers on the
+    /// planet. False if the node wasn't drawn this frame.
+    pub fn bind_node(&self, rpass: &mut wgpu::RenderPass, node: NodeId) -> bool {
+        let Some(draw) = self.draws.iter().find(|draw| self.nodes[(draw.slot - 1) as usize].0 == node) else {
+            return false;
+        };
+        rpass.set_bind_group(1, self.materials[draw.material.0].bind_group(), &[]);
+        self.bind_slot(rpass, draw.slot);
+        true
+    }
+
+    fn bind_slot(&self, rpass: &mut wgpu::RenderPass, slot: u32) {
+        rpass.set_bind_group(2, &self.object_bind_group, &[(slot as u64 * self.stride) as u32]);
+    }
+}
+
+fn create_object_buffer(device: &wgpu::Device, stride: u64, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Scene Object UBO"),
+        size: stride * capacity as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Scene Object Bind Group"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer,
+                offset: 0,
+                size: NonZeroU64::new(size_of::<ObjectUniforms>() as u64),
+            }),
+        }],
+    })
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    target: SceneTarget,
+    layouts: &SceneLayouts,
+    depth_mode: DepthMode,
+    permutation: Permutation,
+) -> wgpu::RenderPipeline {
+    let shader = load_shader("Scene", SHADER_PATH, device);
+    let constants = permutation.constants();
+    let layout = layouts.pipeline_layout(device, "Scene Pipeline Layout", None);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Scene Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+            module: &shader,
+            entry_point: Some(&depth_mode.fragment_entry("fs_main")),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target.format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(depth_mode.depth_stencil_state(true)),
+        multisample: target.multisample_state(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// A frame copied into a mappable buffer, waiting to be written to disk.
+///
+/// Record the copy with `capture` before the encoder is submitted, then call
+/// `save` once it has been.
+pub struct Screenshot {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl Screenshot {
+    /// `texture` needs `COPY_SRC` usage and a 4-byte RGBA/BGRA format.
+    pub fn capture(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) -> Self {
+        let (width, height) = (texture.width(), texture.height());
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (width * 4).div_ceil(align) * align;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        Self { buffer, width, height, padded_bytes_per_row, format: texture.format() }
+    }
+
+    /// Waits for the copy and writes the image as PNG.
+    pub fn save(self, device: &wgpu::Device, path: &Path) -> Result<()> {
+        let swap_red_blue = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            other => bail!("cannot save a {other:?} surface"),
+        };
+
+        let slice = self.buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::Wait).context("waiting for the screenshot copy")?;
+
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..(self.width * 4) as usize]);
+            }
+        }
+        self.buffer.unmap();
+
+        if swap_red_blue {
+            pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+        }
+        let image = image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .context("screenshot size mismatch")?;
+        image.save(path).with_context(|| format!("writing {}", path.display()))
+    }
+}
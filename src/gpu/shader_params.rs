@@ -0,0 +1,536 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use wgpu::naga;
+
+/// Name of the uniform a shader declares its tunables in.
+const PARAMS_VAR: &str = "params";
+
+/// A tunable's WGSL type; only these can be edited.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParamType {
+    F32,
+    I32,
+    U32,
+    Vec(usize), // vecN<f32>
+}
+
+/// How the debug panel edits a parameter, from its annotations.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParamWidget {
+    Slider { min: f64, max: f64 }, // `@range(min, max)`
+    Drag,
+    Color,    // `@color`, on vec3/vec4
+    Checkbox, // `@toggle`, on u32/i32; nonzero is on
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64), // narrowed to f32 for the GPU; f64 keeps the saved file readable
+    Vector(Vec<f64>),
+}
+
+impl ParamValue {
+    fn zero(ty: ParamType) -> Self {
+        match ty {
+            ParamType::F32 => ParamValue::Float(0.0),
+            ParamType::I32 | ParamType::U32 => ParamValue::Int(0),
+            ParamType::Vec(n) => ParamValue::Vector(vec![0.0; n]),
+        }
+    }
+
+    /// `self` as a value of `ty`, if it reasonably is one (a hand-written
+    /// `1` for a float, `true` for a toggle, …).
+    fn to_type(&self, ty: ParamType) -> Option<Self> {
+        match (ty, self) {
+            (ParamType::F32, ParamValue::Float(v)) => Some(ParamValue::Float(*v)),
+            (ParamType::F32, ParamValue::Int(v)) => Some(ParamValue::Float(*v as f64)),
+            (ParamType::I32 | ParamType::U32, ParamValue::Int(v)) => Some(ParamValue::Int(*v)),
+            (ParamType::I32 | ParamType::U32, ParamValue::Bool(v)) => Some(ParamValue::Int(*v as i64)),
+            (ParamType::Vec(n), ParamValue::Vector(v)) if v.len() == n => Some(ParamValue::Vector(v.clone())),
+            _ => None,
+        }
+    }
+
+    fn from_numbers(ty: ParamType, numbers: &[f64]) -> Option<Self> {
+        match (ty, numbers) {
+            (ParamType::F32, [v]) => Some(ParamValue::Float(*v)),
+            (ParamType::I32 | ParamType::U32, [v]) => Some(ParamValue::Int(*v as i64)),
+            (ParamType::Vec(n), v) if v.len() == n => Some(ParamValue::Vector(v.to_vec())),
+            _ => None,
+        }
+    }
+}
+
+/// One field of the params struct.
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub name: String,
+    pub ty: ParamType,
+    pub widget: ParamWidget,
+    pub default: ParamValue,
+    pub value: ParamValue,
+    offset: usize, // in the uniform buffer
+}
+
+impl Param {
+    fn write(&self, bytes: &mut [u8]) {
+        let at = &mut bytes[self.offset..];
+        match (&self.value, self.ty) {
+            (ParamValue::Float(v), _) => at[..4].copy_from_slice(&(*v as f32).to_ne_bytes()),
+            (ParamValue::Int(v), ParamType::U32) => at[..4].copy_from_slice(&(*v as u32).to_ne_bytes()),
+            (ParamValue::Int(v), _) => at[..4].copy_from_slice(&(*v as i32).to_ne_bytes()),
+            (ParamValue::Vector(v), _) => {
+                for (i, v) in v.iter().enumerate() {
+                    at[4 * i..4 * i + 4].copy_from_slice(&(*v as f32).to_ne_bytes());
+                }
+            }
+            (ParamValue::Bool(_), _) => unreachable!("toggles are stored as integers"),
+        }
+    }
+}
+
+/// `shader_params.toml`: saved values, one table per shader.
+type ParamsFile = BTreeMap<String, BTreeMap<String, ParamValue>>;
+
+/// Tunables a shader declares as a `var<uniform> params` struct, editable
+/// from the debug panel. Each field can carry annotations in a trailing
+/// comment:
+///
+/// ```wgsl
+/// struct Params {
+///     ambient : f32,       // @range(0.0, 1.0) @default(0.1)
+///     tint    : vec3<f32>, // @color @default(1.0, 1.0, 1.0)
+///     shadows : u32,       // @toggle @default(1)
+/// };
+/// ```
+///
+/// The struct is reflected again on every shader reload; fields that keep
+/// their name and type keep their value. `bytes` packs the values for the
+/// uniform, which the owner uploads (e.g. as a material's factors).
+pub struct ShaderParams {
+    pub name: String, // table in the settings file
+    pub params: Vec<Param>,
+
+    shader_path: PathBuf,
+    settings_path: PathBuf,
+    size: usize,
+}
+
+impl ShaderParams {
+    /// Reflects the shader at `shader_path` and applies the values saved
+    /// under `name` in `settings_path`.
+    pub fn new(name: &str, shader_path: impl Into<PathBuf>, settings_path: impl Into<PathBuf>) -> Self {
+        let mut params = Self {
+            name: name.to_string(),
+            params: Vec::new(),
+            shader_path: shader_path.into(),
+            settings_path: settings_path.into(),
+            size: 16,
+        };
+        params.reflect();
+        if let Some(saved) = load_file(&params.settings_path).remove(&params.name) {
+            params.apply(&saved);
+        }
+        params
+    }
+
+    /// Re-reads the shader's params struct. On error the current
+    /// parameters stay, as the shader loader keeps the last good module.
+    pub fn reflect(&mut self) {
+        let (params, size) = match reflect(&self.shader_path) {
+            Ok(reflected) => reflected,
+            Err(err) => {
+                log::warn!("shader params of {} not reflected: {err:#}", self.shader_path.display());
+                return;
+            }
+        };
+        let old = std::mem::replace(&mut self.params, params);
+        for param in &mut self.params {
+            if let Some(value) = old
+                .iter()
+                .find(|old| old.name == param.name && old.ty == param.ty)
+                .map(|old| old.value.clone())
+            {
+                param.value = value;
+            }
+        }
+
+        self.size = size.max(16).next_multiple_of(16);
+    }
+
+    /// Sets the parameters named in `values`, ignoring unknown names and
+    /// values of the wrong type.
+    fn apply(&mut self, values: &BTreeMap<String, ParamValue>) {
+        for param in &mut self.params {
+            if let Some(value) = values.get(&param.name) {
+                match value.to_type(param.ty) {
+                    Some(value) => param.value = value,
+                    None => log::warn!("saved value {value:?} doesn't fit shader param `{}`", param.name),
+                }
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for param in &mut self.params {
+            param.value = param.default.clone();
+        }
+    }
+
+    /// Writes the current values to the settings file, keeping the other
+    /// shaders' tables.
+    pub fn save(&self) -> Result<()> {
+        let mut file = load_file(&self.settings_path);
+        file.insert(
+            self.name.clone(),
+            self.params.iter().map(|param| (param.name.clone(), param.value.clone())).collect(),
+        );
+        let text = toml::to_string(&file)?;
+        std::fs::write(&self.settings_path, text)
+            .with_context(|| format!("writing {}", self.settings_path.display()))
+    }
+
+    pub fn settings_path(&self) -> &Path {
+        &self.settings_path
+    }
+
+    /// The current values laid out as the shader's params struct.
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.size];
+        for param in &self.params {
+            param.write(&mut bytes);
+        }
+        bytes
+    }
+}
+
+/// A missing file is an empty one.
+fn load_file(path: &Path) -> ParamsFile {
+    match std::fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+            log::warn!("{} is invalid ({err}), ignoring saved shader params", path.display());
+            ParamsFile::new()
+        }),
+        Err(_) => ParamsFile::new(),
+    }
+}
+
+/// The fields of the shader's params struct and the struct's size; no
+/// params uniform means no fields.
+fn reflect(path: &Path) -> Result<(Vec<Param>, usize)> {
+    let source = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let module = naga::front::wgsl::parse_str(&source).map_err(|err| anyhow!(err.emit_to_string(&source)))?;
+
+    let Some(var) = module
+        .global_variables
+        .iter()
+        .map(|(_, var)| var)
+        .find(|var| var.name.as_deref() == Some(PARAMS_VAR) && var.space == naga::AddressSpace::Uniform)
+    else {
+        return Ok((Vec::new(), 0));
+    };
+    let ty = &module.types[var.ty];
+    let naga::TypeInner::Struct { members, span } = &ty.inner else {
+        bail!("`{PARAMS_VAR}` is not a struct");
+    };
+    let annotations = struct_annotations(&source, ty.name.as_deref().unwrap_or_default());
+
+    let mut params = Vec::new();
+    for member in members {
+        let name = member.name.clone().unwrap_or_default();
+        let Some(ty) = param_type(&module.types[member.ty].inner) else {
+            log::warn!("shader param `{name}` has an unsupported type, leaving it zero");
+            continue;
+        };
+        let annotations = annotations.get(&name).map(String::as_str).unwrap_or_default();
+        let (widget, default) = parse_annotations(annotations, ty)
+            .with_context(|| format!("annotations of shader param `{name}`"))?;
+        params.push(Param {
+            name,
+            ty,
+            widget,
+            value: default.clone(),
+            default,
+            offset: member.offset as usize,
+        });
+    }
+    Ok((params, *span as usize))
+}
+
+fn param_type(inner: &naga::TypeInner) -> Option<ParamType> {
+    use naga::{ScalarKind, TypeInner};
+    match inner {
+        TypeInner::Scalar(scalar) if scalar.width == 4 => match scalar.kind {
+            ScalarKind::Float => Some(ParamType::F32),
+            ScalarKind::Sint => Some(ParamType::I32),
+            ScalarKind::Uint => Some(ParamType::U32),
+            _ => None,
+        },
+        TypeInner::Vector { size, scalar } if *scalar == naga::Scalar::F32 => Some(ParamType::Vec(*size as usize)),
+        _ => None,
+    }
+}
+
+/// The trailing comments of every field in `struct name { … }`, by field
+/// name. A comment, `//` or `/* */`, belongs to the field whose code last
+/// appeared on its line (even past the closing brace), so the braces and the
+/// fields may share lines and braces inside comments don't end the struct.
+fn struct_annotations(source: &str, name: &str) -> BTreeMap<String, String> {
+    let mut annotations: BTreeMap<String, String> = BTreeMap::new();
+    let mut after_struct = false; // the previous word was `struct`
+    let mut named = false;        // right after `struct <name>`
+    let mut in_body = false;
+    let mut closed = false;       // after the `}`, until the end of its line
+    let mut segment = String::new();           // code of the field being declared
+    let mut field: Option<String> = None;      // its name, once its `:` was seen
+    let mut line_field: Option<String> = None; // last field with code on this line
+
+    let mut i = 0;
+    while i < source.len() {
+        let rest = &source[i..];
+        let comment = if let Some(line) = rest.strip_prefix("//") {
+            let len = line.find('\n').unwrap_or(line.len());
+            Some((2 + len, &line[..len]))
+        } else if rest.starts_with("/*") {
+            let len = block_comment_len(rest);
+            Some((len, rest[2..len].trim_end_matches("*/")))
+        } else {
+            None
+        };
+        if let Some((len, text)) = comment {
+            if let Some(field) = line_field.as_ref().filter(|_| in_body) {
+                let annotation = annotations.entry(field.clone()).or_default();
+                if !annotation.is_empty() {
+                    annotation.push(' ');
+                }
+                annotation.push_str(text.trim());
+            }
+            i += len;
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        if !in_body && is_word_char(c) {
+            let len = rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+            let word = &rest[..len];
+            named = after_struct && word == name;
+            after_struct = word == "struct";
+            i += len;
+            continue;
+        }
+        i += c.len_utf8();
+        if closed {
+            if c == '\n' || !(c.is_whitespace() || c == ';') {
+                break;
+            }
+        } else if in_body {
+            match c {
+                '\n' => line_field = None,
+                ',' | '}' => {
+                    field = None;
+                    segment.clear();
+                    closed = c == '}';
+                }
+                ':' if field.is_none() => {
+                    // the name is the last word before the `:`, after any attributes
+                    let name = segment.trim_end().rsplit(|c: char| !is_word_char(c)).next().unwrap_or_default();
+                    field = Some(name.to_string());
+                    line_field = field.clone();
+                }
+                c if c.is_whitespace() => segment.push(c),
+                c => {
+                    segment.push(c);
+                    if field.is_some() {
+                        line_field = field.clone();
+                    }
+                }
+            }
+        } else if c == '{' && named {
+            in_body = true;
+        } else if !c.is_whitespace() {
+            (after_struct, named) = (false, false);
+        }
+    }
+    annotations
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Length of the block comment `text` starts with, up to its `*/`; WGSL
+/// block comments nest.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    text.len()
+}
+
+/// Parses `@range(a, b)`, `@default(…)`, `@color` and `@toggle` out of a
+/// field comment; anything else in it is left alone.
+fn parse_annotations(text: &str, ty: ParamType) -> Result<(ParamWidget, ParamValue)> {
+    let mut widget = ParamWidget::Drag;
+    let mut default = None;
+    for annotation in text.split('@').skip(1) {
+        let name = annotation.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or_default();
+        let args = annotation[name.len()..]
+            .trim_start()
+            .strip_prefix('(')
+            .and_then(|rest| rest.split_once(')'))
+            .map(|(args, _)| args);
+        let numbers = || -> Result<Vec<f64>> {
+            let args = args.with_context(|| format!("@{name} needs arguments"))?;
+            args.split(',')
+                .map(|arg| match arg.trim() {
+                    "true" => Ok(1.0),
+                    "false" => Ok(0.0),
+                    arg => arg.parse::<f64>().with_context(|| format!("`{arg}` in @{name} is not a number")),
+                })
+                .collect()
+        };
+        match name {
+            "range" => match numbers()?[..] {
+                [min, max] => widget = ParamWidget::Slider { min, max },
+                _ => bail!("@range takes a minimum and a maximum"),
+            },
+            "default" => {
+                let value = ParamValue::from_numbers(ty, &numbers()?)
+                    .with_context(|| format!("@default doesn't fit {ty:?}"))?;
+                default = Some(value);
+            }
+            "color" if matches!(ty, ParamType::Vec(3 | 4)) => widget = ParamWidget::Color,
+            "toggle" if matches!(ty, ParamType::I32 | ParamType::U32) => widget = ParamWidget::Checkbox,
+            "color" | "toggle" => bail!("@{name} doesn't apply to {ty:?}"),
+            _ => {}
+        }
+    }
+    Ok((widget, default.unwrap_or_else(|| ParamValue::zero(ty))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotations(source: &str) -> Vec<(String, String)> {
+        struct_annotations(source, "Params").into_iter().collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(name, text)| (name.to_string(), text.to_string())).collect()
+    }
+
+    #[test]
+    fn reads_trailing_field_comments() {
+        let source = "
+            struct Other {
+                ambient : f32, // @range(5.0, 6.0)
+            };
+            // struct Params { ambient : f32, // @toggle }
+            struct Params {
+                // Lighting
+                ambient : f32,       // @range(0.0, 1.0) @default(0.1)
+                @align(16) tint : vec3<f32>, // @color
+                plain   : u32,
+            };
+            struct ParamsExtra {
+                ambient : f32, // @toggle
+            };
+        ";
+        assert_eq!(
+            annotations(source),
+            pairs(&[("ambient", "@range(0.0, 1.0) @default(0.1)"), ("tint", "@color")]),
+        );
+    }
+
+    #[test]
+    fn reads_fields_on_the_brace_lines() {
+        let source = "struct Params { ambient : f32, // @range(0.0, 1.0)
+            shadows : u32 } // @toggle
+            fn unrelated() -> f32 { let x : f32 = 1.0; // @color
+                return x; }";
+        assert_eq!(annotations(source), pairs(&[("ambient", "@range(0.0, 1.0)"), ("shadows", "@toggle")]));
+    }
+
+    #[test]
+    fn reads_a_brace_on_the_next_line() {
+        let source = "struct Params
+            {
+                ambient : f32, // @default(0.5)
+            }";
+        assert_eq!(annotations(source), pairs(&[("ambient", "@default(0.5)")]));
+    }
+
+    #[test]
+    fn reads_one_line_structs_with_block_comments() {
+        let source = "struct Params { a : f32, /* @range(0.0, 2.0) */ b : vec4<f32> /* @color */ };
+            struct Light { c : f32, // @toggle
+            };";
+        assert_eq!(annotations(source), pairs(&[("a", "@range(0.0, 2.0)"), ("b", "@color")]));
+    }
+
+    #[test]
+    fn ignores_braces_inside_comments() {
+        let source = "struct Params {
+                a : f32, // @default(1.0) {not a brace}
+                /* } */
+                b : f32, /* nested /* } */ comment */ // @range(0.0, 4.0)
+            }";
+        assert_eq!(
+            annotations(source),
+            pairs(&[("a", "@default(1.0) {not a brace}"), ("b", "nested /* } */ comment @range(0.0, 4.0)")]),
+        );
+    }
+
+    #[test]
+    fn parses_widgets_and_defaults() {
+        let parse = |text, ty| parse_annotations(text, ty).unwrap();
+        assert_eq!(
+            parse("@range(0.0, 2.5) @default(1)", ParamType::F32),
+            (ParamWidget::Slider { min: 0.0, max: 2.5 }, ParamValue::Float(1.0)),
+        );
+        assert_eq!(
+            parse("@color @default(1.0, 0.5, 0.25)", ParamType::Vec(3)),
+            (ParamWidget::Color, ParamValue::Vector(vec![1.0, 0.5, 0.25])),
+        );
+        assert_eq!(parse("@toggle @default(true)", ParamType::U32), (ParamWidget::Checkbox, ParamValue::Int(1)));
+        assert_eq!(
+            parse("Brightness of the sky, e.g. for @night", ParamType::I32),
+            (ParamWidget::Drag, ParamValue::Int(0)),
+        );
+        assert_eq!(parse("", ParamType::Vec(2)), (ParamWidget::Drag, ParamValue::Vector(vec![0.0; 2])));
+    }
+
+    #[test]
+    fn rejects_annotations_that_dont_fit() {
+        for (text, ty) in [
+            ("@color", ParamType::F32),
+            ("@toggle", ParamType::F32),
+            ("@range(1.0)", ParamType::F32),
+            ("@range", ParamType::F32),
+            ("@default(1.0, 2.0)", ParamType::Vec(3)),
+            ("@default(bright)", ParamType::F32),
+        ] {
+            assert!(parse_annotations(text, ty).is_err(), "{text} on {ty:?}");
+        }
+    }
+}
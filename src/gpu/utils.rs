@@ -1,5 +1,9 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
 use std::{fs, ops::Deref};
 
+use crate::gpu::DepthMode;
+
 pub fn create_depth_view(
     device: &wgpu::Device,
     config: &wgpu::SurfaceConfiguration,
@@ -15,8 +19,9 @@ pub fn create_depth_view(
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Depth32Float,
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: DepthMode::FORMAT,
+        // sampled by full-screen passes (e.g. the atmosphere)
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
         view_formats: &[],
     };
     let texture = device.create_texture(&desc);
@@ -40,11 +45,149 @@ impl Deref for FragmentShader {
     }
 }
 
+/// Latest `load_shader` outcome for one file.
+#[derive(Clone)]
+pub struct ShaderStatus {
+    pub path: String,
+    pub loaded_at: Instant, // of the latest attempt
+    pub loads: u32,
+    pub error: Option<String>, // why the latest attempt failed
+}
+
+struct ShaderEntry {
+    status: ShaderStatus,
+    last_good: Option<String>, // source that last compiled
+}
+
+static SHADERS: Mutex<Vec<ShaderEntry>> = Mutex::new(Vec::new());
+
+/// Every shader loaded so far, in the order they were first loaded.
+pub fn shader_statuses() -> Vec<ShaderStatus> {
+    let shaders = SHADERS.lock().unwrap_or_else(PoisonError::into_inner);
+    shaders.iter().map(|entry| entry.status.clone()).collect()
+}
+
+/// Compiles the WGSL file at `path`. When a file that compiled before fails
+/// to read or validate (a broken edit while hot reloading), the last source
+/// that compiled is used instead and the error is kept for `shader_statuses`.
 pub fn load_shader(label: &str, path: &str, device: &wgpu::Device) -> wgpu::ShaderModule {
-    let src = fs::read_to_string(path).expect("failed to read shader file");
-    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+    let compile = |src: &str| {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some(label),
             source: wgpu::ShaderSource::Wgsl(src.into()),
-    })
+        });
+        (module, pollster::block_on(device.pop_error_scope()))
+    };
+
+    let mut shaders = SHADERS.lock().unwrap_or_else(PoisonError::into_inner);
+    let index = match shaders.iter().position(|entry| entry.status.path == path) {
+        Some(index) => index,
+        None => {
+            let status = ShaderStatus { path: path.to_string(), loaded_at: Instant::now(), loads: 0, error: None };
+            shaders.push(ShaderEntry { status, last_good: None });
+            shaders.len() - 1
+        }
+    };
+    let entry = &mut shaders[index];
+    entry.status.loaded_at = Instant::now();
+    entry.status.loads += 1;
+
+    let error = match fs::read_to_string(path) {
+        Ok(src) => match compile(&src) {
+            (module, None) => {
+                entry.status.error = None;
+                entry.last_good = Some(src);
+                return module;
+            }
+            (module, Some(err)) if entry.last_good.is_none() => {
+                entry.status.error = Some(err.to_string());
+                return module; // nothing to fall back to
+            }
+            (_, Some(err)) => err.to_string(),
+        },
+        Err(err) if entry.last_good.is_none() => panic!("failed to read shader file {path}: {err}"),
+        Err(err) => format!("reading {path}: {err}"),
+    };
+    log::warn!("{path} failed, keeping the previous version: {error}");
+    entry.status.error = Some(error);
+    compile(entry.last_good.as_deref().unwrap_or_default()).0
+}
+
+
+/// Uploads tightly packed texel data into a new sampled 2D texture.
+pub fn create_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    (width, height): (u32, u32),
+    format: wgpu::TextureFormat,
+    data: &[u8],
+) -> wgpu::Texture {
+    let size = wgpu::Extent3d {
+        width, height, depth_or_array_layers: 1,
+    };
+    let bytes_per_texel = format.block_copy_size(None).expect("uncompressed color format");
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes_per_texel * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+
+    texture
 }
 
+/// Uploads an RGBA8 image into a new sampled 2D texture.
+pub fn create_texture_rgba8(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    img: &image::RgbaImage,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    create_texture(device, queue, label, img.dimensions(), format, img)
+}
+
+/// Uploads a single-channel image into a new sampled `R8Unorm` texture.
+pub fn create_texture_r8(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    img: &image::GrayImage,
+) -> wgpu::Texture {
+    create_texture(device, queue, label, img.dimensions(), wgpu::TextureFormat::R8Unorm, img)
+}
+
+/// Uploads a grid of floats into a new `R32Float` texture. It isn't
+/// filterable on every adapter, so shaders read it with `textureLoad`.
+pub fn create_texture_r32f(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    size: (u32, u32),
+    values: &[f32],
+) -> wgpu::Texture {
+    create_texture(device, queue, label, size, wgpu::TextureFormat::R32Float, bytemuck::cast_slice(values))
+}
@@ -0,0 +1,211 @@
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::gpu::{load_shader, DepthMode, SceneLayouts, SceneTarget};
+use crate::vector::{self, LayerConfig, VectorVertex};
+
+const SHADER_PATH: &str = "src/shaders/vector.wgsl";
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct VectorUniforms {
+    viewport: [f32; 2], // 8 bytes @ offset 0, pixels
+    _pad: [f32; 2],     // 8 bytes @ offset 8
+} // total size = 16 bytes
+
+pub struct VectorLayer {
+    pub config: LayerConfig,
+    pub visible: bool,
+    buffer: Option<wgpu::Buffer>,
+    num_vertices: u32,
+}
+
+/// GeoJSON layers listed in a TOML file, drawn over the planet.
+pub struct VectorLayers {
+    pub enabled: bool,
+    pub layers: Vec<VectorLayer>,
+    config_path: PathBuf,
+
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl VectorLayers {
+    pub fn new(
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+        config_path: impl Into<PathBuf>,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vector UBO"),
+            contents: bytemuck::bytes_of(&VectorUniforms::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Vector Bind Group Layout"),
+            entries: &[
+                // binding 0 = Vector UBO (viewport size)
+                wgpu::BindGroupLayoutEntry {
+                    binding:    0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty:                wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size:  NonZeroU64::new(size_of::<VectorUniforms>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Vector Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline = create_pipeline(device, target, layouts, &bind_group_layout, depth_mode);
+
+        Self {
+            enabled: true,
+            layers: Vec::new(),
+            config_path: config_path.into(),
+
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn reload_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        target: SceneTarget,
+        layouts: &SceneLayouts,
+        depth_mode: DepthMode,
+    ) {
+        self.pipeline = create_pipeline(device, target, layouts, &self.bind_group_layout, depth_mode);
+    }
+
+    /// Re-reads the layer list and every GeoJSON file. A layer whose file
+    /// fails to load keeps its previous geometry, so a half-saved edit
+    /// doesn't blank it.
+    pub fn reload(&mut self, device: &wgpu::Device, planet_radius: f64) {
+        let configs = match vector::load_layers(&self.config_path) {
+            Ok(configs) => configs,
+            Err(err) => {
+                log::warn!("vector layers not reloaded: {err:#}");
+                return;
+            }
+        };
+
+        let mut previous = std::mem::take(&mut self.layers);
+        for config in configs {
+            let old = previous
+                .iter()
+                .position(|l| l.config.name == config.name)
+                .map(|i| previous.swap_remove(i));
+            let visible = old.as_ref().is_none_or(|l| l.visible);
+
+            let (buffer, num_vertices) = match vector::load_layer(&config, planet_radius) {
+                Ok(vertices) => {
+                    let buffer = (!vertices.is_empty()).then(|| {
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some(&format!("Vector Layer '{}'", config.name)),
+                            contents: bytemuck::cast_slice(&vertices),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        })
+                    });
+                    (buffer, vertices.len() as u32)
+                }
+                Err(err) => {
+                    log::warn!("layer '{}' not loaded: {err:#}", config.name);
+                    old.map_or((None, 0), |l| (l.buffer, l.num_vertices))
+                }
+            };
+            self.layers.push(VectorLayer { config, visible, buffer, num_vertices });
+        }
+    }
+
+    /// The layer list and every file it references, for hot reloading.
+    pub fn watched_paths(&self) -> Vec<PathBuf> {
+        std::iter::once(self.config_path.clone())
+            .chain(self.layers.iter().map(|l| l.config.path.clone()))
+            .collect()
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, viewport: (f32, f32)) {
+        let uniforms = VectorUniforms { viewport: [viewport.0, viewport.1], _pad: [0.0; 2] };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draws the visible layers in file order. Needs the frame bind group
+    /// and the planet node's groups (see `SceneRenderer::bind_node`) bound.
+    pub fn draw(&self, rpass: &mut wgpu::RenderPass) {
+        if !self.enabled {
+            return;
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(3, &self.bind_group, &[]);
+        for layer in self.layers.iter().filter(|l| l.visible) {
+            if let Some(buffer) = &layer.buffer {
+                rpass.set_vertex_buffer(0, buffer.slice(..));
+                rpass.draw(0..layer.num_vertices, 0..1);
+            }
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    target: SceneTarget,
+    layouts: &SceneLayouts,
+    vector_layout: &wgpu::BindGroupLayout,
+    depth_mode: DepthMode,
+) -> wgpu::RenderPipeline {
+    let shader = load_shader("Vector", SHADER_PATH, device);
+
+    let layout = layouts.pipeline_layout(device, "Vector Pipeline Layout", Some(vector_layout));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        cache: None,
+        label: Some("Vector Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[VectorVertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            compilation_options: Default::default(),
+            module: &shader,
+            entry_point: Some(&depth_mode.fragment_entry("fs_main")),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: target.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        // sits just above the surface: tested against it, never occludes
+        depth_stencil: Some(depth_mode.depth_stencil_state(false)),
+        multisample: target.multisample_state(),
+        multiview: None,
+    })
+}
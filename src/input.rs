@@ -0,0 +1,523 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+use winit::event::MouseButton;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+/// Declares `Action` from one table, so that every action has a config
+/// name and default bindings (in the config file's syntax) and is in
+/// `Action::ALL`.
+macro_rules! actions {
+    ($($action:ident = $name:literal [$($binding:literal),*],)*) => {
+        /// Everything the user can ask for, independent of how it is bound.
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Action {
+            $($action,)*
+        }
+
+        impl Action {
+            pub const ALL: &[Action] = &[$(Action::$action,)*];
+
+            /// Name used in the config file.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Action::$action => $name,)*
+                }
+            }
+
+            fn default_bindings(self) -> &'static [&'static str] {
+                match self {
+                    $(Action::$action => &[$($binding),*],)*
+                }
+            }
+        }
+    };
+}
+
+actions! {
+    // held while dragging
+    Orbit = "orbit" ["MouseLeft"],
+    Pan = "pan" ["MouseRight", "Shift+MouseLeft"],
+    // wheel
+    Zoom = "zoom" ["Wheel"],
+    // fired on press
+    ResetCamera = "reset_camera" ["R"],
+    Screenshot = "screenshot" ["F12"],
+    ToggleWireframe = "toggle_wireframe" ["F"],
+    ToggleGraticule = "toggle_graticule" ["G"],
+    ToggleVectorLayers = "toggle_vector_layers" ["V"],
+    ToggleScalarLayer = "toggle_scalar_layer" ["T"],
+    ToggleSwarm = "toggle_swarm" ["O"],
+    ToggleMoon = "toggle_moon" ["L"],
+    CycleColormap = "cycle_colormap" ["Y"],
+    NarrowScalarRange = "narrow_scalar_range" ["Minus"],
+    WidenScalarRange = "widen_scalar_range" ["Equal"],
+    ToggleBloom = "toggle_bloom" ["F1"],
+    ToggleColorGrading = "toggle_color_grading" ["F3"],
+    ToggleFxaa = "toggle_fxaa" ["F2"],
+    CycleTonemapper = "cycle_tonemapper" ["F4"],
+    ExposureDown = "exposure_down" ["Comma"],
+    ExposureUp = "exposure_up" ["Period"],
+    CycleMsaa = "cycle_msaa" ["F6"],
+    DumpRenderGraph = "dump_render_graph" ["F7"],
+    ToggleProfiler = "toggle_profiler" ["F8"],
+    ExportTrace = "export_trace" ["F9"],
+    ToggleDebugUi = "toggle_debug_ui" ["F10"],
+    ReloadShaders = "reload_shaders" ["Ctrl+R", "F5"],
+    CycleDepthMode = "cycle_depth_mode" ["Z"],
+    CycleDebugView = "cycle_debug_view" ["X"],
+    ToggleProjection = "toggle_projection" ["P"],
+    CycleController = "cycle_controller" ["C"],
+    SaveBookmark = "save_bookmark" ["B"],
+    NextBookmark = "next_bookmark" ["N"],
+    PlayCameraPath = "play_camera_path" ["K"],
+    Pick = "pick" ["MouseMiddle"],
+    AddMarker = "add_marker" ["M"],
+    RemoveMarker = "remove_marker" ["Ctrl+M"],
+    ClearMarkers = "clear_markers" ["Shift+M"],
+    // held, read by the first-person controllers
+    MoveForward = "move_forward" ["W", "Up"],
+    MoveBack = "move_back" ["S", "Down"],
+    MoveLeft = "move_left" ["A", "Left"],
+    MoveRight = "move_right" ["D", "Right"],
+    MoveUp = "move_up" ["E", "Space"],
+    MoveDown = "move_down" ["Q"],
+    Boost = "boost" ["ShiftLeft"],
+}
+
+impl Action {
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|a| a.name() == name)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A physical input a binding can react to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Wheel,
+}
+
+/// Modifier keys a binding requires.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    fn count(self) -> u32 {
+        self.ctrl as u32 + self.shift as u32 + self.alt as u32
+    }
+
+    /// True if every modifier `self` requires is held in `held`.
+    fn satisfied_by(self, held: Modifiers) -> bool {
+        (!self.ctrl || held.ctrl) && (!self.shift || held.shift) && (!self.alt || held.alt)
+    }
+}
+
+impl From<ModifiersState> for Modifiers {
+    fn from(state: ModifiersState) -> Self {
+        Self {
+            ctrl: state.control_key(),
+            shift: state.shift_key(),
+            alt: state.alt_key(),
+        }
+    }
+}
+
+/// A trigger plus the modifiers that must be held, e.g. `Ctrl+R`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub modifiers: Modifiers,
+    pub trigger: Trigger,
+}
+
+impl Binding {
+    fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = Modifiers::default();
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let trigger = parse_trigger(parts.pop()?)?;
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => return None,
+            }
+        }
+        Some(Self { modifiers, trigger })
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.ctrl {
+            f.write_str("Ctrl+")?;
+        }
+        if self.modifiers.shift {
+            f.write_str("Shift+")?;
+        }
+        if self.modifiers.alt {
+            f.write_str("Alt+")?;
+        }
+        match self.trigger {
+            Trigger::Key(code) => {
+                // back to the names `parse_key` accepts: KeyR → R, Digit7 → 7, ArrowUp → Up
+                let name = format!("{code:?}");
+                let short = ["Key", "Digit", "Arrow"].iter().find_map(|prefix| name.strip_prefix(prefix));
+                f.write_str(short.unwrap_or(&name))
+            }
+            Trigger::Mouse(button) => write!(f, "Mouse{button:?}"),
+            Trigger::Wheel => f.write_str("Wheel"),
+        }
+    }
+}
+
+fn parse_trigger(name: &str) -> Option<Trigger> {
+    let trigger = match name {
+        "MouseLeft" => Trigger::Mouse(MouseButton::Left),
+        "MouseRight" => Trigger::Mouse(MouseButton::Right),
+        "MouseMiddle" => Trigger::Mouse(MouseButton::Middle),
+        "MouseBack" => Trigger::Mouse(MouseButton::Back),
+        "MouseForward" => Trigger::Mouse(MouseButton::Forward),
+        "Wheel" => Trigger::Wheel,
+        _ => Trigger::Key(parse_key(name)?),
+    };
+    Some(trigger)
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF,
+        KeyCode::KeyG, KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL,
+        KeyCode::KeyM, KeyCode::KeyN, KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR,
+        KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU, KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX,
+        KeyCode::KeyY, KeyCode::KeyZ,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+        KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    const FUNCTION: [KeyCode; 12] = [
+        KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+        KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    ];
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => Some(LETTERS[(c as u8 - b'A') as usize]),
+            c @ '0'..='9' => Some(DIGITS[(c as u8 - b'0') as usize]),
+            _ => None,
+        };
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION.get(n.checked_sub(1)?).copied();
+    }
+
+    let key = match name {
+        "Space" => KeyCode::Space,
+        "Enter" => KeyCode::Enter,
+        "Escape" => KeyCode::Escape,
+        "Tab" => KeyCode::Tab,
+        "Backspace" => KeyCode::Backspace,
+        "Up" => KeyCode::ArrowUp,
+        "Down" => KeyCode::ArrowDown,
+        "Left" => KeyCode::ArrowLeft,
+        "Right" => KeyCode::ArrowRight,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "ShiftLeft" => KeyCode::ShiftLeft,
+        "ShiftRight" => KeyCode::ShiftRight,
+        "ControlLeft" => KeyCode::ControlLeft,
+        "ControlRight" => KeyCode::ControlRight,
+        "AltLeft" => KeyCode::AltLeft,
+        "AltRight" => KeyCode::AltRight,
+        "Minus" => KeyCode::Minus,
+        "Equal" => KeyCode::Equal,
+        "Comma" => KeyCode::Comma,
+        "Period" => KeyCode::Period,
+        "Slash" => KeyCode::Slash,
+        "Backquote" => KeyCode::Backquote,
+        "BracketLeft" => KeyCode::BracketLeft,
+        "BracketRight" => KeyCode::BracketRight,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[derive(Deserialize, Default)]
+struct InputFile {
+    #[serde(default)]
+    bindings: BTreeMap<String, Vec<String>>,
+}
+
+/// Maps bindings to actions. Built from the defaults, overridden per action
+/// by the config file.
+pub struct InputMap {
+    bindings: Vec<(Binding, Action)>, // in definition order
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::from_config(&InputFile::default())
+    }
+}
+
+impl InputMap {
+    /// Loads `path`, falling back to the defaults (with a warning) for
+    /// anything missing or invalid.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                log::warn!("{} not loaded ({err}), using default input bindings", path.display());
+                return Self::default();
+            }
+        };
+        match toml::from_str::<InputFile>(&text) {
+            Ok(file) => Self::from_config(&file),
+            Err(err) => {
+                log::warn!("{} is invalid ({err}), using default input bindings", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    fn from_config(file: &InputFile) -> Self {
+        let (map, warnings) = Self::build(file);
+        for warning in warnings {
+            log::warn!("{warning}");
+        }
+        map
+    }
+
+    /// The map for `file`, and what was wrong with it.
+    fn build(file: &InputFile) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        for name in file.bindings.keys() {
+            if Action::from_name(name).is_none() {
+                warnings.push(format!("unknown input action `{name}`"));
+            }
+        }
+
+        let mut bindings: Vec<(Binding, Action)> = Vec::new();
+        for &action in Action::ALL {
+            // an action listed in the file replaces all of its defaults
+            let texts: Vec<&str> = match file.bindings.get(action.name()) {
+                Some(texts) => texts.iter().map(String::as_str).collect(),
+                None => action.default_bindings().to_vec(),
+            };
+            for text in texts {
+                let Some(binding) = Binding::parse(text) else {
+                    warnings.push(format!("cannot parse binding `{text}` for `{action}`"));
+                    continue;
+                };
+                if (binding.trigger == Trigger::Wheel) != (action == Action::Zoom) {
+                    warnings.push(format!("`{binding}` cannot be bound to `{action}`: only zoom uses the wheel"));
+                    continue;
+                }
+                match bindings.iter().find(|(b, _)| *b == binding) {
+                    Some(&(_, other)) if other != action => {
+                        warnings.push(format!("`{binding}` is bound to both `{other}` and `{action}`, keeping `{other}`"));
+                    }
+                    Some(_) => {}
+                    None => bindings.push((binding, action)),
+                }
+            }
+        }
+        (Self { bindings }, warnings)
+    }
+
+    /// Action for `trigger` given the held modifiers. The binding requiring
+    /// the most modifiers wins, so `Shift+MouseLeft` beats `MouseLeft`; on a
+    /// tie, one requiring exactly the held modifiers, then the one defined
+    /// first.
+    pub fn action(&self, trigger: Trigger, held: Modifiers) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|(b, _)| b.trigger == trigger && b.modifiers.satisfied_by(held))
+            .rev() // `max_by_key` keeps the last of equal elements
+            .max_by_key(|(b, _)| (b.modifiers.count(), b.modifiers == held))
+            .map(|&(_, action)| action)
+    }
+}
+
+/// Live input state: current modifiers and which trigger started which held
+/// action, so releases end the right action even if modifiers changed since.
+/// An action bound to several triggers stays active until the last is released.
+#[derive(Default)]
+pub struct Input {
+    pub map: InputMap,
+    modifiers: Modifiers,
+    held: HashMap<Trigger, Action>,
+    active: HashMap<Action, u32>, // number of held triggers per action
+}
+
+impl Input {
+    pub fn new(map: InputMap) -> Self {
+        Self { map, ..Default::default() }
+    }
+
+    pub fn set_modifiers(&mut self, state: ModifiersState) {
+        self.modifiers = state.into();
+    }
+
+    /// Press or release of a key or button; returns the action it starts or
+    /// ends. Key repeats are ignored by the caller.
+    pub fn button(&mut self, trigger: Trigger, pressed: bool) -> Option<Action> {
+        if pressed {
+            let action = self.map.action(trigger, self.modifiers)?;
+            if let Some(previous) = self.held.insert(trigger, action) {
+                self.release(previous);
+            }
+            *self.active.entry(action).or_default() += 1;
+            Some(action)
+        } else {
+            let action = self.held.remove(&trigger)?;
+            self.release(action).then_some(action)
+        }
+    }
+
+    /// Drops one held trigger of `action`; true if it was the last.
+    fn release(&mut self, action: Action) -> bool {
+        let Some(count) = self.active.get_mut(&action) else { return true };
+        *count -= 1;
+        if *count > 0 {
+            return false;
+        }
+        self.active.remove(&action);
+        true
+    }
+
+    /// Action for a wheel movement, if any.
+    pub fn wheel(&self) -> Option<Action> {
+        self.map.action(Trigger::Wheel, self.modifiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(modifiers: Modifiers, trigger: Trigger) -> Binding {
+        Binding { modifiers, trigger }
+    }
+
+    const CTRL: Modifiers = Modifiers { ctrl: true, shift: false, alt: false };
+    const SHIFT: Modifiers = Modifiers { ctrl: false, shift: true, alt: false };
+    const NONE: Modifiers = Modifiers { ctrl: false, shift: false, alt: false };
+
+    fn build(config: &str) -> (InputMap, Vec<String>) {
+        InputMap::build(&toml::from_str(config).unwrap())
+    }
+
+    #[test]
+    fn parses_bindings() {
+        assert_eq!(Binding::parse("R"), Some(binding(NONE, Trigger::Key(KeyCode::KeyR))));
+        assert_eq!(Binding::parse("Ctrl+R"), Some(binding(CTRL, Trigger::Key(KeyCode::KeyR))));
+        assert_eq!(Binding::parse("control+r"), Some(binding(CTRL, Trigger::Key(KeyCode::KeyR))));
+        assert_eq!(
+            Binding::parse("Ctrl + Shift + Alt + F5"),
+            Some(binding(Modifiers { ctrl: true, shift: true, alt: true }, Trigger::Key(KeyCode::F5))),
+        );
+        assert_eq!(Binding::parse("Shift+MouseLeft"), Some(binding(SHIFT, Trigger::Mouse(MouseButton::Left))));
+        assert_eq!(Binding::parse("Wheel"), Some(binding(NONE, Trigger::Wheel)));
+        assert_eq!(Binding::parse("Ctrl+Wheel"), Some(binding(CTRL, Trigger::Wheel)));
+        assert_eq!(Binding::parse("7"), Some(binding(NONE, Trigger::Key(KeyCode::Digit7))));
+        assert_eq!(Binding::parse("Up"), Some(binding(NONE, Trigger::Key(KeyCode::ArrowUp))));
+    }
+
+    #[test]
+    fn rejects_bad_bindings() {
+        for text in ["", "Ctrl+", "Hyper+R", "F0", "F13", "Wheelie", "R+Ctrl", "?"] {
+            assert_eq!(Binding::parse(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn displays_what_it_parses() {
+        for text in ["Ctrl+Shift+Alt+R", "Shift+MouseLeft", "Ctrl+Wheel", "F5", "7", "Up", "Space", "ShiftLeft"] {
+            let parsed = Binding::parse(text).unwrap();
+            assert_eq!(parsed.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid_and_distinct() {
+        let (map, warnings) = build("");
+        assert!(warnings.is_empty(), "{warnings:?}");
+        for &action in Action::ALL {
+            assert!(map.bindings.iter().any(|&(_, a)| a == action), "`{action}` has no binding");
+            assert_eq!(Action::from_name(action.name()), Some(action));
+        }
+    }
+
+    #[test]
+    fn warns_about_duplicate_bindings() {
+        let (map, warnings) = build("[bindings]\ntoggle_bloom = [\"G\"]");
+        assert_eq!(warnings, ["`G` is bound to both `toggle_graticule` and `toggle_bloom`, keeping `toggle_graticule`"]);
+        assert_eq!(map.action(Trigger::Key(KeyCode::KeyG), NONE), Some(Action::ToggleGraticule));
+        assert_eq!(map.action(Trigger::Key(KeyCode::F1), NONE), None); // the defaults were replaced
+    }
+
+    #[test]
+    fn warns_about_bad_config_entries() {
+        let (_, warnings) = build("[bindings]\nfly = [\"J\"]\nscreenshot = [\"Hyper+S\"]\npick = [\"Wheel\"]");
+        assert_eq!(warnings, [
+            "unknown input action `fly`",
+            "cannot parse binding `Hyper+S` for `screenshot`",
+            "`Wheel` cannot be bound to `pick`: only zoom uses the wheel",
+        ]);
+    }
+
+    #[test]
+    fn most_specific_binding_wins() {
+        let map = InputMap::default();
+        let left = Trigger::Mouse(MouseButton::Left);
+        assert_eq!(map.action(left, NONE), Some(Action::Orbit));
+        assert_eq!(map.action(left, SHIFT), Some(Action::Pan));
+        assert_eq!(map.action(left, CTRL), Some(Action::Orbit));
+        assert_eq!(map.action(Trigger::Key(KeyCode::KeyR), CTRL), Some(Action::ReloadShaders));
+    }
+
+    #[test]
+    fn ties_go_to_the_exact_match_then_the_first_defined() {
+        let (map, warnings) = build("[bindings]\npick = [\"Ctrl+J\"]\nadd_marker = [\"Shift+J\"]");
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let j = Trigger::Key(KeyCode::KeyJ);
+        assert_eq!(map.action(j, CTRL), Some(Action::Pick));
+        assert_eq!(map.action(j, SHIFT), Some(Action::AddMarker));
+        // both are satisfied and neither is exact: `pick` comes first in `Action::ALL`
+        let both = Modifiers { ctrl: true, shift: true, alt: false };
+        for _ in 0..8 {
+            assert_eq!(map.action(j, both), Some(Action::Pick));
+        }
+    }
+
+    #[test]
+    fn action_ends_when_its_last_trigger_is_released() {
+        let (map, _) = build("[bindings]\nmove_down = [\"Q\", \"J\"]");
+        let mut input = Input::new(map);
+        let (q, j) = (Trigger::Key(KeyCode::KeyQ), Trigger::Key(KeyCode::KeyJ));
+        assert_eq!(input.button(q, true), Some(Action::MoveDown));
+        assert_eq!(input.button(j, true), Some(Action::MoveDown));
+        assert_eq!(input.button(q, false), None);
+        assert_eq!(input.button(j, false), Some(Action::MoveDown));
+        assert_eq!(input.button(j, false), None);
+    }
+}
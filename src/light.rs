@@ -0,0 +1,30 @@
+use glam::Vec3;
+
+pub struct Light {
+    pub dir: Vec3,   // direction the light travels (not towards the sun)
+    pub color: Vec3,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            dir: Vec3::new(-0.8, -1.0, -1.0),
+            color: Vec3::ONE, // white sun so the sky can scatter every wavelength
+        }
+    }
+}
+
+impl Light {
+    /// Direction pointing from the surface towards the light source.
+    pub fn sun_dir(&self) -> Vec3 {
+        -self.dir.normalize()
+    }
+
+    /// Layout of the Light UBO: direction + color, each padded to 16 bytes.
+    pub fn to_uniform(&self) -> [[f32; 4]; 2] {
+        [
+            self.dir.extend(0.0).to_array(),
+            self.color.extend(0.0).to_array(),
+        ]
+    }
+}
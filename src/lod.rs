@@ -0,0 +1,565 @@
+use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_2;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use glam::{DVec3, Mat4, Vec3, Vec4};
+
+use crate::camera::Projection;
+use crate::vertex::{self, Vertex};
+
+/// Quads along each chunk edge. Every chunk has the same topology, so one
+/// index buffer serves them all.
+pub const GRID: u16 = 16;
+
+/// Children only get merged back once their error drops well below the split
+/// threshold, so chunks don't flicker between levels at the boundary.
+const MERGE_HYSTERESIS: f32 = 0.5;
+
+/// One node of a cube face quadtree: face 0‥6 (in `vertex::VERTICES` order),
+/// `level` 0 is the whole face, `x`/`y` index the 2^level × 2^level grid.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ChunkKey {
+    pub face: u8,
+    pub level: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl ChunkKey {
+    fn root(face: u8) -> Self {
+        Self { face, level: 0, x: 0, y: 0 }
+    }
+
+    fn children(&self) -> [ChunkKey; 4] {
+        let (level, x, y) = (self.level + 1, self.x * 2, self.y * 2);
+        [(0, 0), (1, 0), (0, 1), (1, 1)]
+            .map(|(dx, dy)| ChunkKey { face: self.face, level, x: x + dx, y: y + dy })
+    }
+
+    /// Unit-sphere direction at chunk-local coords `u, v` ∈ [0,1].
+    fn dir64(&self, u: f64, v: f64) -> DVec3 {
+        let size = 1.0 / (1u32 << self.level) as f64;
+        let s = (self.x as f64 + u) * size;
+        let t = (self.y as f64 + v) * size;
+        vertex::face_point(self.face as usize, s, t).normalize()
+    }
+
+    /// f32 version for LOD decisions, where precision doesn't matter.
+    fn dir(&self, u: f32, v: f32) -> Vec3 {
+        self.dir64(u as f64, v as f64).as_vec3()
+    }
+
+    /// Bounding sphere (center, radius) of the chunk surface on a planet of `radius`.
+    fn bounds(&self, radius: f32) -> (Vec3, f32) {
+        let center = self.dir(0.5, 0.5) * radius;
+        let extent = [(0., 0.), (1., 0.), (0., 1.), (1., 1.)]
+            .iter()
+            .map(|&(u, v)| (self.dir(u, v) * radius).distance(center))
+            .fold(0.0, f32::max);
+        (center, extent)
+    }
+
+    /// Angle between the chunk center and its farthest corner.
+    fn angular_radius(&self) -> f32 {
+        let n = self.dir(0.5, 0.5);
+        [(0., 0.), (1., 0.), (0., 1.), (1., 1.)]
+            .iter()
+            .map(|&(u, v)| n.angle_between(self.dir(u, v)))
+            .fold(0.0, f32::max)
+    }
+
+    /// Worst distance between the tessellated chunk and the true sphere: the
+    /// sagitta of one grid cell.
+    fn geometric_error(&self, radius: f32) -> f32 {
+        let cell_angle = FRAC_PI_2 / (1u32 << self.level) as f32 / GRID as f32;
+        radius * (1.0 - (cell_angle * 0.5).cos())
+    }
+}
+
+pub struct LodParams {
+    pub planet_radius: f32,
+    pub max_level: u8,
+    pub max_pixel_error: f32, // split once a chunk's error covers more pixels than this
+    pub skirt_depth: f32,     // skirt length as a fraction of the chunk's edge length
+}
+
+impl Default for LodParams {
+    fn default() -> Self {
+        Self {
+            planet_radius: 1.0,
+            max_level: 14,
+            max_pixel_error: 1.0,
+            skirt_depth: 0.05,
+        }
+    }
+}
+
+/// What the LOD needs to know about the camera this frame.
+pub struct LodView {
+    pub eye: Vec3,        // relative to the planet center
+    pub view_proj: Mat4,  // camera-relative, like everything we render
+    pub forward: Vec3,
+    pub projection: Projection,
+    pub viewport_height: f32,
+}
+
+/// Finished chunk mesh, produced on a worker thread. Vertex positions are
+/// relative to `origin` (planet-local), so they stay small at any planet size.
+pub struct ChunkGeometry {
+    pub key: ChunkKey,
+    pub origin: DVec3,
+    pub vertices: Vec<Vertex>,
+}
+
+/// GPU-side changes the owner of the chunk buffers has to apply.
+#[derive(Default)]
+pub struct LodChanges {
+    pub uploads: Vec<ChunkGeometry>,
+    pub evicted: Vec<ChunkKey>,
+}
+
+struct ChunkJob {
+    key: ChunkKey,
+    radius: f64,
+    skirt_depth: f64,
+}
+
+struct Node {
+    key: ChunkKey,
+    children: Option<Box<[Node; 4]>>,
+}
+
+/// Chunked quadtree LOD for the cube-sphere planet.
+///
+/// Each cube face is a quadtree. Every frame `update` splits chunks whose
+/// screen-space error is too large, merges ones that got too coarse, culls
+/// chunks outside the frustum or behind the horizon (dropping everything
+/// below them) and collects the leaves to draw. Chunk meshes are built on
+/// worker threads; a chunk is only split once all four children are ready,
+/// so there are no holes while they load. Cracks between neighbours at
+/// different levels are hidden by skirts.
+pub struct PlanetLod {
+    pub params: LodParams,
+
+    roots: Vec<Node>,
+    pending: HashSet<ChunkKey>,
+    ready: HashSet<ChunkKey>,
+    visible: Vec<ChunkKey>,
+
+    jobs: Sender<ChunkJob>,
+    results: Receiver<ChunkGeometry>,
+}
+
+impl PlanetLod {
+    pub fn new(params: LodParams) -> Self {
+        let (jobs, job_rx) = mpsc::channel::<ChunkJob>();
+        let (result_tx, results) = mpsc::channel::<ChunkGeometry>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        let workers = thread::available_parallelism().map_or(2, |n| n.get().clamp(1, 4));
+        for i in 0..workers {
+            let job_rx = Arc::clone(&job_rx);
+            let result_tx = result_tx.clone();
+            thread::Builder::new()
+                .name(format!("chunk-mesher-{i}"))
+                .spawn(move || loop {
+                    // lock only while receiving so the other workers can pick up jobs
+                    let job = match job_rx.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break, // PlanetLod dropped
+                    };
+                    if result_tx.send(build_chunk(&job)).is_err() {
+                        break;
+                    }
+                })
+                .expect("failed to spawn chunk mesher");
+        }
+
+        Self {
+            params,
+            roots: (0..6).map(|face| Node { key: ChunkKey::root(face), children: None }).collect(),
+            pending: HashSet::new(),
+            ready: HashSet::new(),
+            visible: Vec::new(),
+            jobs,
+            results,
+        }
+    }
+
+    /// Chunks to draw this frame, all of which have been uploaded.
+    pub fn visible(&self) -> &[ChunkKey] {
+        &self.visible
+    }
+
+    pub fn update(&mut self, view: &LodView) -> LodChanges {
+        let mut changes = LodChanges::default();
+
+        // pick up finished meshes; drop the ones nobody wants anymore
+        while let Ok(geometry) = self.results.try_recv() {
+            if self.pending.remove(&geometry.key) {
+                self.ready.insert(geometry.key);
+                changes.uploads.push(geometry);
+            }
+        }
+
+        let mut roots = std::mem::take(&mut self.roots);
+        let mut ctx = Visit {
+            view,
+            planes: frustum_planes(view.view_proj),
+            pixel_scale: match view.projection {
+                Projection::Perspective { fov_y, .. } => view.viewport_height / (2.0 * (fov_y * 0.5).tan()),
+                Projection::Orthographic { height, .. } => view.viewport_height / height,
+            },
+            visible: Vec::new(),
+            changes: &mut changes,
+        };
+        for root in &mut roots {
+            self.visit(root, &mut ctx);
+        }
+        self.visible = ctx.visible;
+        self.roots = roots;
+
+        changes
+    }
+
+    fn visit(&mut self, node: &mut Node, ctx: &mut Visit) {
+        let key = node.key;
+        if self.culled(key, ctx) {
+            // nothing below an unseen chunk is worth keeping resident
+            self.merge(node, ctx.changes);
+            self.abandon_children(key, ctx.changes);
+            return;
+        }
+
+        let threshold = if node.children.is_some() {
+            self.params.max_pixel_error * MERGE_HYSTERESIS
+        } else {
+            self.params.max_pixel_error
+        };
+        let wants_split = key.level < self.params.max_level && self.screen_error(key, ctx) > threshold;
+
+        if wants_split && node.children.is_none() {
+            let children = key.children();
+            if children.iter().all(|c| self.ready.contains(c)) {
+                node.children = Some(Box::new(children.map(|key| Node { key, children: None })));
+            } else {
+                children.iter().for_each(|&c| self.request(c));
+            }
+        } else if !wants_split && node.children.is_some() {
+            self.merge(node, ctx.changes);
+        } else if !wants_split {
+            self.abandon_children(key, ctx.changes);
+        }
+
+        match node.children.as_deref_mut() {
+            Some(children) => children.iter_mut().for_each(|child| self.visit(child, ctx)),
+            None if self.ready.contains(&key) => ctx.visible.push(key),
+            None => self.request(key),
+        }
+    }
+
+    fn culled(&self, key: ChunkKey, ctx: &Visit) -> bool {
+        let radius = self.params.planet_radius;
+        let (center, extent) = key.bounds(radius);
+        let extent = extent + self.skirt(key);
+
+        // frustum: entirely on the outside of any plane (planes are camera-relative)
+        let center_rel = center - ctx.view.eye;
+        if ctx.planes.iter().any(|p| p.truncate().dot(center_rel) + p.w < -extent * p.truncate().length()) {
+            return true;
+        }
+
+        // horizon: every point of the chunk is farther round the sphere than
+        // the eye can see. Only meaningful from outside the planet; an
+        // orthographic eye is infinitely far away and sees a full hemisphere.
+        let eye_dist = ctx.view.eye.length();
+        if eye_dist > radius {
+            let (eye_dir, horizon) = match ctx.view.projection {
+                Projection::Perspective { .. } => (ctx.view.eye, (radius / eye_dist).acos()),
+                Projection::Orthographic { .. } => (-ctx.view.forward, FRAC_PI_2),
+            };
+            let to_chunk = eye_dir.angle_between(center);
+            if to_chunk - key.angular_radius() > horizon {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Geometric error of the chunk projected to pixels at its nearest point.
+    fn screen_error(&self, key: ChunkKey, ctx: &Visit) -> f32 {
+        let error = key.geometric_error(self.params.planet_radius);
+        match ctx.view.projection {
+            Projection::Perspective { .. } => {
+                let (center, extent) = key.bounds(self.params.planet_radius);
+                let distance = (ctx.view.eye.distance(center) - extent).max(1e-6);
+                error / distance * ctx.pixel_scale
+            }
+            Projection::Orthographic { .. } => error * ctx.pixel_scale,
+        }
+    }
+
+    fn skirt(&self, key: ChunkKey) -> f32 {
+        skirt_length(key, self.params.planet_radius as f64, self.params.skirt_depth as f64) as f32
+    }
+
+    fn request(&mut self, key: ChunkKey) {
+        if self.ready.contains(&key) || !self.pending.insert(key) {
+            return;
+        }
+        let _ = self.jobs.send(ChunkJob {
+            key,
+            radius: self.params.planet_radius as f64,
+            skirt_depth: self.params.skirt_depth as f64,
+        });
+    }
+
+    /// Cancels a split that is no longer wanted, of a leaf whose children
+    /// were requested but not all of them ready yet. Meshes still in flight
+    /// are dropped when they arrive; ready ones have no node to own them.
+    fn abandon_children(&mut self, key: ChunkKey, changes: &mut LodChanges) {
+        for child in key.children() {
+            self.pending.remove(&child);
+            if self.ready.remove(&child) {
+                changes.evicted.push(child);
+            }
+        }
+    }
+
+    /// Drops every descendant of `node`; it becomes a leaf again.
+    fn merge(&mut self, node: &mut Node, changes: &mut LodChanges) {
+        let mut stack: Vec<Node> = node.children.take().map_or(Vec::new(), |c| Vec::from(*c));
+        while let Some(child) = stack.pop() {
+            if self.ready.remove(&child.key) {
+                changes.evicted.push(child.key);
+            }
+            self.pending.remove(&child.key);
+            if let Some(grandchildren) = child.children {
+                stack.extend(Vec::from(*grandchildren));
+            }
+        }
+    }
+}
+
+struct Visit<'a> {
+    view: &'a LodView,
+    planes: [Vec4; 6],
+    pixel_scale: f32, // pixels per radian (perspective) or per world unit (orthographic)
+    visible: Vec<ChunkKey>,
+    changes: &'a mut LodChanges,
+}
+
+/// Clip planes (xyz·p + w ≥ 0 inside) for wgpu's 0 ≤ z ≤ w clip space.
+fn frustum_planes(view_proj: Mat4) -> [Vec4; 6] {
+    let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+    [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2]
+}
+
+fn skirt_length(key: ChunkKey, radius: f64, skirt_depth: f64) -> f64 {
+    let edge = std::f64::consts::FRAC_PI_2 / (1u32 << key.level) as f64 * radius;
+    edge * skirt_depth
+}
+
+/// Index list shared by every chunk: the (GRID+1)² surface grid followed by
+/// the skirt strips hanging down from its four edges.
+pub fn chunk_indices() -> Vec<u16> {
+    let n = GRID;
+    let row = n + 1;
+    let mut indices = Vec::new();
+    vertex::grid_indices(n, 0, &mut indices);
+
+    let skirt_base = row * row;
+    for (edge, ring) in edge_rings().iter().enumerate() {
+        let base = skirt_base + edge as u16 * row;
+        for k in 0..n {
+            let (top_a, top_b) = (ring[k as usize], ring[k as usize + 1]);
+            let (low_a, low_b) = (base + k, base + k + 1);
+            indices.extend_from_slice(&[top_a, low_a, low_b, top_a, low_b, top_b]);
+        }
+    }
+    indices
+}
+
+/// Surface vertex indices along each chunk edge: bottom, right, top, left.
+fn edge_rings() -> [Vec<u16>; 4] {
+    let n = GRID;
+    let row = n + 1;
+    [
+        (0..=n).collect(),
+        (0..=n).map(|j| j * row + n).collect(),
+        (0..=n).map(|i| n * row + i).collect(),
+        (0..=n).map(|j| j * row).collect(),
+    ]
+}
+
+fn build_chunk(job: &ChunkJob) -> ChunkGeometry {
+    let key = job.key;
+    let n = GRID;
+    let mut vertices = Vec::with_capacity((n as usize + 1).pow(2) + 4 * (n as usize + 1));
+
+    // positions are computed in f64 and only narrowed once made relative to the chunk
+    let origin = key.dir64(0.5, 0.5) * job.radius;
+    let vertex_at = |dir: DVec3, radius: f64, uv: [f32; 2]| Vertex {
+        position: (dir * radius - origin).as_vec3().to_array(),
+        normal:   dir.as_vec3().to_array(),
+        uv,
+    };
+
+    let face_size = 1.0 / (1u32 << key.level) as f32;
+    for j in 0..=n {
+        for i in 0..=n {
+            let (u, v) = (i as f64 / n as f64, j as f64 / n as f64);
+            let uv = [(key.x as f32 + u as f32) * face_size, (key.y as f32 + v as f32) * face_size];
+            vertices.push(vertex_at(key.dir64(u, v), job.radius, uv));
+        }
+    }
+
+    // skirts: copies of the edge vertices pushed down towards the center
+    let drop = skirt_length(key, job.radius, job.skirt_depth);
+    for ring in edge_rings() {
+        for &i in &ring {
+            let (i, j) = (i % (n + 1), i / (n + 1));
+            let (u, v) = (i as f64 / n as f64, j as f64 / n as f64);
+            let top = vertices[(j * (n + 1) + i) as usize];
+            vertices.push(vertex_at(key.dir64(u, v), job.radius - drop, top.uv));
+        }
+    }
+
+    ChunkGeometry { key, origin, vertices }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// A perspective camera `altitude` planet radii above the surface along
+    /// `dir`, looking straight down.
+    fn view_from(dir: Vec3, altitude: f32) -> LodView {
+        let projection = Projection::Perspective { fov_y: 1.0, near: 0.001, far: 100.0 };
+        let forward = -dir;
+        let up = if forward.y.abs() > 0.9 { Vec3::Z } else { Vec3::Y };
+        LodView {
+            eye: dir * (1.0 + altitude),
+            view_proj: Mat4::perspective_rh(1.0, 1.0, 0.001, 100.0) * Mat4::look_to_rh(Vec3::ZERO, forward, up),
+            forward,
+            projection,
+            viewport_height: 720.0,
+        }
+    }
+
+    fn lod() -> PlanetLod {
+        PlanetLod::new(LodParams { max_level: 5, ..Default::default() })
+    }
+
+    /// Mirrors the GPU side: the chunks uploaded and not evicted since.
+    #[derive(Default)]
+    struct Resident(HashSet<ChunkKey>);
+
+    impl Resident {
+        fn apply(&mut self, changes: LodChanges) -> bool {
+            let changed = !changes.uploads.is_empty() || !changes.evicted.is_empty();
+            for geometry in changes.uploads {
+                assert!(self.0.insert(geometry.key), "{:?} uploaded twice", geometry.key);
+            }
+            for key in changes.evicted {
+                assert!(self.0.remove(&key), "{key:?} evicted but never uploaded");
+            }
+            changed
+        }
+    }
+
+    /// Updates until no meshes are in flight and nothing changes anymore.
+    fn settle(lod: &mut PlanetLod, resident: &mut Resident, view: &LodView) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let changed = resident.apply(lod.update(view));
+            if !changed && lod.pending.is_empty() {
+                return;
+            }
+            assert!(Instant::now() < deadline, "LOD did not settle");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Every chunk in the quadtree, leaves and inner nodes.
+    fn tree(lod: &PlanetLod) -> HashSet<ChunkKey> {
+        let mut keys = HashSet::new();
+        let mut stack: Vec<&Node> = lod.roots.iter().collect();
+        while let Some(node) = stack.pop() {
+            keys.insert(node.key);
+            if let Some(children) = &node.children {
+                stack.extend(children.iter());
+            }
+        }
+        keys
+    }
+
+    /// Every uploaded chunk belongs to a node, so evicting the node frees it.
+    fn assert_owned(lod: &PlanetLod, resident: &Resident) {
+        assert_eq!(resident.0, lod.ready);
+        let tree = tree(lod);
+        let orphans: Vec<_> = lod.ready.difference(&tree).collect();
+        assert!(orphans.is_empty(), "chunks without a node: {orphans:?}");
+    }
+
+    fn max_level(keys: impl IntoIterator<Item = ChunkKey>) -> u8 {
+        keys.into_iter().map(|key| key.level).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn splits_near_the_surface() {
+        let (mut lod, mut resident) = (lod(), Resident::default());
+        settle(&mut lod, &mut resident, &view_from(Vec3::Z, 0.01));
+
+        assert_eq!(max_level(lod.visible().iter().copied()), 5);
+        assert!(lod.visible().iter().all(|key| lod.ready.contains(key)));
+        assert_owned(&lod, &resident);
+    }
+
+    #[test]
+    fn merges_when_moving_away() {
+        let (mut lod, mut resident) = (lod(), Resident::default());
+        settle(&mut lod, &mut resident, &view_from(Vec3::Z, 0.01));
+        let near = lod.ready.len();
+
+        settle(&mut lod, &mut resident, &view_from(Vec3::Z, 50.0));
+        assert!(max_level(lod.ready.iter().copied()) <= 1);
+        assert!(lod.ready.len() < near);
+        assert_owned(&lod, &resident);
+    }
+
+    #[test]
+    fn frees_chunks_behind_the_horizon() {
+        let (mut lod, mut resident) = (lod(), Resident::default());
+        settle(&mut lod, &mut resident, &view_from(Vec3::Z, 0.01));
+
+        settle(&mut lod, &mut resident, &view_from(-Vec3::Z, 0.01));
+        let far_side: Vec<_> = lod.ready.iter().filter(|key| key.level > 0 && key.dir(0.5, 0.5).z > 0.0).collect();
+        assert!(far_side.is_empty(), "still resident: {far_side:?}");
+        assert_owned(&lod, &resident);
+    }
+
+    #[test]
+    fn abandons_splits_that_are_no_longer_wanted() {
+        let (mut lod, mut resident) = (lod(), Resident::default());
+        let near = view_from(Vec3::Z, 0.01);
+        let far = view_from(Vec3::Z, 50.0);
+        settle(&mut lod, &mut resident, &far);
+
+        // request the next level, then leave before all of it has arrived,
+        // over and over so that some results land in between
+        for _ in 0..20 {
+            resident.apply(lod.update(&near));
+            resident.apply(lod.update(&far));
+            assert!(lod.pending.iter().all(|key| key.level == 0));
+        }
+        settle(&mut lod, &mut resident, &far);
+        assert!(max_level(lod.ready.iter().copied()) <= 1);
+        assert_owned(&lod, &resident);
+    }
+}
@@ -1,8 +1,21 @@
 use winit::event_loop::{ControlFlow, EventLoop};
 
 mod gpu;
+mod animation;
 mod app;
+mod atlas;
 mod camera;
+mod controller;
+mod input;
+mod light;
+mod lod;
+mod markers;
+mod moon;
+mod picking;
+mod scalar;
+mod scene;
+mod swarm;
+mod vector;
 mod vertex;
 mod uniform;
 
@@ -0,0 +1,138 @@
+use glam::DVec3;
+
+use crate::controller::direction;
+
+/// Where a marker sits.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MarkerPosition {
+    /// Degrees on the planet; altitude in planet radii above the surface.
+    LatLong { latitude: f64, longitude: f64, altitude: f64 },
+    World(DVec3),
+}
+
+impl MarkerPosition {
+    pub fn world(self, planet_center: DVec3, planet_radius: f64) -> DVec3 {
+        match self {
+            MarkerPosition::LatLong { latitude, longitude, altitude } => {
+                // same convention as picking
+                let up = direction(longitude.to_radians(), latitude.to_radians());
+                planet_center + up * planet_radius * (1.0 + altitude)
+            }
+            MarkerPosition::World(position) => position,
+        }
+    }
+}
+
+/// Icon shapes baked into the marker atlas.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Icon {
+    #[default]
+    Circle,
+    Ring,
+    Square,
+    Diamond,
+    Triangle,
+}
+
+impl Icon {
+    pub const ALL: [Icon; 5] = [Icon::Circle, Icon::Ring, Icon::Square, Icon::Diamond, Icon::Triangle];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&i| i == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Coverage at `(x, y)` in -1..1 across the icon, `pixel` wide samples.
+    pub fn coverage(self, x: f32, y: f32, pixel: f32) -> f32 {
+        // signed distance to the outline, negative inside
+        let distance = match self {
+            Icon::Circle => (x * x + y * y).sqrt() - 0.8,
+            Icon::Ring => ((x * x + y * y).sqrt() - 0.65).abs() - 0.18,
+            Icon::Square => x.abs().max(y.abs()) - 0.7,
+            Icon::Diamond => (x.abs() + y.abs() - 0.9) * std::f32::consts::FRAC_1_SQRT_2,
+            // apex up (y grows downwards): base edge, then the mirrored sides
+            // through (0, -0.8) and (±0.85, 0.65)
+            Icon::Triangle => (y - 0.65).max(x.abs() * 0.8627 - (y + 0.8) * 0.5057),
+        };
+        (0.5 - distance / pixel).clamp(0.0, 1.0)
+    }
+}
+
+/// A billboard with an optional text label.
+#[derive(Clone, Debug)]
+pub struct Marker {
+    pub position: MarkerPosition,
+    pub label: String,
+    pub icon: Icon,
+    pub color: [f32; 4], // linear RGBA, tints icon and label
+    pub size: f32,       // icon size in pixels
+    pub priority: i32,   // higher wins when labels overlap
+}
+
+impl Marker {
+    pub fn new(position: MarkerPosition, label: impl Into<String>) -> Self {
+        Self {
+            position,
+            label: label.into(),
+            icon: Icon::default(),
+            color: [1.0, 0.85, 0.3, 1.0],
+            size: 14.0,
+            priority: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MarkerId(u32);
+
+/// The marker list; ids stay valid until their marker is removed.
+#[derive(Default)]
+pub struct Markers {
+    markers: Vec<(MarkerId, Marker)>,
+    next_id: u32,
+}
+
+impl Markers {
+    pub fn add(&mut self, marker: Marker) -> MarkerId {
+        let id = MarkerId(self.next_id);
+        self.next_id += 1;
+        self.markers.push((id, marker));
+        id
+    }
+
+    pub fn remove(&mut self, id: MarkerId) -> Option<Marker> {
+        let index = self.markers.iter().position(|(i, _)| *i == id)?;
+        Some(self.markers.remove(index).1)
+    }
+
+    pub fn get_mut(&mut self, id: MarkerId) -> Option<&mut Marker> {
+        self.markers.iter_mut().find(|(i, _)| *i == id).map(|(_, m)| m)
+    }
+
+    /// The marker closest to `world`, if one is within `max_distance`.
+    pub fn nearest(
+        &self,
+        world: DVec3,
+        planet_center: DVec3,
+        planet_radius: f64,
+        max_distance: f64,
+    ) -> Option<MarkerId> {
+        self.iter()
+            .map(|(id, m)| (id, m.position.world(planet_center, planet_radius).distance(world)))
+            .filter(|&(_, distance)| distance <= max_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+
+    pub fn clear(&mut self) {
+        self.markers.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.markers.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MarkerId, &Marker)> {
+        self.markers.iter().map(|(id, m)| (*id, m))
+    }
+}
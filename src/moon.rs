@@ -0,0 +1,179 @@
+use glam::{DQuat, DVec3, Vec2};
+
+use crate::gpu::create_texture_rgba8;
+use crate::gpu::material::{Material, MaterialDesc, MaterialFactors};
+use crate::gpu::scene_renderer::{MaterialId, MeshId, SceneRenderer};
+use crate::scene::{NodeId, SceneGraph, Transform};
+use crate::vertex;
+
+const ORBIT_RADIUS: f64 = 3.0; // planet radii
+const MOON_RADIUS: f64 = 0.27; // planet radii
+const ORBIT_TILT: f64 = 0.09; // radians
+const ORBIT_SPEED: f64 = 0.15; // radians per second
+const STATION_SPEED: f64 = 0.8; // radians per second
+const CRATER_MAP_SIZE: u32 = 128;
+
+/// Meshes and materials of the moon system, registered once and shared by
+/// every spawn.
+pub struct MoonAssets {
+    sphere: MeshId,
+    cube: MeshId,
+    rock: MaterialId,   // textured
+    hull: MaterialId,
+    panel: MaterialId,
+    beacon: MaterialId, // unlit
+}
+
+impl MoonAssets {
+    pub fn load(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        renderer: &mut SceneRenderer,
+        material_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let (sphere_vertices, sphere_indices) = vertex::cube_sphere(16);
+        let sphere = renderer.add_mesh(device, &sphere_vertices, &sphere_indices);
+        let cube = renderer.add_mesh(device, vertex::VERTICES, vertex::INDICES);
+
+        let crater_map = create_texture_rgba8(
+            device,
+            queue,
+            "Crater Map",
+            &crater_map(CRATER_MAP_SIZE),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        let mut add = |name, factors: MaterialFactors, base_color_map, unlit| {
+            let material = Material::new(device, queue, material_layout, MaterialDesc {
+                name,
+                factors: &factors.bytes(),
+                base_color_map,
+                sampler: None,
+                unlit,
+            });
+            renderer.add_material(device, material)
+        };
+        Self {
+            sphere,
+            cube,
+            rock: add(
+                "moon rock",
+                MaterialFactors { base_color: [0.6, 0.58, 0.55, 1.0], ..Default::default() },
+                Some(crater_map.create_view(&Default::default())),
+                false,
+            ),
+            hull: add("station hull", MaterialFactors { base_color: [0.85, 0.85, 0.9, 1.0], ..Default::default() }, None, false),
+            panel: add(
+                "solar panel",
+                MaterialFactors { base_color: [0.15, 0.25, 0.6, 1.0], emissive: [0.01, 0.02, 0.05] },
+                None,
+                false,
+            ),
+            beacon: add("beacon", MaterialFactors { base_color: [0.0; 4], emissive: [1.0, 0.15, 0.05] }, None, true),
+        }
+    }
+}
+
+/// A moon on a circular orbit around the planet, with a station orbiting
+/// the moon. Only the two orbit pivots are animated; the moon, the station
+/// and its panels follow through their parents.
+///
+/// ```text
+/// planet
+/// └── moon orbit         (spins about the tilted orbit axis)
+///     └── moon           (offset by the orbit radius, scaled to the moon)
+///         └── station orbit
+///             └── station
+///                 ├── panel
+///                 ├── panel
+///                 └── beacon
+/// ```
+pub struct MoonSystem {
+    orbit: NodeId,
+    station_orbit: NodeId,
+}
+
+impl MoonSystem {
+    pub fn spawn(scene: &mut SceneGraph, assets: &MoonAssets, planet: NodeId, planet_radius: f64) -> Option<Self> {
+        let orbit = scene.add(Some(planet), Transform::default())?;
+        let moon = scene.add(
+            Some(orbit),
+            Transform::from_translation(DVec3::X * ORBIT_RADIUS * planet_radius)
+                .with_scale(DVec3::splat(MOON_RADIUS * planet_radius)),
+        )?;
+        let station_orbit = scene.add(Some(moon), Transform::default())?;
+        // the rest is in moon radii
+        let station = scene.add(
+            Some(station_orbit),
+            Transform::from_translation(DVec3::new(0.0, 0.4, 1.6)).with_scale(DVec3::splat(0.06)),
+        )?;
+        let mut parts = vec![(moon, assets.sphere, assets.rock), (station, assets.cube, assets.hull)];
+        for side in [-1.0, 1.0] {
+            let panel = scene.add(
+                Some(station),
+                Transform::from_translation(DVec3::X * side * 3.0).with_scale(DVec3::new(2.0, 0.05, 0.8)),
+            )?;
+            parts.push((panel, assets.cube, assets.panel));
+        }
+        let beacon = scene.add(
+            Some(station),
+            Transform::from_translation(DVec3::Y * 1.3).with_scale(DVec3::splat(0.3)),
+        )?;
+        parts.push((beacon, assets.cube, assets.beacon));
+
+        for (node, mesh, material) in parts {
+            let node = scene.node_mut(node)?;
+            node.mesh = Some(mesh);
+            node.material = Some(material);
+        }
+
+        Some(Self { orbit, station_orbit })
+    }
+
+    /// Moves the moon and the station to where they are at `time` seconds.
+    pub fn update(&self, scene: &mut SceneGraph, time: f64) {
+        if let Some(orbit) = scene.transform_mut(self.orbit) {
+            orbit.rotation = DQuat::from_rotation_x(ORBIT_TILT) * DQuat::from_rotation_y(ORBIT_SPEED * time);
+        }
+        if let Some(station_orbit) = scene.transform_mut(self.station_orbit) {
+            station_orbit.rotation = DQuat::from_rotation_y(STATION_SPEED * time);
+        }
+    }
+
+    pub fn despawn(self, scene: &mut SceneGraph) {
+        scene.remove(self.orbit);
+    }
+}
+
+/// Gray regolith with scattered craters, each a dark floor inside a bright
+/// rim. Wraps around at the edges.
+fn crater_map(size: u32) -> image::RgbaImage {
+    let mut seed = 0x6d6f6f6eu32;
+    let mut random = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32
+    };
+    let craters: Vec<(Vec2, f32)> = (0..40)
+        .map(|_| (Vec2::new(random(), random()) * size as f32, 2.0 + 10.0 * random().powi(3)))
+        .collect();
+    let noise: Vec<f32> = (0..size * size).map(|_| random()).collect();
+
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        let p = Vec2::new(x as f32, y as f32);
+        let mut shade = 0.8 + 0.1 * noise[(y * size + x) as usize];
+        for &(center, radius) in &craters {
+            // shortest offset on the wrapped texture
+            let d = p - center;
+            let d = d - (d / size as f32).round() * size as f32;
+            let r = d.length() / radius;
+            if r < 1.0 {
+                shade *= 0.75;
+            } else if r < 1.25 {
+                shade *= 1.15;
+            }
+        }
+        let v = (shade.min(1.0) * 255.0) as u8;
+        image::Rgba([v, v, v, 255])
+    })
+}
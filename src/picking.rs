@@ -0,0 +1,87 @@
+use std::fmt;
+
+use glam::{DVec2, DVec3};
+
+use crate::controller::yaw_pitch;
+use crate::scene::NodeId;
+
+/// Object id written by the pick pass for the planet; 0 means nothing.
+pub const PLANET_ID: u32 = 1;
+/// Scene graph nodes are numbered from here on, in their drawing order.
+pub const FIRST_NODE_ID: u32 = 2;
+
+/// What lies under a picked pixel.
+#[derive(Copy, Clone, Debug)]
+pub struct PickResult {
+    pub object_id: u32,
+    pub node: Option<NodeId>, // the scene graph node hit, None for the planet
+    pub position: DVec3, // world space
+    pub normal: DVec3,   // world space, unit
+    pub latitude: Option<f64>,  // degrees, planet-local; None off the planet
+    pub longitude: Option<f64>, // degrees, same convention as the orbit yaw
+    pub texel: Option<[u8; 4]>, // surface texture at the hit, sRGB
+}
+
+impl PickResult {
+    /// Fills in the planet-relative fields for a hit at `position`.
+    pub fn on_planet(
+        position: DVec3,
+        normal: DVec3,
+        planet_center: DVec3,
+        texture: &image::RgbaImage,
+    ) -> Self {
+        let up = (position - planet_center).normalize();
+        let (longitude, latitude) = yaw_pitch(up);
+        Self {
+            object_id: PLANET_ID,
+            node: None,
+            position,
+            normal,
+            latitude: Some(latitude.to_degrees()),
+            longitude: Some(longitude.to_degrees()),
+            texel: Some(sample_octahedral(texture, up)),
+        }
+    }
+
+    /// A hit on scene graph node `node`, which has no surface readout.
+    pub fn on_node(object_id: u32, node: Option<NodeId>, position: DVec3, normal: DVec3) -> Self {
+        Self { object_id, node, position, normal, latitude: None, longitude: None, texel: None }
+    }
+}
+
+impl fmt::Display for PickResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (Some(latitude), Some(longitude), Some([r, g, b, _])) = (self.latitude, self.longitude, self.texel) else {
+            return write!(f, "object {}", self.object_id);
+        };
+        write!(
+            f,
+            "{:.4}°{} {:.4}°{}  #{r:02x}{g:02x}{b:02x}",
+            latitude.abs(),
+            if latitude >= 0.0 { 'N' } else { 'S' },
+            longitude.abs(),
+            if longitude >= 0.0 { 'E' } else { 'W' },
+        )
+    }
+}
+
+/// CPU twin of `encode_octahedral` in the planet shaders.
+pub fn encode_octahedral(n: DVec3) -> DVec2 {
+    let abs_n = n.abs();
+    let inv_sum = 1.0 / (abs_n.x + abs_n.y + abs_n.z + 1e-6);
+    let mut uv = DVec2::new(n.x, n.y) * inv_sum;
+    if n.z < 0.0 {
+        uv = (1.0 - DVec2::new(uv.y, uv.x).abs()) * uv.signum();
+    }
+    uv * 0.5 + 0.5
+}
+
+/// Nearest texel of an octahedral texture in direction `dir`. `texture` is
+/// the image as uploaded, so uv (0, 0) is its first pixel.
+pub fn sample_octahedral(texture: &image::RgbaImage, dir: DVec3) -> [u8; 4] {
+    let uv = encode_octahedral(dir);
+    let (w, h) = texture.dimensions();
+    let x = ((uv.x * w as f64) as u32).min(w - 1);
+    let y = ((uv.y * h as f64) as u32).min(h - 1);
+    texture.get_pixel(x, y).0
+}
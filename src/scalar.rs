@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+
+/// Values on a regular latitude/longitude grid, cell-centered. Row 0 is the
+/// northernmost band, column 0 starts at 180°W and columns run east. NaN
+/// marks cells without data.
+pub struct ScalarGrid {
+    pub width: u32,  // longitude cells
+    pub height: u32, // latitude cells
+    pub values: Vec<f32>,
+}
+
+impl ScalarGrid {
+    /// `.csv` files hold one grid row per line; anything else is read as
+    /// the binary raster format (see `from_raster`).
+    pub fn load(path: &Path) -> Result<Self> {
+        let grid = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("csv")) {
+            let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+            Self::from_csv(&text)
+        } else {
+            let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+            Self::from_raster(&bytes)
+        };
+        grid.with_context(|| format!("loading {}", path.display()))
+    }
+
+    /// Comma-separated numbers, one line per row; empty cells and `nan`
+    /// are missing data. Lines starting with `#` are comments.
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut values = Vec::new();
+        let mut width = None;
+        let mut height = 0;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let row: Vec<f32> = line
+                .split(',')
+                .map(|cell| match cell.trim() {
+                    "" => Ok(f32::NAN),
+                    cell => cell.parse::<f32>(),
+                })
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("line {}", number + 1))?;
+            match width {
+                None => width = Some(row.len()),
+                Some(w) if w != row.len() => bail!("line {} has {} values, expected {w}", number + 1, row.len()),
+                Some(_) => {}
+            }
+            values.extend(row);
+            height += 1;
+        }
+        let width = width.context("no data rows")?;
+        Ok(Self { width: width as u32, height, values })
+    }
+
+    /// Little-endian `u32` width, `u32` height, then `width * height` `f32`
+    /// values in row order.
+    pub fn from_raster(bytes: &[u8]) -> Result<Self> {
+        ensure!(bytes.len() >= 8, "raster header is truncated");
+        let word = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let (width, height) = (word(0), word(4));
+        let body = &bytes[8..];
+        ensure!(width > 0 && height > 0, "raster is empty");
+        ensure!(
+            body.len() == width as usize * height as usize * 4,
+            "raster of {width}x{height} needs {} bytes of values, found {}",
+            width as usize * height as usize * 4,
+            body.len(),
+        );
+        let values = body.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        Ok(Self { width, height, values })
+    }
+
+    /// Smallest and largest value, ignoring missing data.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        self.values
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold(None, |range, &v| match range {
+                None => Some((v, v)),
+                Some((min, max)) => Some((min.min(v), max.max(v))),
+            })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Magma,
+    Diverging, // blue–white–red, for anomalies around a midpoint
+}
+
+impl Colormap {
+    pub fn next(self) -> Self {
+        match self {
+            Colormap::Viridis => Colormap::Magma,
+            Colormap::Magma => Colormap::Diverging,
+            Colormap::Diverging => Colormap::Viridis,
+        }
+    }
+
+    /// Evenly spaced sRGB control points.
+    fn stops(self) -> &'static [[u8; 3]] {
+        match self {
+            Colormap::Viridis => &[
+                [68, 1, 84], [70, 50, 126], [59, 82, 139], [44, 114, 142], [33, 145, 140],
+                [40, 174, 128], [94, 201, 98], [173, 220, 48], [253, 231, 37],
+            ],
+            Colormap::Magma => &[
+                [0, 0, 4], [28, 16, 68], [79, 18, 123], [129, 37, 129], [181, 54, 122],
+                [229, 80, 100], [251, 135, 97], [254, 194, 135], [252, 253, 191],
+            ],
+            Colormap::Diverging => &[
+                [59, 76, 192], [130, 165, 251], [221, 221, 221], [244, 154, 123], [180, 4, 38],
+            ],
+        }
+    }
+
+    /// `size` sRGB texels from the low to the high end.
+    pub fn lut(self, size: usize) -> Vec<[u8; 4]> {
+        let stops = self.stops();
+        (0..size)
+            .map(|i| {
+                let t = i as f32 / (size - 1) as f32 * (stops.len() - 1) as f32;
+                let (a, b) = (stops[t.floor() as usize], stops[(t.ceil() as usize).min(stops.len() - 1)]);
+                let f = t.fract();
+                let mix = |c: usize| (a[c] as f32 + (b[c] as f32 - a[c] as f32) * f).round() as u8;
+                [mix(0), mix(1), mix(2), 255]
+            })
+            .collect()
+    }
+}
+
+/// `data.toml`: which grid to show and how.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScalarConfig {
+    pub path: PathBuf, // relative to the config file
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub units: String,
+    #[serde(default)]
+    pub colormap: Colormap,
+    #[serde(default)]
+    pub range: Option<[f32; 2]>, // defaults to the data's own range
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+fn default_opacity() -> f32 {
+    0.8
+}
+
+impl ScalarConfig {
+    /// `None` if there is no config file.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+        let config: ScalarConfig = toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Ok(Some(ScalarConfig { path: base.join(&config.path), ..config }))
+    }
+}
@@ -0,0 +1,184 @@
+use glam::{DMat4, DQuat, DVec3};
+
+use crate::gpu::scene_renderer::{MaterialId, MeshId};
+
+/// Position, orientation and size of a node relative to its parent.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: DVec3,
+    pub rotation: DQuat,
+    pub scale: DVec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self { translation: DVec3::ZERO, rotation: DQuat::IDENTITY, scale: DVec3::ONE }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: DVec3) -> Self {
+        Self { translation, ..Default::default() }
+    }
+
+    pub fn with_scale(mut self, scale: DVec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn matrix(&self) -> DMat4 {
+        DMat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// Stays valid until its node is removed; a stale id matches nothing, even
+/// after its slot was reused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+pub struct Node {
+    pub mesh: Option<MeshId>,
+    pub material: Option<MaterialId>, // the renderer's default material if None
+    pub visible: bool, // hides the whole subtree
+
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    local: Transform,
+    world: DMat4,
+    world_visible: bool, // this node and all its ancestors are visible
+    dirty: bool,         // `local` changed since the world matrix was computed
+}
+
+impl Node {
+    /// World matrix as of the last `SceneGraph::update`.
+    pub fn world(&self) -> DMat4 {
+        self.world
+    }
+}
+
+/// A hierarchy of transforms. Each node's world matrix is its parent's world
+/// matrix times its own local transform; `update` recomputes only the nodes
+/// whose transform, or an ancestor's, changed since the previous call.
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<(u32, Option<Node>)>, // generation and node, None while free
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+impl SceneGraph {
+    /// Adds a node under `parent`, or as a new root. Returns None if
+    /// `parent` was removed.
+    pub fn add(&mut self, parent: Option<NodeId>, transform: Transform) -> Option<NodeId> {
+        if let Some(parent) = parent {
+            self.node(parent)?;
+        }
+        let node = Node {
+            mesh: None,
+            material: None,
+            visible: true,
+            parent,
+            children: Vec::new(),
+            local: transform,
+            world: DMat4::IDENTITY,
+            world_visible: false, // not placed until the next `update`
+            dirty: true,
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                let entry = &mut self.nodes[index as usize];
+                entry.1 = Some(node);
+                NodeId { index, generation: entry.0 }
+            }
+            None => {
+                self.nodes.push((0, Some(node)));
+                NodeId { index: self.nodes.len() as u32 - 1, generation: 0 }
+            }
+        };
+        match parent {
+            Some(parent) => self.node_mut(parent)?.children.push(id),
+            None => self.roots.push(id),
+        }
+        Some(id)
+    }
+
+    /// Removes a node and everything below it; false if it was already gone.
+    pub fn remove(&mut self, id: NodeId) -> bool {
+        let Some(node) = self.node(id) else {
+            return false;
+        };
+        match node.parent {
+            Some(parent) => {
+                if let Some(parent) = self.node_mut(parent) {
+                    parent.children.retain(|&child| child != id);
+                }
+            }
+            None => self.roots.retain(|&root| root != id),
+        }
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let entry = &mut self.nodes[id.index as usize];
+            if let Some(node) = entry.1.take() {
+                stack.extend(node.children);
+                entry.0 = entry.0.wrapping_add(1);
+                self.free.push(id.index);
+            }
+        }
+        true
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        match self.nodes.get(id.index as usize)? {
+            (generation, Some(node)) if *generation == id.generation => Some(node),
+            _ => None,
+        }
+    }
+
+    /// For the mesh, material and visibility; the transform goes
+    /// through `transform_mut` so that it is marked dirty.
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        match self.nodes.get_mut(id.index as usize)? {
+            (generation, Some(node)) if *generation == id.generation => Some(node),
+            _ => None,
+        }
+    }
+
+    pub fn transform_mut(&mut self, id: NodeId) -> Option<&mut Transform> {
+        let node = self.node_mut(id)?;
+        node.dirty = true;
+        Some(&mut node.local)
+    }
+
+    /// Recomputes the world matrices of dirty nodes and their descendants.
+    pub fn update(&mut self) {
+        // (node, parent world matrix, parent recomputed, parent visible)
+        let mut stack: Vec<_> = self.roots.iter().map(|&id| (id, DMat4::IDENTITY, false, true)).collect();
+        while let Some((id, parent_world, parent_changed, parent_visible)) = stack.pop() {
+            let Some(node) = self.node_mut(id) else {
+                continue;
+            };
+            let changed = node.dirty || parent_changed;
+            if changed {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+            }
+            node.world_visible = parent_visible && node.visible;
+            let (world, visible) = (node.world, node.world_visible);
+            stack.extend(node.children.iter().map(|&child| (child, world, changed, visible)));
+        }
+    }
+
+    /// Visible nodes that have a mesh, with their world matrices.
+    pub fn drawables(&self) -> impl Iterator<Item = (NodeId, DMat4, MeshId, Option<MaterialId>)> {
+        self.nodes.iter().enumerate().filter_map(|(index, (generation, node))| {
+            let node = node.as_ref()?;
+            let mesh = node.mesh.filter(|_| node.world_visible)?;
+            let id = NodeId { index: index as u32, generation: *generation };
+            Some((id, node.world, mesh, node.material))
+        })
+    }
+}
@@ -0,0 +1,193 @@
+// Rayleigh + Mie single scattering, ray-marched per pixel
+// Marching happens in planet-centered space; the view-projection is camera
+// relative, so unprojected points are offset by the camera position
+
+struct Atmosphere {
+    inv_view_proj         : mat4x4<f32>,
+    camera_pos            : vec3<f32>,
+    planet_radius         : f32,
+    sun_dir               : vec3<f32>,
+    atmosphere_radius     : f32,
+    sun_color             : vec3<f32>,
+    sun_intensity         : f32,
+    rayleigh              : vec3<f32>,
+    rayleigh_scale_height : f32,
+    mie                   : f32,
+    mie_scale_height      : f32,
+    mie_g                 : f32,
+    log_depth_coef        : f32,
+    camera_forward        : vec3<f32>,
+    depth_mode            : u32,
+};
+
+@group(0) @binding(0) var<uniform> atmo : Atmosphere;
+@group(0) @binding(1) var scene_depth   : texture_depth_2d;
+
+const PI            : f32 = 3.14159265;
+const VIEW_SAMPLES  : i32 = 32;
+const LIGHT_SAMPLES : i32 = 8;
+const NO_HIT        : f32 = 1e30;
+
+// must match DepthMode::shader_index
+const DEPTH_STANDARD    : u32 = 0u;
+const DEPTH_REVERSE_Z   : u32 = 1u;
+const DEPTH_LOGARITHMIC : u32 = 2u;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) ndc       : vec2<f32>,
+};
+
+// single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) i : u32) -> VSOut {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    var out: VSOut;
+    out.pos = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+/// Returns (near, far) distances along the ray, or (NO_HIT, -NO_HIT) on a miss.
+fn ray_sphere(origin : vec3<f32>, dir : vec3<f32>, radius : f32) -> vec2<f32> {
+    let b = dot(origin, dir);
+    let c = dot(origin, origin) - radius * radius;
+    let disc = b * b - c;
+    if (disc < 0.0) {
+        return vec2<f32>(NO_HIT, -NO_HIT);
+    }
+    let s = sqrt(disc);
+    return vec2<f32>(-b - s, -b + s);
+}
+
+/// Rayleigh and Mie densities at a point, relative to sea level.
+fn densities(p : vec3<f32>) -> vec2<f32> {
+    let h = max(length(p) - atmo.planet_radius, 0.0);
+    return exp(-h / vec2<f32>(atmo.rayleigh_scale_height, atmo.mie_scale_height));
+}
+
+fn phase_rayleigh(mu : f32) -> f32 {
+    return 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+}
+
+fn phase_mie(mu : f32, g : f32) -> f32 {
+    let g2 = g * g;
+    let denom = pow(1.0 + g2 - 2.0 * g * mu, 1.5);
+    return 3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + mu * mu)) / ((2.0 + g2) * denom);
+}
+
+/// Optical depth (rayleigh, mie) from `p` towards the sun, or -1 if the
+/// planet blocks the sun.
+fn light_optical_depth(p : vec3<f32>) -> vec2<f32> {
+    if (ray_sphere(p, atmo.sun_dir, atmo.planet_radius).x > 0.0) {
+        return vec2<f32>(-1.0);
+    }
+    let t_exit = ray_sphere(p, atmo.sun_dir, atmo.atmosphere_radius).y;
+    let step = t_exit / f32(LIGHT_SAMPLES);
+    var depth = vec2<f32>(0.0);
+    for (var i = 0; i < LIGHT_SAMPLES; i++) {
+        depth += densities(p + atmo.sun_dir * (f32(i) + 0.5) * step) * step;
+    }
+    return depth;
+}
+
+/// True where the depth buffer still holds its clear value.
+fn is_sky(depth : f32) -> bool {
+    if (atmo.depth_mode == DEPTH_REVERSE_Z) {
+        return depth <= 0.0;
+    }
+    return depth >= 1.0;
+}
+
+/// Camera-relative point at a given NDC depth.
+fn unproject(ndc : vec2<f32>, depth : f32) -> vec3<f32> {
+    let p = atmo.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return p.xyz / p.w;
+}
+
+/// NDC depth of the near plane (the matrix is reverse-Z unless standard).
+fn near_depth() -> f32 {
+    return select(1.0, 0.0, atmo.depth_mode == DEPTH_STANDARD);
+}
+
+/// Distance from the ray start on the near plane to the surface behind this pixel.
+fn scene_distance(ndc : vec2<f32>, depth : f32, near : vec3<f32>, dir : vec3<f32>) -> f32 {
+    if (atmo.depth_mode == DEPTH_LOGARITHMIC) {
+        // invert depth = log2(1 + w) * coef, w being the view-space depth;
+        // only perspective uses this mode, so rays start at the eye
+        let w = exp2(depth / atmo.log_depth_coef) - 1.0;
+        return w / max(dot(dir, atmo.camera_forward), 1e-4) - length(near);
+    }
+    return distance(unproject(ndc, depth), near);
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    // reconstruct the world-space point behind this pixel
+    let dims  = textureDimensions(scene_depth);
+    let pixel = vec2<i32>(clamp(
+        (in.ndc * vec2<f32>(0.5, -0.5) + 0.5) * vec2<f32>(dims),
+        vec2<f32>(0.0),
+        vec2<f32>(dims) - 1.0,
+    ));
+    let depth = textureLoad(scene_depth, pixel, 0);
+
+    // the ray starts on the near plane, so orthographic views get parallel
+    // rays; any depth strictly inside the range gives the direction
+    let near   = unproject(in.ndc, near_depth());
+    let origin = atmo.camera_pos + near;
+    let dir    = normalize(unproject(in.ndc, 0.5) - near);
+
+    // clip the view ray to the atmosphere shell, the planet and the scene
+    let shell = ray_sphere(origin, dir, atmo.atmosphere_radius);
+    if (shell.y < 0.0) {
+        return vec4<f32>(0.0);
+    }
+    let t_start = max(shell.x, 0.0);
+    var t_end = shell.y;
+    let ground = ray_sphere(origin, dir, atmo.planet_radius);
+    if (ground.x > 0.0) {
+        t_end = min(t_end, ground.x);
+    }
+    if (!is_sky(depth)) {
+        t_end = min(t_end, scene_distance(in.ndc, depth, near, dir));
+    }
+    if (t_end <= t_start) {
+        return vec4<f32>(0.0);
+    }
+
+    let step = (t_end - t_start) / f32(VIEW_SAMPLES);
+    var view_depth = vec2<f32>(0.0);
+    var sum_r = vec3<f32>(0.0);
+    var sum_m = vec3<f32>(0.0);
+
+    for (var i = 0; i < VIEW_SAMPLES; i++) {
+        let p = origin + dir * (t_start + (f32(i) + 0.5) * step);
+        let d = densities(p) * step;
+        view_depth += d;
+
+        let light_depth = light_optical_depth(p);
+        if (light_depth.x < 0.0) {
+            continue; // in the planet's shadow
+        }
+
+        let tau = atmo.rayleigh * (view_depth.x + light_depth.x)
+                + vec3<f32>(atmo.mie * 1.1 * (view_depth.y + light_depth.y));
+        let attenuation = exp(-tau);
+        sum_r += d.x * attenuation;
+        sum_m += d.y * attenuation;
+    }
+
+    let mu = dot(dir, atmo.sun_dir);
+    let inscatter = atmo.sun_intensity * atmo.sun_color * (
+        sum_r * atmo.rayleigh * phase_rayleigh(mu) +
+        sum_m * atmo.mie * phase_mie(mu, atmo.mie_g)
+    );
+
+    let transmittance = exp(-(atmo.rayleigh * view_depth.x + vec3<f32>(atmo.mie * 1.1 * view_depth.y)));
+    let coverage = 1.0 - dot(transmittance, vec3<f32>(1.0 / 3.0));
+
+    // HDR; exposure and tonemapping happen in post
+    return vec4<f32>(inscatter, coverage);
+}
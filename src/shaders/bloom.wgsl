@@ -0,0 +1,90 @@
+// Bloom: the bright parts of the HDR scene are blurred down a mip chain
+// (prefilter + 13-tap downsample) and back up (tent upsample, added onto
+// each level). The tonemap pass mixes the result back in.
+
+struct Post {
+    exposure         : f32, // linear scale
+    tonemapper       : u32,
+    bloom_intensity  : f32, // 0 while bloom is off
+    bloom_threshold  : f32,
+    bloom_knee       : f32,
+    grading_strength : f32,
+};
+
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var input_sampler : sampler;
+@group(0) @binding(2) var<uniform> post : Post;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) uv        : vec2<f32>,
+};
+
+// single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) i : u32) -> VSOut {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    var out: VSOut;
+    out.pos = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv  = vec2<f32>(uv.x, 1.0 - uv.y); // texture space, y down
+    return out;
+}
+
+fn sample(uv : vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(input_texture, input_sampler, uv, 0.0).rgb;
+}
+
+/// 13 taps over a 4x4 texel area, weighted to avoid the blockiness of a
+/// plain box filter (Jimenez, "Next Generation Post Processing in Call of
+/// Duty: Advanced Warfare").
+fn downsample(uv : vec2<f32>) -> vec3<f32> {
+    let t = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let a = sample(uv + t * vec2(-2.0, -2.0));
+    let b = sample(uv + t * vec2( 0.0, -2.0));
+    let c = sample(uv + t * vec2( 2.0, -2.0));
+    let d = sample(uv + t * vec2(-2.0,  0.0));
+    let e = sample(uv);
+    let f = sample(uv + t * vec2( 2.0,  0.0));
+    let g = sample(uv + t * vec2(-2.0,  2.0));
+    let h = sample(uv + t * vec2( 0.0,  2.0));
+    let i = sample(uv + t * vec2( 2.0,  2.0));
+    let j = sample(uv + t * vec2(-1.0, -1.0));
+    let k = sample(uv + t * vec2( 1.0, -1.0));
+    let l = sample(uv + t * vec2(-1.0,  1.0));
+    let m = sample(uv + t * vec2( 1.0,  1.0));
+    return e * 0.125
+         + (a + c + g + i) * 0.03125
+         + (b + d + f + h) * 0.0625
+         + (j + k + l + m) * 0.125;
+}
+
+@fragment
+fn fs_prefilter(in : VSOut) -> @location(0) vec4<f32> {
+    // keep stray infinities from flooding the chain
+    let color = min(downsample(in.uv), vec3(65000.0));
+
+    // soft threshold: a quadratic ramp over [threshold - knee, threshold + knee]
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = max(post.bloom_knee, 1e-5);
+    var soft = clamp(brightness - post.bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - post.bloom_threshold) / max(brightness, 1e-5);
+    return vec4(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in : VSOut) -> @location(0) vec4<f32> {
+    return vec4(downsample(in.uv), 1.0);
+}
+
+/// 3x3 tent over the smaller level, blended additively into the larger one.
+@fragment
+fn fs_upsample(in : VSOut) -> @location(0) vec4<f32> {
+    let t = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    var sum = sample(in.uv) * 4.0;
+    sum += (sample(in.uv + t * vec2( 0.0, -1.0)) + sample(in.uv + t * vec2(-1.0, 0.0))
+          + sample(in.uv + t * vec2( 1.0,  0.0)) + sample(in.uv + t * vec2( 0.0, 1.0))) * 2.0;
+    sum += sample(in.uv + t * vec2(-1.0, -1.0)) + sample(in.uv + t * vec2(1.0, -1.0))
+         + sample(in.uv + t * vec2(-1.0,  1.0)) + sample(in.uv + t * vec2(1.0,  1.0));
+    return vec4(sum / 16.0, 1.0);
+}
@@ -0,0 +1,97 @@
+// Cloud shell: unit sphere mesh scaled to the cloud radius, rotating over time
+// Coverage lives in an octahedral texture, same layout as the planet texture
+
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
+@group(0) @binding(0) var<uniform> camera : Camera;
+
+// The planet node's model matrix, rebased on the camera every frame.
+struct Object {
+    model  : mat4x4<f32>,
+    normal : mat3x3<f32>, // inverse transpose of the model's upper 3x3
+};
+@group(2) @binding(0) var<uniform> object : Object;
+
+struct Light { dir : vec3<f32>, color : vec3<f32> };
+@group(0) @binding(1) var<uniform> light : Light;
+
+struct Globals { time : f32, center : vec2<f32>, zoom : f32 };
+@group(0) @binding(2) var<uniform> globals : Globals;
+
+struct Clouds {
+    radius          : f32,
+    rotation_speed  : f32,
+    opacity         : f32,
+    shadow_strength : f32,
+    shadow_softness : f32,
+};
+@group(3) @binding(0) var<uniform> clouds : Clouds;
+@group(3) @binding(1) var cloud_texture : texture_2d<f32>;
+@group(3) @binding(2) var cloud_sampler : sampler;
+
+fn encode_octahedral(n : vec3<f32>) -> vec2<f32> {
+    let abs_n   = abs(n);
+    let inv_sum = 1.0 / (abs_n.x + abs_n.y + abs_n.z + 1e-6);
+    var uv      = n.xy * inv_sum;
+    if (n.z < 0.0) {
+        uv = (1.0 - abs(vec2<f32>(uv.y, uv.x))) * sign(uv);
+    }
+    return uv * 0.5 + vec2<f32>(0.5);
+}
+
+/// Rotates a direction into the cloud layer's frame (spinning around +Y).
+fn cloud_frame(dir : vec3<f32>) -> vec3<f32> {
+    let a = globals.time * clouds.rotation_speed;
+    let c = cos(a);
+    let s = sin(a);
+    return vec3<f32>(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+}
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) dir       : vec3<f32>,
+    @location(3) log_z     : f32, // 1 + view depth, for logarithmic depth
+};
+
+struct FSOutLog {
+    @location(0) color       : vec4<f32>,
+    @builtin(frag_depth) depth : f32,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>
+) -> VSOut {
+    let world_pos = object.model * vec4(normalize(position) * clouds.radius, 1.0);
+    var out: VSOut;
+    out.pos = camera.view_proj * world_pos;
+    out.dir = normalize((object.model * vec4(normal, 0.0)).xyz);
+    out.log_z = 1.0 + out.pos.w;
+    return out;
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Variant used in logarithmic depth mode.
+@fragment
+fn fs_main_log(in : VSOut) -> FSOutLog {
+    return FSOutLog(shade(in), log2(in.log_z) * camera.log_depth_coef);
+}
+
+fn shade(in : VSOut) -> vec4<f32> {
+    let N = normalize(in.dir);
+    let L = normalize(-light.dir);
+
+    let uv       = encode_octahedral(cloud_frame(N));
+    let coverage = textureSample(cloud_texture, cloud_sampler, uv).r;
+
+    // clouds scatter a lot, so wrap the lighting a bit past the terminator
+    let diff    = clamp(dot(N, L) * 0.8 + 0.2, 0.0, 1.0);
+    let ambient = 0.1;
+    let lit     = vec3<f32>(ambient) + diff * light.color;
+    return vec4<f32>(lit, coverage * clouds.opacity);
+}
@@ -0,0 +1,60 @@
+// Color grading through a 3D LUT. The LUT maps sRGB-encoded colors to
+// sRGB-encoded colors, as most grading tools export them.
+
+struct Post {
+    exposure         : f32, // linear scale
+    tonemapper       : u32,
+    bloom_intensity  : f32, // 0 while bloom is off
+    bloom_threshold  : f32,
+    bloom_knee       : f32,
+    grading_strength : f32,
+};
+
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var input_sampler : sampler;
+@group(0) @binding(2) var<uniform> post : Post;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) uv        : vec2<f32>,
+};
+
+// single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) i : u32) -> VSOut {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    var out: VSOut;
+    out.pos = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv  = vec2<f32>(uv.x, 1.0 - uv.y); // texture space, y down
+    return out;
+}
+
+fn sample(uv : vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(input_texture, input_sampler, uv, 0.0).rgb;
+}
+@group(0) @binding(3) var lut_texture : texture_3d<f32>;
+
+// set for pipelines writing a non-sRGB output, which then encode themselves
+override ENCODE_SRGB : bool = false;
+
+fn linear_to_srgb(c : vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+fn output(c : vec3<f32>) -> vec4<f32> {
+    return vec4(select(c, linear_to_srgb(c), ENCODE_SRGB), 1.0);
+}
+
+fn srgb_to_linear(c : vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    let color = sample(in.uv);
+    // texel centers span [0.5, size - 0.5]
+    let size  = f32(textureDimensions(lut_texture).x);
+    let coord = linear_to_srgb(clamp(color, vec3(0.0), vec3(1.0))) * (size - 1.0) / size + 0.5 / size;
+    let graded = srgb_to_linear(textureSampleLevel(lut_texture, input_sampler, coord, 0.0).rgb);
+    return output(mix(color, graded, post.grading_strength));
+}
@@ -1,11 +1,52 @@
 // Octahedral‑to‑cube continuous mapping fragment shader
 // One square texture (octahedral layout) → seamless cube‑sphere planet
 
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
+@group(0) @binding(0) var<uniform> camera : Camera;
+
+// The planet node's model matrix; the cloud shell is drawn with it too.
+struct Object {
+    model  : mat4x4<f32>,
+    normal : mat3x3<f32>, // inverse transpose of the model's upper 3x3
+};
+@group(2) @binding(0) var<uniform> object : Object;
+
 struct Light { dir : vec3<f32>, color : vec3<f32> };
 
-@group(0) @binding(2) var<uniform> light            : Light;
-@group(0) @binding(3) var texture_data    : texture_2d<f32>;
-@group(0) @binding(4) var texture_sampler : sampler;
+@group(0) @binding(1) var<uniform> light            : Light;
+
+struct Globals {
+    time        : f32,
+    center      : vec2<f32>,
+    zoom        : f32,
+    debug_view  : u32, // one of the VIEW_* constants
+    depth_near  : f32, // view depths drawn white and black in VIEW_DEPTH
+    depth_far   : f32,
+};
+@group(0) @binding(2) var<uniform> globals : Globals;
+
+struct Clouds {
+    radius          : f32,
+    rotation_speed  : f32,
+    opacity         : f32,
+    shadow_strength : f32,
+    shadow_softness : f32,
+};
+@group(3) @binding(0) var<uniform> clouds : Clouds;
+@group(3) @binding(1) var cloud_texture : texture_2d<f32>;
+@group(3) @binding(2) var cloud_sampler : sampler;
+
+// The planet material: its factors are tunables, edited live from the debug
+// panel and saved to shader_params.toml.
+// Annotations: @range(min, max), @default(…), @color, @toggle.
+struct Params {
+    ambient       : f32,        // @range(0.0, 1.0) @default(0.1)
+    ambient_color : vec3<f32>,  // @color @default(1.0, 1.0, 1.0)
+    cloud_shadows : u32,        // @toggle @default(1)
+};
+@group(1) @binding(0) var<uniform> params : Params;
+@group(1) @binding(1) var texture_data    : texture_2d<f32>;
+@group(1) @binding(2) var texture_sampler : sampler;
 
 /// Encode a unit vector to octahedral UV in [0,1]².
 fn encode_octahedral(n : vec3<f32>) -> vec2<f32> {
@@ -21,13 +62,141 @@ fn encode_octahedral(n : vec3<f32>) -> vec2<f32> {
     return uv * 0.5 + vec2<f32>(0.5); // → 0…1
 }
 
+/// Same frame as `cloud_frame` in clouds.wgsl.
+fn cloud_frame(dir : vec3<f32>) -> vec3<f32> {
+    let a = globals.time * clouds.rotation_speed;
+    let c = cos(a);
+    let s = sin(a);
+    return vec3<f32>(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+}
+
+/// Fraction of sunlight let through by the cloud layer above `p`, given
+/// like `L` in world orientation (relative to the planet center), which is
+/// how clouds.wgsl samples the cloud texture.
+fn cloud_shadow(p : vec3<f32>, L : vec3<f32>) -> f32 {
+    // where the ray towards the sun leaves the cloud shell
+    let b   = dot(p, L);
+    let c   = dot(p, p) - clouds.radius * clouds.radius;
+    let t   = -b + sqrt(max(b * b - c, 0.0));
+    let uv  = encode_octahedral(cloud_frame(normalize(p + L * t)));
+
+    // small cross-shaped blur keeps the shadow edges soft
+    let o = clouds.shadow_softness;
+    var coverage = textureSample(cloud_texture, cloud_sampler, uv).r * 0.4;
+    coverage += textureSample(cloud_texture, cloud_sampler, uv + vec2<f32>( o, 0.0)).r * 0.15;
+    coverage += textureSample(cloud_texture, cloud_sampler, uv + vec2<f32>(-o, 0.0)).r * 0.15;
+    coverage += textureSample(cloud_texture, cloud_sampler, uv + vec2<f32>(0.0,  o)).r * 0.15;
+    coverage += textureSample(cloud_texture, cloud_sampler, uv + vec2<f32>(0.0, -o)).r * 0.15;
+
+    return 1.0 - coverage * clouds.shadow_strength;
+}
+
 struct FSIn {
-    @location(0) frag_pos : vec3<f32>,  // world or model‑space position
+    @location(0) frag_pos : vec3<f32>,  // planet-local position
     @location(1) normal   : vec3<f32>,
+    @location(3) log_z    : f32,
+    @location(4) grid     : vec2<f32>,
+};
+
+struct FSOutLog {
+    @location(0) color       : vec4<f32>,
+    @builtin(frag_depth) depth : f32,
 };
 
 @fragment
 fn fs_main(in : FSIn) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Variant used in logarithmic depth mode.
+@fragment
+fn fs_main_log(in : FSIn) -> FSOutLog {
+    return FSOutLog(shade(in), log2(in.log_z) * camera.log_depth_coef);
+}
+
+// Must match DebugView::shader_index.
+const VIEW_SHADED        : u32 = 0u;
+const VIEW_WIREFRAME     : u32 = 1u; // only without a line overlay pipeline
+const VIEW_NORMALS       : u32 = 2u;
+const VIEW_OCTAHEDRAL_UV : u32 = 3u;
+const VIEW_DEPTH         : u32 = 4u;
+const VIEW_TEXEL_DENSITY : u32 = 5u;
+
+const WIRE_COLOR : vec3<f32> = vec3<f32>(1.0, 0.8, 0.1);
+
+/// Edge lines drawn by the line overlay pipeline.
+@fragment
+fn fs_wireframe(in : FSIn) -> @location(0) vec4<f32> {
+    return vec4<f32>(WIRE_COLOR, 1.0);
+}
+
+@fragment
+fn fs_wireframe_log(in : FSIn) -> FSOutLog {
+    return FSOutLog(vec4<f32>(WIRE_COLOR, 1.0), log2(in.log_z) * camera.log_depth_coef);
+}
+
+/// Lines along the vertex normals, colored like VIEW_NORMALS.
+@fragment
+fn fs_normal_vectors(in : FSIn) -> @location(0) vec4<f32> {
+    return vec4<f32>(normal_color(in.normal), 1.0);
+}
+
+@fragment
+fn fs_normal_vectors_log(in : FSIn) -> FSOutLog {
+    return FSOutLog(vec4<f32>(normal_color(in.normal), 1.0), log2(in.log_z) * camera.log_depth_coef);
+}
+
+fn normal_color(normal : vec3<f32>) -> vec3<f32> {
+    return normalize(normal) * 0.5 + 0.5;
+}
+
+/// How much of the pixel a triangle edge covers. Every grid cell is split
+/// along its (0,0)–(1,1) diagonal (see `vertex::grid_indices`), which gives
+/// the barycentrics of either half from the position in the cell.
+fn wire_coverage(grid : vec2<f32>) -> f32 {
+    let f    = fract(grid);
+    let hi   = max(f.x, f.y);
+    let lo   = min(f.x, f.y);
+    let bary = vec3<f32>(1.0 - hi, hi - lo, lo);
+    let px   = bary / max(fwidth(bary), vec3<f32>(1e-6));
+    return 1.0 - clamp(min(px.x, min(px.y, px.z)) - 0.5, 0.0, 1.0);
+}
+
+/// Diagnostic surface color for the views that replace shading.
+fn debug_color(in : FSIn) -> vec3<f32> {
+    let uv = encode_octahedral(normalize(in.frag_pos));
+    switch globals.debug_view {
+        case VIEW_NORMALS: {
+            return normal_color(in.normal);
+        }
+        case VIEW_OCTAHEDRAL_UV: {
+            let cells = uv * 16.0;
+            let d     = abs(fract(cells - 0.5) - 0.5) / max(fwidth(cells), vec2<f32>(1e-6));
+            let line  = 1.0 - clamp(min(d.x, d.y), 0.0, 1.0);
+            return mix(vec3<f32>(uv, 0.0), vec3<f32>(1.0), line);
+        }
+        case VIEW_DEPTH: {
+            let depth = (in.log_z - 1.0 - globals.depth_near) / (globals.depth_far - globals.depth_near);
+            return vec3<f32>(1.0 - clamp(depth, 0.0, 1.0));
+        }
+        default: { // VIEW_TEXEL_DENSITY
+            let texel   = uv * vec2<f32>(textureDimensions(texture_data));
+            let per_px  = length(fwidth(texel)) * 0.70710678; // texels across a pixel
+            let t       = clamp(log2(max(per_px, 1e-6)) * 0.5, -1.0, 1.0); // ±1 at 4× off
+            var color   = mix(vec3<f32>(0.1, 0.9, 0.1), vec3<f32>(0.9, 0.1, 0.1), max(t, 0.0));
+            color       = mix(color, vec3<f32>(0.1, 0.2, 0.9), max(-t, 0.0));
+            let cell    = vec2<i32>(floor(texel / 8.0));
+            let checker = f32((cell.x + cell.y) & 1);
+            return color * (0.6 + 0.4 * checker);
+        }
+    }
+}
+
+fn shade(in : FSIn) -> vec4<f32> {
+    if (globals.debug_view >= VIEW_NORMALS) {
+        return vec4<f32>(debug_color(in), 1.0);
+    }
+
     // basic lambert lighting
     let N    = normalize(in.normal);
     let L    = normalize(-light.dir);
@@ -37,7 +206,15 @@ fn fs_main(in : FSIn) -> @location(0) vec4<f32> {
     let uv   = encode_octahedral(normalize(in.frag_pos));
     let tex  = textureSample(texture_data, texture_sampler, uv);
 
-    let ambient = 0.1;
-    let lit     = tex.rgb * (ambient + diff * light.color);
+    var shadow  = 1.0;
+    if (params.cloud_shadows != 0u) {
+        shadow = cloud_shadow((object.model * vec4(in.frag_pos, 0.0)).xyz, L);
+    }
+
+    let ambient = params.ambient * params.ambient_color;
+    var lit     = tex.rgb * (ambient + diff * shadow * light.color);
+    if (globals.debug_view == VIEW_WIREFRAME) {
+        lit = mix(lit, WIRE_COLOR, wire_coverage(in.grid));
+    }
     return vec4<f32>(lit, tex.a);
 }
@@ -1,27 +1,61 @@
-struct Camera { view_proj : mat4x4<f32> };
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
 @group(0) @binding(0) var<uniform> camera : Camera;
 
-struct Model { model : mat4x4<f32> };
-@group(0) @binding(1) var<uniform> modelUBO : Model;
+// The planet node's model matrix, rebased on the camera every frame; only
+// its rotation applies, the chunk offsets carry the translation.
+struct Object {
+    model  : mat4x4<f32>,
+    normal : mat3x3<f32>, // inverse transpose of the model's upper 3x3
+};
+@group(2) @binding(0) var<uniform> object : Object;
 
 struct VSOut {
     @builtin(position) pos : vec4<f32>,
     @location(0) frag_pos  : vec3<f32>,
     @location(1) normal    : vec3<f32>,
-    @location(2) uv        : vec2<f32>
+    @location(2) uv        : vec2<f32>,
+    @location(3) log_z     : f32, // 1 + view depth, for logarithmic depth
+    @location(4) grid      : vec2<f32>, // position in the chunk grid, for the wireframe view
 };
 
+const GRID : u32 = 16u; // lod::GRID
+
+/// Grid coordinates of chunk vertex `index`; skirt vertices take those of
+/// the edge vertex they hang from (see `lod::build_chunk`).
+fn grid_position(index : u32) -> vec2<f32> {
+    let row = GRID + 1u;
+    if (index < row * row) {
+        return vec2<f32>(f32(index % row), f32(index / row));
+    }
+    let edge = (index - row * row) / row;
+    let k    = f32((index - row * row) % row);
+    let n    = f32(GRID);
+    switch edge {
+        case 0u: { return vec2<f32>(k, 0.0); }
+        case 1u: { return vec2<f32>(n, k); }
+        case 2u: { return vec2<f32>(k, n); }
+        default: { return vec2<f32>(0.0, k); }
+    }
+}
+
+// Positions arrive relative to their chunk; the chunk's camera-relative
+// offset was computed in f64 on the CPU, so no large numbers meet in f32.
 @vertex
 fn vs_main(
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
-    @location(2) uv: vec2<f32>
+    @location(2) uv: vec2<f32>,
+    @location(3) chunk_offset: vec3<f32>,
+    @location(4) chunk_origin: vec3<f32>,
+    @builtin(vertex_index) index: u32
 ) -> VSOut {
-    let world_pos = modelUBO.model * vec4(position, 1.0);
+    let view_pos = (object.model * vec4(position, 0.0)).xyz + chunk_offset;
     var out: VSOut;
-    out.pos      = camera.view_proj * world_pos;
-    out.frag_pos = world_pos.xyz;
-    out.normal   = normalize((modelUBO.model * vec4(normal, 0.0)).xyz);
+    out.pos      = camera.view_proj * vec4(view_pos, 1.0);
+    out.frag_pos = chunk_origin + position; // planet-local, only used for shading
+    out.normal   = normalize((object.model * vec4(normal, 0.0)).xyz);
     out.uv = uv;
+    out.log_z = 1.0 + out.pos.w;
+    out.grid = grid_position(index);
     return out;
 }
@@ -0,0 +1,16 @@
+// Copies the first sample of the multisampled scene depth into the
+// single-sample depth texture read by later passes.
+
+@group(0) @binding(0) var scene_depth : texture_depth_multisampled_2d;
+
+// single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) i : u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) pos : vec4<f32>) -> @builtin(frag_depth) f32 {
+    return textureLoad(scene_depth, vec2<i32>(pos.xy), 0);
+}
@@ -0,0 +1,83 @@
+// FXAA: finds edges by local luma contrast and blends along them. The
+// compact variant from Timothy Lottes' FXAA 3.11 console path.
+
+struct Post {
+    exposure         : f32, // linear scale
+    tonemapper       : u32,
+    bloom_intensity  : f32, // 0 while bloom is off
+    bloom_threshold  : f32,
+    bloom_knee       : f32,
+    grading_strength : f32,
+};
+
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var input_sampler : sampler;
+@group(0) @binding(2) var<uniform> post : Post;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) uv        : vec2<f32>,
+};
+
+// single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) i : u32) -> VSOut {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    var out: VSOut;
+    out.pos = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv  = vec2<f32>(uv.x, 1.0 - uv.y); // texture space, y down
+    return out;
+}
+
+fn sample(uv : vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(input_texture, input_sampler, uv, 0.0).rgb;
+}
+
+// set for pipelines writing a non-sRGB output, which then encode themselves
+override ENCODE_SRGB : bool = false;
+
+fn linear_to_srgb(c : vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+fn output(c : vec3<f32>) -> vec4<f32> {
+    return vec4(select(c, linear_to_srgb(c), ENCODE_SRGB), 1.0);
+}
+
+const REDUCE_MIN : f32 = 1.0 / 128.0;
+const REDUCE_MUL : f32 = 1.0 / 8.0;
+const SPAN_MAX   : f32 = 8.0;
+
+/// Perceptual brightness; the input is linear, so take it to roughly gamma
+/// space first.
+fn luma(c : vec3<f32>) -> f32 {
+    return dot(sqrt(c), vec3(0.299, 0.587, 0.114));
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    let t = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let center = sample(in.uv);
+    let nw = luma(sample(in.uv + t * vec2(-1.0, -1.0)));
+    let ne = luma(sample(in.uv + t * vec2( 1.0, -1.0)));
+    let sw = luma(sample(in.uv + t * vec2(-1.0,  1.0)));
+    let se = luma(sample(in.uv + t * vec2( 1.0,  1.0)));
+    let m  = luma(center);
+    let luma_min = min(m, min(min(nw, ne), min(sw, se)));
+    let luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+    // blur across the edge, i.e. along the gradient's perpendicular
+    var dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    let reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    let scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * t;
+
+    let a = 0.5 * (sample(in.uv + dir * (1.0 / 3.0 - 0.5)) + sample(in.uv + dir * (2.0 / 3.0 - 0.5)));
+    let b = a * 0.5 + 0.25 * (sample(in.uv - dir * 0.5) + sample(in.uv + dir * 0.5));
+    let luma_b = luma(b);
+    // the wider blend crossed another edge: fall back to the narrow one
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return output(a);
+    }
+    return output(b);
+}
@@ -0,0 +1,90 @@
+// Latitude/longitude grid drawn over the planet surface. Runs on the planet
+// mesh (vertex stage from cube.vert.wgsl) and only passes where the surface
+// is the nearest thing in the depth buffer.
+
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
+@group(0) @binding(0) var<uniform> camera : Camera;
+
+struct Graticule {
+    color         : vec4<f32>, // rgb + opacity of the regular lines
+    special_color : vec4<f32>, // equator, prime meridian and tropics
+    spacing       : f32,       // degrees between major lines
+    minor_spacing : f32,       // degrees between minor lines, 0 for none
+    line_width    : f32,       // pixels
+    fade_px       : f32,       // lines closer than this on screen fade out
+};
+@group(3) @binding(0) var<uniform> graticule : Graticule;
+
+const TROPIC_LATITUDE : f32 = 23.4365;
+
+struct FSIn {
+    @location(0) frag_pos : vec3<f32>, // planet-local position
+    @location(3) log_z    : f32,
+};
+
+struct FSOutLog {
+    @location(0) color       : vec4<f32>,
+    @builtin(frag_depth) depth : f32,
+};
+
+@fragment
+fn fs_main(in : FSIn) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Variant used in logarithmic depth mode.
+@fragment
+fn fs_main_log(in : FSIn) -> FSOutLog {
+    return FSOutLog(shade(in), log2(in.log_z) * camera.log_depth_coef);
+}
+
+/// Coverage of a line `dist` pixels away, anti-aliased over one pixel.
+fn line(dist : f32, width : f32) -> f32 {
+    let half = width * 0.5;
+    return 1.0 - smoothstep(half - 0.5, half + 0.5, dist);
+}
+
+/// Coverage of the family of lines every `spacing` degrees of `coord`
+/// (`px` degrees per pixel), faded out where they crowd together on screen.
+fn grid(coord : f32, px : f32, spacing : f32) -> f32 {
+    if (spacing <= 0.0) {
+        return 0.0;
+    }
+    let dist = abs(fract(coord / spacing + 0.5) - 0.5) * spacing;
+    let fade = smoothstep(graticule.fade_px, graticule.fade_px * 2.0, spacing / px);
+    return line(dist / px, graticule.line_width) * fade;
+}
+
+fn shade(in : FSIn) -> vec4<f32> {
+    let p   = normalize(in.frag_pos);
+    let lat = degrees(asin(clamp(p.y, -1.0, 1.0)));
+    let lon = degrees(atan2(p.z, p.x)); // same convention as picking
+
+    // atan2 jumps at ±180°; the mirrored angle jumps at 0° instead, so the
+    // smaller of the two derivatives is valid everywhere
+    let lon_flipped = degrees(atan2(-p.z, -p.x));
+    let lat_px = max(fwidth(lat), 1e-6);
+    let lon_px = max(min(fwidth(lon), fwidth(lon_flipped)), 1e-6);
+
+    let major = max(grid(lat, lat_px, graticule.spacing), grid(lon, lon_px, graticule.spacing));
+    let minor = max(grid(lat, lat_px, graticule.minor_spacing), grid(lon, lon_px, graticule.minor_spacing));
+    let regular = max(major, minor * 0.5);
+
+    let width   = graticule.line_width * 1.5;
+    let tropics = abs(abs(lat) - TROPIC_LATITUDE);
+    let special = max(
+        max(line(abs(lat) / lat_px, width), line(abs(lon) / lon_px, width)),
+        line(tropics / lat_px, width),
+    );
+
+    // special lines sit on top of the regular grid
+    let a_regular = regular * graticule.color.a;
+    let a_special = special * graticule.special_color.a;
+    let alpha = a_special + a_regular * (1.0 - a_special);
+    if (alpha <= 0.0) {
+        discard;
+    }
+    let rgb = (graticule.special_color.rgb * a_special
+        + graticule.color.rgb * a_regular * (1.0 - a_special)) / alpha;
+    return vec4<f32>(rgb, alpha);
+}
@@ -0,0 +1,67 @@
+// Instanced meshes: one small mesh drawn many times, each instance placed by
+// its own translation, rotation, scale and color from an instance buffer
+
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
+@group(0) @binding(0) var<uniform> camera : Camera;
+
+struct Light { dir : vec3<f32>, color : vec3<f32> };
+@group(0) @binding(1) var<uniform> light : Light;
+
+// Instance positions are relative to the set's origin, which is rebased on
+// the camera (in f64 on the CPU) every frame.
+struct InstanceSet { origin : vec3<f32> };
+@group(3) @binding(0) var<uniform> instances : InstanceSet;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) normal    : vec3<f32>,
+    @location(1) color     : vec4<f32>,
+    @location(3) log_z     : f32, // 1 + view depth, for logarithmic depth
+};
+
+struct FSOutLog {
+    @location(0) color       : vec4<f32>,
+    @builtin(frag_depth) depth : f32,
+};
+
+/// Rotates `v` by the unit quaternion `q` (xyzw).
+fn rotate(q : vec4<f32>, v : vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(5) translation: vec3<f32>,
+    @location(6) scale: f32,
+    @location(7) rotation: vec4<f32>,
+    @location(8) color: vec4<f32>
+) -> VSOut {
+    let view_pos = instances.origin + translation + rotate(rotation, position * scale);
+    var out: VSOut;
+    out.pos    = camera.view_proj * vec4(view_pos, 1.0);
+    out.normal = rotate(rotation, normal);
+    out.color  = color;
+    out.log_z  = 1.0 + out.pos.w;
+    return out;
+}
+
+fn shade(in : VSOut) -> vec4<f32> {
+    let N    = normalize(in.normal);
+    let L    = normalize(-light.dir);
+    let diff = max(dot(N, L), 0.0);
+    return vec4<f32>(in.color.rgb * (0.15 + diff * light.color), in.color.a);
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Variant used in logarithmic depth mode.
+@fragment
+fn fs_main_log(in : VSOut) -> FSOutLog {
+    return FSOutLog(shade(in), log2(in.log_z) * camera.log_depth_coef);
+}
@@ -0,0 +1,37 @@
+// Overlay text and icons: screen-space quads sampling a coverage atlas.
+
+@group(0) @binding(0) var atlas_texture : texture_2d<f32>;
+@group(0) @binding(1) var atlas_sampler : sampler;
+
+// set when the frame isn't an sRGB format; colors are linear
+override ENCODE_SRGB : bool = false;
+
+fn linear_to_srgb(c : vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) uv        : vec2<f32>,
+    @location(1) color     : vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position : vec2<f32>, // NDC
+    @location(1) uv       : vec2<f32>,
+    @location(2) color    : vec4<f32>
+) -> VSOut {
+    var out : VSOut;
+    out.pos   = vec4(position, 0.0, 1.0);
+    out.uv    = uv;
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas_texture, atlas_sampler, in.uv).r;
+    let rgb = select(in.color.rgb, linear_to_srgb(in.color.rgb), ENCODE_SRGB);
+    return vec4(rgb, in.color.a * coverage);
+}
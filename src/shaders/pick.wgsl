@@ -0,0 +1,21 @@
+// Object-ID pass for GPU picking: renders a 1×1 target around the cursor,
+// writing each object's id and reverse-Z depth.
+
+struct Pick { view_proj : mat4x4<f32>, object_id : u32 };
+@group(0) @binding(0) var<uniform> pick : Pick;
+
+// Same inputs as cube.vert.wgsl: positions relative to their chunk plus the
+// chunk's camera-relative offset per instance. Other meshes get a zero
+// offset, and their model matrix is part of `view_proj`.
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(3) chunk_offset: vec3<f32>
+) -> @builtin(position) vec4<f32> {
+    return pick.view_proj * vec4(position + chunk_offset, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) u32 {
+    return pick.object_id;
+}
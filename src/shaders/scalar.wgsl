@@ -0,0 +1,79 @@
+// Scalar grid drawn over the planet surface through a colormap. Runs on the
+// planet mesh (vertex stage from cube.vert.wgsl) and only passes where the
+// surface is the nearest thing in the depth buffer.
+
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
+@group(0) @binding(0) var<uniform> camera : Camera;
+
+struct Scalar {
+    min     : f32, // value at the low end of the colormap
+    max     : f32, // value at the high end
+    opacity : f32,
+};
+@group(3) @binding(0) var<uniform> scalar : Scalar;
+// row 0 is the northernmost band, column 0 starts at 180°W; NaN is no data
+@group(3) @binding(1) var data_texture : texture_2d<f32>;
+@group(3) @binding(2) var lut_texture  : texture_2d<f32>;
+@group(3) @binding(3) var lut_sampler  : sampler;
+
+const PI : f32 = 3.14159265;
+
+struct FSIn {
+    @location(0) frag_pos : vec3<f32>, // planet-local position
+    @location(3) log_z    : f32,
+};
+
+struct FSOutLog {
+    @location(0) color       : vec4<f32>,
+    @builtin(frag_depth) depth : f32,
+};
+
+@fragment
+fn fs_main(in : FSIn) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Variant used in logarithmic depth mode.
+@fragment
+fn fs_main_log(in : FSIn) -> FSOutLog {
+    return FSOutLog(shade(in), log2(in.log_z) * camera.log_depth_coef);
+}
+
+/// Grid cell value; longitude wraps around, latitude clamps at the poles.
+fn cell(x : i32, y : i32, size : vec2<i32>) -> f32 {
+    let column = ((x % size.x) + size.x) % size.x;
+    let row    = clamp(y, 0, size.y - 1);
+    return textureLoad(data_texture, vec2(column, row), 0).r;
+}
+
+fn shade(in : FSIn) -> vec4<f32> {
+    let p   = normalize(in.frag_pos);
+    let lat = asin(clamp(p.y, -1.0, 1.0));
+    let lon = atan2(p.z, p.x);
+
+    // bilinear between cell centers, leaving out cells without data
+    let size = vec2<i32>(textureDimensions(data_texture));
+    let g    = vec2((lon + PI) / (2.0 * PI), (0.5 * PI - lat) / PI) * vec2<f32>(size) - 0.5;
+    let base = vec2<i32>(floor(g));
+    let f    = fract(g);
+    var sum    = 0.0;
+    var weight = 0.0;
+    for (var i = 0; i < 4; i++) {
+        let corner = vec2(i & 1, i >> 1);
+        let value  = cell(base.x + corner.x, base.y + corner.y, size);
+        let w      = select(1.0 - f.x, f.x, corner.x == 1) * select(1.0 - f.y, f.y, corner.y == 1);
+        // rejects NaN as well as infinities
+        if (abs(value) < 3.0e38) {
+            sum    += value * w;
+            weight += w;
+        }
+    }
+    if (weight <= 0.0) {
+        return vec4(0.0);
+    }
+
+    let t     = clamp((sum / weight - scalar.min) / (scalar.max - scalar.min), 0.0, 1.0);
+    let texel = 0.5 / f32(textureDimensions(lut_texture).x);
+    let color = textureSampleLevel(lut_texture, lut_sampler, vec2(mix(texel, 1.0 - texel, t), 0.5), 0.0);
+    return vec4(color.rgb, scalar.opacity);
+}
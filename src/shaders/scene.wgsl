@@ -0,0 +1,78 @@
+// Scene graph nodes: one draw per node, each with its own model matrix from
+// a dynamically offset uniform slot, shaded by its material
+
+// Permutation, set per material (see material::Permutation)
+override BASE_COLOR_MAP : bool = false;
+override UNLIT          : bool = false;
+
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
+@group(0) @binding(0) var<uniform> camera : Camera;
+
+struct Light { dir : vec3<f32>, color : vec3<f32> };
+@group(0) @binding(1) var<uniform> light : Light;
+
+struct Factors {
+    base_color : vec4<f32>, // multiplies the base color map
+    emissive   : vec3<f32>,
+};
+@group(1) @binding(0) var<uniform> material : Factors;
+@group(1) @binding(1) var base_color_map     : texture_2d<f32>;
+@group(1) @binding(2) var base_color_sampler : sampler;
+
+// The model matrix is rebased on the camera (in f64 on the CPU) every frame.
+struct Object {
+    model  : mat4x4<f32>,
+    normal : mat3x3<f32>, // inverse transpose of the model's upper 3x3
+};
+@group(2) @binding(0) var<uniform> object : Object;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) normal    : vec3<f32>,
+    @location(2) uv        : vec2<f32>,
+    @location(3) log_z     : f32, // 1 + view depth, for logarithmic depth
+};
+
+struct FSOutLog {
+    @location(0) color       : vec4<f32>,
+    @builtin(frag_depth) depth : f32,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>
+) -> VSOut {
+    var out: VSOut;
+    out.pos    = camera.view_proj * object.model * vec4(position, 1.0);
+    out.normal = object.normal * normal;
+    out.uv     = uv;
+    out.log_z  = 1.0 + out.pos.w;
+    return out;
+}
+
+fn shade(in : VSOut) -> vec4<f32> {
+    var base = material.base_color;
+    if (BASE_COLOR_MAP) {
+        base *= textureSample(base_color_map, base_color_sampler, in.uv);
+    }
+    if (UNLIT) {
+        return vec4<f32>(base.rgb + material.emissive, base.a);
+    }
+    let N    = normalize(in.normal);
+    let L    = normalize(-light.dir);
+    let diff = max(dot(N, L), 0.0);
+    return vec4<f32>(base.rgb * (0.15 + diff * light.color) + material.emissive, base.a);
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Variant used in logarithmic depth mode.
+@fragment
+fn fs_main_log(in : VSOut) -> FSOutLog {
+    return FSOutLog(shade(in), log2(in.log_z) * camera.log_depth_coef);
+}
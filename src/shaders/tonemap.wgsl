@@ -0,0 +1,111 @@
+// Exposure and tonemapping: HDR scene (plus bloom) to display range. Writes
+// linear colors; an sRGB target encodes them, otherwise ENCODE_SRGB does.
+
+struct Post {
+    exposure         : f32, // linear scale
+    tonemapper       : u32,
+    bloom_intensity  : f32, // 0 while bloom is off
+    bloom_threshold  : f32,
+    bloom_knee       : f32,
+    grading_strength : f32,
+};
+
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var input_sampler : sampler;
+@group(0) @binding(2) var<uniform> post : Post;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) uv        : vec2<f32>,
+};
+
+// single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) i : u32) -> VSOut {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    var out: VSOut;
+    out.pos = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv  = vec2<f32>(uv.x, 1.0 - uv.y); // texture space, y down
+    return out;
+}
+
+fn sample(uv : vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(input_texture, input_sampler, uv, 0.0).rgb;
+}
+@group(0) @binding(3) var bloom_texture : texture_2d<f32>;
+
+// set for pipelines writing a non-sRGB output, which then encode themselves
+override ENCODE_SRGB : bool = false;
+
+fn linear_to_srgb(c : vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+fn output(c : vec3<f32>) -> vec4<f32> {
+    return vec4(select(c, linear_to_srgb(c), ENCODE_SRGB), 1.0);
+}
+
+// must match Tonemapper::shader_index
+const TONEMAP_NONE     : u32 = 0u;
+const TONEMAP_REINHARD : u32 = 1u;
+const TONEMAP_ACES     : u32 = 2u;
+const TONEMAP_AGX      : u32 = 3u;
+
+/// Stephen Hill's fit of the ACES reference rendering and output
+/// transforms.
+fn aces(color : vec3<f32>) -> vec3<f32> {
+    let input = mat3x3<f32>(
+        0.59719, 0.07600, 0.02840,
+        0.35458, 0.90834, 0.13383,
+        0.04823, 0.01566, 0.83777,
+    );
+    let output = mat3x3<f32>(
+         1.60475, -0.10208, -0.00327,
+        -0.53108,  1.10813, -0.07276,
+        -0.07367, -0.00605,  1.07602,
+    );
+    let v = input * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return output * (a / b);
+}
+
+/// Minimal AgX with the default look (Benjamin Wrensch's polynomial fit of
+/// Troy Sobotka's curve).
+fn agx(color : vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094,  0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772,  0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3<f32>(
+         1.19687900512017,  -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368,  1.15190312990417,  -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433,  1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var x = clamp(log2(max(inset * color, vec3(1e-10))), vec3(min_ev), vec3(max_ev));
+    x = (x - min_ev) / (max_ev - min_ev);
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    x = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+    // the curve produces display-encoded values; back to linear for the target
+    return pow(max(outset * x, vec3(0.0)), vec3(2.2));
+}
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    let bloom = textureSampleLevel(bloom_texture, input_sampler, in.uv, 0.0).rgb;
+    let color = (sample(in.uv) + bloom * post.bloom_intensity) * post.exposure;
+
+    var mapped : vec3<f32>;
+    switch post.tonemapper {
+        case TONEMAP_REINHARD: { mapped = color / (1.0 + color); }
+        case TONEMAP_ACES:     { mapped = aces(color); }
+        case TONEMAP_AGX:      { mapped = agx(color); }
+        default:               { mapped = color; }
+    }
+    return output(clamp(mapped, vec3(0.0), vec3(1.0)));
+}
@@ -0,0 +1,88 @@
+// GeoJSON overlays: fills, great-circle lines and point billboards. Lines and
+// points are expanded in screen space so they keep their pixel size.
+
+struct Camera { view_proj : mat4x4<f32>, log_depth_coef : f32 };
+@group(0) @binding(0) var<uniform> camera : Camera;
+
+// The planet node's model matrix, rebased on the camera every frame.
+struct Object {
+    model  : mat4x4<f32>,
+    normal : mat3x3<f32>, // inverse transpose of the model's upper 3x3
+};
+@group(2) @binding(0) var<uniform> object : Object;
+
+struct Vector { viewport : vec2<f32> };
+@group(3) @binding(0) var<uniform> vector : Vector;
+
+const KIND_LINE  : u32 = 1u;
+const KIND_POINT : u32 = 2u;
+
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0) color     : vec4<f32>,
+    @location(1) corner    : vec2<f32>, // points: -1..1 across the billboard
+    @location(2) @interpolate(flat) kind : u32,
+    @location(3) log_z     : f32,
+};
+
+fn clip(position : vec3<f32>) -> vec4<f32> {
+    // the model matrix carries the camera-relative planet position
+    return camera.view_proj * (object.model * vec4(position, 1.0));
+}
+
+@vertex
+fn vs_main(
+    @location(0) position : vec3<f32>,
+    @location(1) other    : vec3<f32>,
+    @location(2) offset   : vec2<f32>,
+    @location(3) color    : vec4<f32>,
+    @location(4) kind     : u32
+) -> VSOut {
+    var pos = clip(position);
+    var px  = offset;
+    if (kind == KIND_LINE) {
+        // perpendicular to the segment on screen
+        let far = clip(other);
+        let dir = normalize((far.xy / far.w - pos.xy / pos.w) * vector.viewport + vec2(1e-9, 0.0));
+        px = vec2(-dir.y, dir.x) * offset.y;
+    }
+    pos = vec4(pos.xy + px * 2.0 / vector.viewport * pos.w, pos.zw);
+
+    var out : VSOut;
+    out.pos    = pos;
+    out.color  = color;
+    out.corner = sign(offset);
+    out.kind   = kind;
+    out.log_z  = 1.0 + pos.w;
+    return out;
+}
+
+struct FSOutLog {
+    @location(0) color       : vec4<f32>,
+    @builtin(frag_depth) depth : f32,
+};
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Variant used in logarithmic depth mode.
+@fragment
+fn fs_main_log(in : VSOut) -> FSOutLog {
+    return FSOutLog(shade(in), log2(in.log_z) * camera.log_depth_coef);
+}
+
+fn shade(in : VSOut) -> vec4<f32> {
+    // derivatives have to be taken in uniform control flow
+    let r     = length(in.corner);
+    let edge  = fwidth(r);
+    var alpha = in.color.a;
+    if (in.kind == KIND_POINT) {
+        alpha *= 1.0 - smoothstep(1.0 - edge, 1.0, r); // round dot, soft edge
+    }
+    if (alpha <= 0.0) {
+        discard;
+    }
+    return vec4(in.color.rgb, alpha);
+}
@@ -0,0 +1,101 @@
+use glam::{DVec3, Quat};
+
+use crate::gpu::instancing::{Instance, InstanceId, InstancedMesh};
+
+/// A circular orbit around the planet center.
+struct Orbit {
+    radius: f64,
+    axis: DVec3,  // normal of the orbital plane
+    start: DVec3, // unit direction at angle 0, perpendicular to `axis`
+    speed: f64,   // radians per second
+    phase: f64,
+}
+
+/// Satellites on random circular orbits, moved every frame through the
+/// instancing API.
+pub struct Swarm {
+    satellites: Vec<(InstanceId, Orbit)>,
+    center: DVec3,
+    size: f32,
+    colors: [[f32; 4]; 3],
+}
+
+impl Swarm {
+    /// Adds `count` satellites to `instances`, between 1.1 and 2 planet
+    /// radii from `center`.
+    pub fn spawn(instances: &mut InstancedMesh, center: DVec3, planet_radius: f64, count: usize) -> Self {
+        let mut rng = SplitMix64(0x5eed);
+        let mut swarm = Swarm {
+            satellites: Vec::with_capacity(count),
+            center,
+            size: (planet_radius * 0.004) as f32,
+            colors: [[0.9, 0.9, 0.95, 1.0], [1.0, 0.75, 0.3, 1.0], [0.4, 0.7, 1.0, 1.0]],
+        };
+        for i in 0..count {
+            let axis = rng.unit_vector();
+            let start = axis.any_orthonormal_vector();
+            let radius = planet_radius * (1.1 + 0.9 * rng.next_f64().powi(2)); // denser low down
+            let orbit = Orbit {
+                radius,
+                axis,
+                start,
+                // Kepler: angular speed falls off with radius^1.5
+                speed: 0.2 * (planet_radius / radius).powf(1.5),
+                phase: rng.next_f64() * std::f64::consts::TAU,
+            };
+            let id = instances.add(swarm.instance(&orbit, 0.0, i));
+            swarm.satellites.push((id, orbit));
+        }
+        swarm
+    }
+
+    /// Moves every satellite to where it is at `time` seconds.
+    pub fn update(&self, instances: &mut InstancedMesh, time: f64) {
+        for (i, (id, orbit)) in self.satellites.iter().enumerate() {
+            instances.update(*id, self.instance(orbit, time, i));
+        }
+    }
+
+    pub fn despawn(self, instances: &mut InstancedMesh) {
+        for (id, _) in self.satellites {
+            instances.remove(id);
+        }
+    }
+
+    fn instance(&self, orbit: &Orbit, time: f64, index: usize) -> Instance {
+        let angle = orbit.phase + orbit.speed * time;
+        let rotation = Quat::from_axis_angle(orbit.axis.as_vec3(), angle as f32);
+        let offset = orbit.start * angle.cos() + orbit.axis.cross(orbit.start) * angle.sin();
+        Instance {
+            position: self.center + offset * orbit.radius,
+            rotation,
+            scale: self.size,
+            color: self.colors[index % self.colors.len()],
+        }
+    }
+}
+
+/// Small deterministic generator, so the swarm looks the same every run.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn unit_vector(&mut self) -> DVec3 {
+        let z = self.next_f64() * 2.0 - 1.0;
+        let angle = self.next_f64() * std::f64::consts::TAU;
+        let r = (1.0 - z * z).sqrt();
+        DVec3::new(r * angle.cos(), r * angle.sin(), z)
+    }
+}
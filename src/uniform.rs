@@ -7,8 +7,10 @@ pub struct Uniforms {
     _pad0: f32,              // 4 bytes  @ offset 4 (align next field at 8)
     pub center: [f32; 2],    // 8 bytes  @ offset 8
     pub zoom: f32,           // 4 bytes  @ offset 16
-    _pad1: f32,              // 4 bytes  @ offset 20
-} // total size = 24 bytes
+    pub debug_view: u32,     // 4 bytes  @ offset 20, see DebugView::shader_index
+    pub depth_near: f32,     // 4 bytes  @ offset 24, view depth shown white in the depth view
+    pub depth_far: f32,      // 4 bytes  @ offset 28, … and black
+} // total size = 32 bytes
 
 impl Uniforms {
     pub fn new() -> Self {
@@ -17,7 +19,27 @@ impl Uniforms {
             _pad0: 0.0,
             center: [-0.0, -1.0],
             zoom: 1.0,
-            _pad1: 0.0,
+            debug_view: 0,
+            depth_near: 0.0,
+            depth_far: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct CameraUniforms {
+    pub view_proj: [[f32; 4]; 4], // 64 bytes @ offset 0
+    pub log_depth_coef: f32,      //  4 bytes @ offset 64 (only read in log-depth mode)
+    _pad: [f32; 3],               // 12 bytes @ offset 68
+} // total size = 80 bytes
+
+impl CameraUniforms {
+    pub fn new(view_proj: glam::Mat4, log_depth_coef: f32) -> Self {
+        Self {
+            view_proj: view_proj.to_cols_array_2d(),
+            log_depth_coef,
+            _pad: [0.0; 3],
         }
     }
 }
@@ -0,0 +1,387 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use bytemuck::{Pod, Zeroable};
+use geojson::{GeoJson, Geometry, Position, Value};
+use glam::{DVec2, DVec3};
+use serde::Deserialize;
+
+use crate::controller::direction;
+
+/// Height of overlays above the surface, in planet radii; clears the sag of
+/// tessellated chords and of the LOD mesh between its vertices.
+const ALTITUDE: f64 = 5e-4;
+const LINE_STEP: f64 = 1.0; // degrees of great circle per line segment
+const FILL_STEP: f64 = 2.0; // longest fill triangle edge, degrees of great circle
+const MAX_SUBDIVISIONS: u32 = 8;
+/// Fills reach at most ~85° from their projection center, where the gnomonic
+/// projection is still well conditioned.
+const MIN_PROJECTION_COS: f64 = 0.08;
+
+pub const KIND_FILL: u32 = 0;
+pub const KIND_LINE: u32 = 1;
+pub const KIND_POINT: u32 = 2;
+
+/// Overlay geometry, expanded to constant screen size in the vertex shader.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct VectorVertex {
+    pub position: [f32; 3], // planet-local
+    pub other:    [f32; 3], // lines: the far end of the segment, to extrude across
+    pub offset:   [f32; 2], // pixels: lines (0, signed half width), points (corner * radius)
+    pub color:    [f32; 4],
+    pub kind:     u32,      // KIND_*
+}
+
+impl VectorVertex {
+    pub const ATTRIBS: [wgpu::VertexAttribute; 5] =
+        wgpu::vertex_attr_array![
+            0 => Float32x3,  // position
+            1 => Float32x3,  // other
+            2 => Float32x2,  // offset
+            3 => Float32x4,  // color
+            4 => Uint32      // kind
+        ];
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<VectorVertex>() as _,
+            step_mode:    wgpu::VertexStepMode::Vertex,
+            attributes:   &Self::ATTRIBS,
+        }
+    }
+}
+
+/// How one layer is drawn. Colors are linear RGBA.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LayerStyle {
+    pub line_color: [f32; 4],
+    pub line_width: f32,             // pixels, 0 hides lines and outlines
+    pub fill_color: Option<[f32; 4]>, // polygons are only outlined without one
+    pub point_color: [f32; 4],
+    pub point_size: f32,             // pixels
+}
+
+impl Default for LayerStyle {
+    fn default() -> Self {
+        Self {
+            line_color: [1.0, 1.0, 1.0, 1.0],
+            line_width: 1.5,
+            fill_color: None,
+            point_color: [1.0, 0.3, 0.2, 1.0],
+            point_size: 8.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayerConfig {
+    pub name: String,
+    pub path: PathBuf, // relative to the config file
+    #[serde(flatten)]
+    pub style: LayerStyle,
+}
+
+#[derive(Deserialize)]
+struct OverlayFile {
+    #[serde(default, rename = "layer")]
+    layers: Vec<LayerConfig>,
+}
+
+/// Reads the layer list; a missing file just means no layers.
+pub fn load_layers(path: &Path) -> Result<Vec<LayerConfig>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+    let file: OverlayFile = toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(file
+        .layers
+        .into_iter()
+        .map(|layer| LayerConfig { path: base.join(&layer.path), ..layer })
+        .collect())
+}
+
+/// Loads and tessellates one layer's GeoJSON file.
+pub fn load_layer(layer: &LayerConfig, radius: f64) -> Result<Vec<VectorVertex>> {
+    let text = std::fs::read_to_string(&layer.path)
+        .with_context(|| format!("reading {}", layer.path.display()))?;
+    let geojson: GeoJson = text.parse().with_context(|| format!("parsing {}", layer.path.display()))?;
+    tessellate(&geojson, &layer.style, radius).with_context(|| format!("in {}", layer.path.display()))
+}
+
+/// Triangles for every geometry in `geojson`: fills first, then lines, then
+/// points, so nothing is hidden under a translucent fill.
+pub fn tessellate(geojson: &GeoJson, style: &LayerStyle, radius: f64) -> Result<Vec<VectorVertex>> {
+    let mut tessellator = Tessellator {
+        style,
+        radius: radius * (1.0 + ALTITUDE),
+        fills: Vec::new(),
+        lines: Vec::new(),
+        points: Vec::new(),
+    };
+    match geojson {
+        GeoJson::Geometry(geometry) => tessellator.geometry(geometry)?,
+        GeoJson::Feature(feature) => {
+            if let Some(geometry) = &feature.geometry {
+                tessellator.geometry(geometry)?;
+            }
+        }
+        GeoJson::FeatureCollection(collection) => {
+            for geometry in collection.features.iter().filter_map(|f| f.geometry.as_ref()) {
+                tessellator.geometry(geometry)?;
+            }
+        }
+    }
+    let Tessellator { mut fills, lines, points, .. } = tessellator;
+    fills.extend(lines);
+    fills.extend(points);
+    Ok(fills)
+}
+
+struct Tessellator<'a> {
+    style: &'a LayerStyle,
+    radius: f64,
+    fills: Vec<VectorVertex>,
+    lines: Vec<VectorVertex>,
+    points: Vec<VectorVertex>,
+}
+
+impl Tessellator<'_> {
+    fn geometry(&mut self, geometry: &Geometry) -> Result<()> {
+        match &geometry.value {
+            Value::Point(p) => self.point(lon_lat(p)?),
+            Value::MultiPoint(points) => {
+                for p in points {
+                    self.point(lon_lat(p)?);
+                }
+            }
+            Value::LineString(line) => self.line(&ring(line)?),
+            Value::MultiLineString(lines) => {
+                for line in lines {
+                    self.line(&ring(line)?);
+                }
+            }
+            Value::Polygon(rings) => self.polygon(rings)?,
+            Value::MultiPolygon(polygons) => {
+                for rings in polygons {
+                    self.polygon(rings)?;
+                }
+            }
+            Value::GeometryCollection(geometries) => {
+                for geometry in geometries {
+                    self.geometry(geometry)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn on_sphere(&self, unit: DVec3) -> [f32; 3] {
+        (unit * self.radius).as_vec3().to_array()
+    }
+
+    fn point(&mut self, lon_lat: DVec2) {
+        let position = self.on_sphere(unit(lon_lat));
+        let radius = self.style.point_size * 0.5;
+        let corner = |x: f32, y: f32| VectorVertex {
+            position,
+            other: position,
+            offset: [x * radius, y * radius],
+            color: self.style.point_color,
+            kind: KIND_POINT,
+        };
+        let quad = [corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0)];
+        self.points.extend([0, 1, 2, 0, 2, 3].map(|i| quad[i]));
+    }
+
+    /// A polyline following great circles between its points.
+    fn line(&mut self, lon_lats: &[DVec2]) {
+        if self.style.line_width <= 0.0 {
+            return;
+        }
+        let half = self.style.line_width * 0.5;
+        for pair in lon_lats.windows(2) {
+            let (a, b) = (unit(pair[0]), unit(pair[1]));
+            let steps = (a.angle_between(b).to_degrees() / LINE_STEP).ceil().max(1.0) as u32;
+            for i in 0..steps {
+                let p = self.on_sphere(a.slerp(b, i as f64 / steps as f64));
+                let q = self.on_sphere(a.slerp(b, (i + 1) as f64 / steps as f64));
+                // the extrusion flips with the segment direction, so the far
+                // end's offsets are negated to stay on the same side
+                let corner = |position, other, side: f32| VectorVertex {
+                    position,
+                    other,
+                    offset: [0.0, side * half],
+                    color: self.style.line_color,
+                    kind: KIND_LINE,
+                };
+                let (p_left, p_right) = (corner(p, q, 1.0), corner(p, q, -1.0));
+                let (q_left, q_right) = (corner(q, p, -1.0), corner(q, p, 1.0));
+                self.lines.extend([p_left, p_right, q_left, q_left, p_right, q_right]);
+            }
+        }
+    }
+
+    fn polygon(&mut self, rings: &[Vec<Position>]) -> Result<()> {
+        let mut outlines = Vec::with_capacity(rings.len());
+        for positions in rings {
+            let mut points = ring(positions)?;
+            if points.len() > 1 && points.first() == points.last() {
+                points.pop(); // GeoJSON repeats the first point
+            }
+            if points.len() < 3 {
+                bail!("polygon ring with fewer than 3 points");
+            }
+            outlines.push(points);
+        }
+
+        if let Some(color) = self.style.fill_color {
+            self.fill_polygon(&outlines, color)?;
+        }
+        for mut outline in outlines {
+            outline.push(outline[0]);
+            self.line(&outline);
+        }
+        Ok(())
+    }
+
+    /// Triangulates in a gnomonic projection around the polygon, where great
+    /// circles are straight lines, so triangle edges follow the rings' edges.
+    fn fill_polygon(&mut self, rings: &[Vec<DVec2>], color: [f32; 4]) -> Result<()> {
+        let center = projection_center(&rings[0]).context("polygon ring has no center")?;
+        let (u, v) = center.any_orthonormal_pair();
+        let mut points: Vec<DVec3> = Vec::new();
+        let mut coords = Vec::new();
+        let mut holes = Vec::new();
+        for (i, lon_lats) in rings.iter().enumerate() {
+            if i > 0 {
+                holes.push(points.len());
+            }
+            let start = points.len();
+            for p in lon_lats.iter().map(|&lon_lat| unit(lon_lat)) {
+                // rings through a pole list it once per meridian
+                if points.len() > start && points[points.len() - 1].distance(p) < 1e-12 {
+                    continue;
+                }
+                let cos = p.dot(center);
+                if cos < MIN_PROJECTION_COS {
+                    bail!("polygon reaches too far from its center to fill; split it");
+                }
+                coords.extend([p.dot(u) / cos, p.dot(v) / cos]);
+                points.push(p);
+            }
+        }
+
+        let indices = earcutr::earcut(&coords, &holes, 2).context("triangulating polygon")?;
+        for tri in indices.chunks_exact(3) {
+            self.fill([points[tri[0]], points[tri[1]], points[tri[2]]], color, 0);
+        }
+        Ok(())
+    }
+
+    /// Splits a spherical triangle at its edges' great-circle midpoints until
+    /// it hugs the sphere, then emits it.
+    fn fill(&mut self, tri: [DVec3; 3], color: [f32; 4], depth: u32) {
+        let longest = (0..3).map(|i| tri[i].angle_between(tri[(i + 1) % 3])).fold(0.0, f64::max);
+        if longest > FILL_STEP.to_radians() && depth < MAX_SUBDIVISIONS {
+            let [a, b, c] = tri;
+            let mid = |p: DVec3, q: DVec3| (p + q).normalize();
+            let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+            for sub in [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]] {
+                self.fill(sub, color, depth + 1);
+            }
+            return;
+        }
+        for unit in tri {
+            let position = self.on_sphere(unit);
+            self.fills.push(VectorVertex { position, other: position, offset: [0.0; 2], color, kind: KIND_FILL });
+        }
+    }
+}
+
+/// GeoJSON positions are `[longitude, latitude, (altitude)]` in degrees.
+fn lon_lat(position: &[f64]) -> Result<DVec2> {
+    match position {
+        [lon, lat, ..] => Ok(DVec2::new(*lon, *lat)),
+        _ => bail!("position with fewer than 2 coordinates"),
+    }
+}
+
+fn ring(positions: &[Position]) -> Result<Vec<DVec2>> {
+    positions.iter().map(|p| lon_lat(p)).collect()
+}
+
+/// Same convention as picking: longitude is the orbit yaw, latitude the pitch.
+fn unit(lon_lat: DVec2) -> DVec3 {
+    direction(lon_lat.x.to_radians(), lon_lat.y.to_radians())
+}
+
+/// Where to center the fill projection of a polygon with this outer ring: the
+/// pole it winds around, if any, otherwise the mean of its points.
+fn projection_center(lon_lats: &[DVec2]) -> Option<DVec3> {
+    let winding: f64 = lon_lats
+        .iter()
+        .zip(lon_lats.iter().cycle().skip(1))
+        .map(|(a, b)| (b.x - a.x) - ((b.x - a.x) / 360.0).round() * 360.0)
+        .sum();
+    if winding.abs() > 180.0 {
+        let mean_lat = lon_lats.iter().map(|p| p.y).sum::<f64>() / lon_lats.len() as f64;
+        return Some(unit(DVec2::new(0.0, 90.0f64.copysign(mean_lat))));
+    }
+    lon_lats.iter().map(|&lon_lat| unit(lon_lat)).sum::<DVec3>().try_normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(rings: &str) -> Result<Vec<DVec3>> {
+        let geojson: GeoJson = format!(r#"{{"type": "Polygon", "coordinates": {rings}}}"#).parse()?;
+        let style = LayerStyle { fill_color: Some([1.0; 4]), line_width: 0.0, ..Default::default() };
+        let vertices = tessellate(&geojson, &style, 1.0)?;
+        Ok(vertices.iter().map(|v| DVec3::from(v.position.map(f64::from)).normalize()).collect())
+    }
+
+    /// Area of a spherical triangle, from its spherical excess.
+    fn area(tri: &[DVec3]) -> f64 {
+        let [a, b, c] = [tri[0], tri[1], tri[2]];
+        2.0 * a.dot(b.cross(c)).abs().atan2(1.0 + a.dot(b) + b.dot(c) + c.dot(a))
+    }
+
+    #[test]
+    fn fills_follow_great_circles() {
+        // the top edge runs from 60°N to 60°N: as a great circle it bulges
+        // towards the pole, a rhumb line would stay on the parallel
+        let vertices = fill("[[[0, 0], [40, 0], [40, 60], [0, 60], [0, 0]]]").unwrap();
+        for tri in vertices.chunks_exact(3) {
+            for i in 0..3 {
+                assert!(tri[i].angle_between(tri[(i + 1) % 3]).to_degrees() <= FILL_STEP + 1e-6);
+            }
+        }
+        let highest = vertices.iter().map(|p| p.y).fold(f64::MIN, f64::max);
+        assert!(highest.asin().to_degrees() > 61.0, "top edge peaks at {}", highest.asin().to_degrees());
+    }
+
+    #[test]
+    fn fills_rings_around_a_pole() {
+        // a cap south of 70°S, given the way global datasets do: along the
+        // parallel from -180 to 180, then out to the pole and back
+        let parallel: Vec<String> = (0..=36).map(|i| format!("[{}, -70]", -180 + i * 10)).collect();
+        let ring = format!("[[{}, [180, -90], [-180, -90], [-180, -70]]]", parallel.join(", "));
+        let vertices = fill(&ring).unwrap();
+
+        let cap = 2.0 * std::f64::consts::PI * (1.0 - 20f64.to_radians().cos());
+        let total: f64 = vertices.chunks_exact(3).map(area).sum();
+        assert!((total - cap).abs() < cap * 0.02, "filled {total}, cap is {cap}");
+        assert!(vertices.iter().all(|p| p.y < -0.9));
+    }
+
+    #[test]
+    fn rejects_fills_larger_than_a_hemisphere() {
+        let ring = "[[[-85, -60], [85, -60], [85, 60], [-85, 60], [-85, -60]]]";
+        assert!(fill(ring).is_err());
+    }
+}
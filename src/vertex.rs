@@ -1,4 +1,5 @@
 use bytemuck::{Pod, Zeroable};
+use glam::DVec3;
 
 #[repr(C)]
 #[derive(Copy, Clone, Pod, Zeroable)]
@@ -71,3 +72,59 @@ pub const INDICES: &[u16] = &[
     16, 17, 18, 16, 18, 19,   // +Z
     20, 21, 22, 20, 22, 23,   // -Z
 ];
+
+/// Point on cube face `face` (0‥6, in `VERTICES` order) at face coords `s, t` ∈ [0,1].
+/// Done in f64 so planet-sized meshes built from it stay precise.
+///
+/// The face corners go round the quad (c0 opposite c2), so blending c0→c3 and
+/// c1→c2 keeps the face's winding.
+pub fn face_point(face: usize, s: f64, t: f64) -> DVec3 {
+    let [c0, c1, c2, c3] = [0, 1, 2, 3].map(|i| DVec3::from(VERTICES[face * 4 + i].position.map(f64::from)));
+    let bottom = c0.lerp(c3, s);
+    let top    = c1.lerp(c2, s);
+    bottom.lerp(top, t)
+}
+
+/// Triangulates an `n`×`n` grid of vertices laid out row by row (rows along t),
+/// using the same triangle order as the cube faces.
+pub fn grid_indices(n: u16, base: u16, indices: &mut Vec<u16>) {
+    // every face is triangulated the same way, so reuse the first face's pattern
+    let pattern = &INDICES[..6];
+    let row = n + 1;
+    for j in 0..n {
+        for i in 0..n {
+            let a = base + j * row + i;
+            let b = a + row;
+            let corners = [a, b, b + 1, a + 1]; // c0 c1 c2 c3 of this grid quad
+            indices.extend(pattern.iter().map(|&k| corners[k as usize]));
+        }
+    }
+}
+
+/// Builds a cube-sphere from the cube faces above: every face is split into
+/// `subdivisions`² quads and each vertex is pushed out onto the unit sphere.
+pub fn cube_sphere(subdivisions: u16) -> (Vec<Vertex>, Vec<u16>) {
+    let n = subdivisions.max(1);
+    let mut vertices = Vec::with_capacity(6 * (n as usize + 1).pow(2));
+    let mut indices  = Vec::with_capacity(6 * 6 * (n as usize).pow(2));
+
+    for face in 0..6 {
+        let base = vertices.len() as u16;
+
+        for j in 0..=n {
+            for i in 0..=n {
+                let (s, t) = (i as f32 / n as f32, j as f32 / n as f32);
+                let dir = face_point(face, s as f64, t as f64).normalize().as_vec3();
+                vertices.push(Vertex {
+                    position: dir.to_array(),
+                    normal:   dir.to_array(),
+                    uv:       [s, t],
+                });
+            }
+        }
+
+        grid_indices(n, base, &mut indices);
+    }
+
+    (vertices, indices)
+}